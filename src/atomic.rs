@@ -0,0 +1,84 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+
+/// Where [`AtomicFile`] writes before renaming into `path`: a hidden file next to it,
+/// so the rename stays on one filesystem
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.yek-tmp-{}", name, std::process::id()))
+}
+
+/// A file that only appears at its path once it is complete. Writes go to a temporary
+/// file that [`AtomicFile::commit`] renames into place; if the file is dropped without
+/// being committed (an error part way through), the temporary file is removed and
+/// whatever was at the path before is left untouched.
+pub struct AtomicFile {
+    file: Option<File>,
+    temp: PathBuf,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let temp = temp_path(&path);
+        let file = File::create(&temp)
+            .map_err(|e| anyhow!("output: cannot create '{}': {}", temp.display(), e))?;
+        Ok(Self {
+            file: Some(file),
+            temp,
+            path,
+        })
+    }
+
+    /// Final path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Flush everything to disk and move the file into place
+    pub fn commit(mut self) -> Result<()> {
+        if let Some(file) = &self.file {
+            file.sync_all()?;
+        }
+        // Close the file before moving it; dropping `self` now leaves the temp path alone
+        self.file = None;
+        fs::rename(&self.temp, &self.path).map_err(|e| {
+            let _ = fs::remove_file(&self.temp);
+            anyhow!("output: cannot write '{}': {}", self.path.display(), e)
+        })
+    }
+
+    fn file(&mut self) -> io::Result<&mut File> {
+        self.file
+            .as_mut()
+            .ok_or_else(|| io::Error::other("file already committed"))
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file()?.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        // Not committed: throw the partial output away
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
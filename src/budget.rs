@@ -0,0 +1,44 @@
+use anyhow::{anyhow, Result};
+
+/// A `--budget <glob>:<percent>` rule: matching files are guaranteed `percent` of the
+/// output budget before the rest of it is filled in output order
+#[derive(Debug, Clone)]
+pub struct BudgetRule {
+    pub pattern: glob::Pattern,
+    pub percent: usize,
+}
+
+/// Parse a `<glob>:<percent>` spec, with or without a trailing `%`. The percentage
+/// follows the last colon, so globs may themselves contain colons.
+pub fn parse_budget_rule(spec: &str) -> Result<BudgetRule> {
+    let (glob, percent) = spec
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("budget: Invalid rule '{}': expected <glob>:<percent>", spec))?;
+    let percent = percent
+        .trim()
+        .trim_end_matches('%')
+        .parse::<usize>()
+        .map_err(|e| anyhow!("budget: Invalid percentage in '{}': {}", spec, e))?;
+    if percent == 0 || percent > 100 {
+        return Err(anyhow!(
+            "budget: Percentage in '{}' must be between 1 and 100",
+            spec
+        ));
+    }
+    let pattern = glob::Pattern::new(glob.trim())
+        .map_err(|e| anyhow!("budget: Invalid pattern in '{}': {}", spec, e))?;
+    Ok(BudgetRule { pattern, percent })
+}
+
+/// Index of the rule a file's reservation comes from: the first one that matches
+pub fn budget_group(path: &str, rules: &[BudgetRule]) -> Option<usize> {
+    rules.iter().position(|rule| rule.pattern.matches(path))
+}
+
+/// Amount reserved for each rule out of `total`, rounded down
+pub fn reservations(total: usize, rules: &[BudgetRule]) -> Vec<usize> {
+    rules
+        .iter()
+        .map(|rule| (total as u128 * rule.percent as u128 / 100) as usize)
+        .collect()
+}
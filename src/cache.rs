@@ -0,0 +1,237 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{config::YekConfig, models::Tokenizer};
+
+/// Name of the cache file inside the cache directory
+pub const CACHE_FILE: &str = "token-cache.json";
+
+/// Bump when the layout of the cache file changes; older files are then ignored
+const CACHE_VERSION: u32 = 1;
+
+/// Counts kept per file. The same file is measured once per template and position
+/// in the output, so this bounds how many of those variants are remembered.
+const MAX_COUNTS_PER_FILE: usize = 8;
+
+/// The cache directory used when --cache-dir is not given: `$XDG_CACHE_HOME/yek`,
+/// else `~/.cache/yek` (`%LOCALAPPDATA%\yek` on Windows)
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("yek"))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: HashMap<String, CacheEntry>,
+}
+
+/// What is remembered about one file: its size and modification time when it was measured,
+/// and token counts of text rendered from it, keyed by tokenizer and a hash of the text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    counts: Vec<(String, usize)>,
+}
+
+/// Token counts remembered between runs, so files that have not changed are not tokenized
+/// again. Entries are keyed by path and dropped once the file's size or modification time
+/// changes. Each count is also keyed by a hash of the text that was measured, so a changed
+/// template or position in the output never reuses a count for different text.
+/// Languages are not stored: looking them up from the file name is cheaper than the cache,
+/// and a stored one would go stale when `--language-map` changes.
+#[derive(Debug)]
+pub struct TokenCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl TokenCache {
+    /// The cache stored in `dir`. A missing, unreadable or outdated cache file starts empty.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(CACHE_FILE);
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheFile>(&bytes).ok())
+            .filter(|file| file.version == CACHE_VERSION)
+            .map(|file| file.entries)
+            .unwrap_or_default();
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// Number of files with remembered counts
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CacheEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Tokens in `text`, rendered from the file at `disk_path`: the remembered count if the
+    /// file is unchanged and the same text was measured before, else `count(text)`, which is
+    /// then remembered. Files that cannot be stat'ed are always counted.
+    pub fn tokens<F>(&self, disk_path: &Path, text: &str, tokenizer: Tokenizer, count: F) -> usize
+    where
+        F: FnOnce(&str) -> usize,
+    {
+        let Some((key, size, mtime_secs, mtime_nanos)) = file_stamp(disk_path) else {
+            return count(text);
+        };
+        let text_key = format!("{:?}:{}", tokenizer, text_hash(text));
+
+        let remembered = self.lock().get(&key).and_then(|entry| {
+            (entry.size == size
+                && entry.mtime_secs == mtime_secs
+                && entry.mtime_nanos == mtime_nanos)
+                .then(|| entry.counts.iter().find(|(k, _)| *k == text_key))
+                .flatten()
+                .map(|(_, tokens)| *tokens)
+        });
+        if let Some(tokens) = remembered {
+            return tokens;
+        }
+
+        // Count outside the lock so other threads keep going
+        let tokens = count(text);
+        let mut entries = self.lock();
+        let entry = entries.entry(key).or_insert_with(|| CacheEntry {
+            size,
+            mtime_secs,
+            mtime_nanos,
+            counts: Vec::new(),
+        });
+        if (entry.size, entry.mtime_secs, entry.mtime_nanos) != (size, mtime_secs, mtime_nanos) {
+            *entry = CacheEntry {
+                size,
+                mtime_secs,
+                mtime_nanos,
+                counts: Vec::new(),
+            };
+        }
+        if !entry.counts.iter().any(|(k, _)| *k == text_key) {
+            if entry.counts.len() >= MAX_COUNTS_PER_FILE {
+                entry.counts.remove(0);
+            }
+            entry.counts.push((text_key, tokens));
+        }
+        tokens
+    }
+
+    /// Write the cache back to disk, leaving out files that no longer exist
+    pub fn save(&self) -> Result<()> {
+        let mut entries = self.lock().clone();
+        entries.retain(|path, _| Path::new(path).exists());
+        let file = CacheFile {
+            version: CACHE_VERSION,
+            entries,
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| anyhow!("cache_dir: cannot create '{}': {}", dir.display(), e))?;
+        }
+        // Write to a temporary file first so a concurrent run never reads half a cache
+        let tmp = self
+            .path
+            .with_extension(format!("json.{}", std::process::id()));
+        fs::write(&tmp, serde_json::to_vec(&file)?)
+            .map_err(|e| anyhow!("cache_dir: cannot write '{}': {}", tmp.display(), e))?;
+        fs::rename(&tmp, &self.path)
+            .map_err(|e| anyhow!("cache_dir: cannot write '{}': {}", self.path.display(), e))?;
+        Ok(())
+    }
+}
+
+/// Absolute path, size and modification time of a file
+fn file_stamp(disk_path: &Path) -> Option<(String, u64, u64, u32)> {
+    let meta = fs::metadata(disk_path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    let path = std::path::absolute(disk_path).ok()?;
+    Some((
+        path.to_string_lossy().to_string(),
+        meta.len(),
+        mtime.as_secs(),
+        mtime.subsec_nanos(),
+    ))
+}
+
+fn text_hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())[..12]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Caches loaded in this process, one per directory
+fn loaded_caches() -> &'static Mutex<HashMap<PathBuf, Arc<TokenCache>>> {
+    static CACHES: OnceLock<Mutex<HashMap<PathBuf, Arc<TokenCache>>>> = OnceLock::new();
+    CACHES.get_or_init(Default::default)
+}
+
+/// The token cache for the config, loaded on first use. None with --no-cache, or when
+/// no cache directory is set (library callers opt in by setting `cache_dir`).
+pub fn token_cache(config: &YekConfig) -> Option<Arc<TokenCache>> {
+    let dir = config.token_cache_dir()?;
+    let mut caches = loaded_caches().lock().unwrap_or_else(|e| e.into_inner());
+    Some(
+        caches
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| Arc::new(TokenCache::load(dir)))
+            .clone(),
+    )
+}
+
+/// Write the config's token cache to disk, if it was used
+pub fn save_token_cache(config: &YekConfig) -> Result<()> {
+    let Some(dir) = config.token_cache_dir() else {
+        return Ok(());
+    };
+    let cache = loaded_caches()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(dir)
+        .cloned();
+    match cache {
+        Some(cache) => cache.save(),
+        None => Ok(()),
+    }
+}
+
+/// Delete the cache stored in `dir`, on disk and in memory. Returns whether a cache file existed.
+pub fn clear_token_cache(dir: &Path) -> Result<bool> {
+    loaded_caches()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(dir);
+    let path = dir.join(CACHE_FILE);
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(anyhow!(
+            "clear_cache: cannot remove '{}': {}",
+            path.display(),
+            e
+        )),
+    }
+}
@@ -0,0 +1,27 @@
+use anyhow::{anyhow, Result};
+use bytesize::ByteSize;
+
+/// Copy text to the system clipboard. Fails with a clear message when no clipboard
+/// is available (e.g. a headless Linux box without X11 or Wayland).
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| {
+        anyhow!(
+            "clipboard: no system clipboard available ({}). Run without --clipboard to print to stdout",
+            e
+        )
+    })?;
+    clipboard
+        .set_text(text)
+        .map_err(|e| anyhow!("clipboard: failed to copy output: {}", e))
+}
+
+/// Confirmation line shown after copying, e.g. "Copied 42 files (18.3 KiB) to clipboard"
+pub fn clipboard_summary(file_count: usize, bytes: usize) -> String {
+    let noun = if file_count == 1 { "file" } else { "files" };
+    format!(
+        "Copied {} {} ({}) to clipboard",
+        file_count,
+        noun,
+        ByteSize::b(bytes as u64)
+    )
+}
@@ -0,0 +1,80 @@
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+
+/// Compression applied to the output file with `--compress`
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// gzip (`.gz`)
+    Gzip,
+    /// Zstandard (`.zst`)
+    Zstd,
+}
+
+impl Compression {
+    /// File extension for this format, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+}
+
+/// `path` with the compression's extension appended, unless it already ends with it
+pub fn compressed_path(path: &str, compression: Compression) -> String {
+    let suffix = format!(".{}", compression.extension());
+    if path.ends_with(&suffix) {
+        path.to_string()
+    } else {
+        format!("{path}{suffix}")
+    }
+}
+
+/// A writer that compresses as it goes (or passes data through), so the output is
+/// never held in memory. Call [`CompressedWriter::finish`] to write the trailer.
+pub enum CompressedWriter<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    pub fn new(inner: W, compression: Option<Compression>) -> io::Result<Self> {
+        Ok(match compression {
+            None => CompressedWriter::Plain(inner),
+            Some(Compression::Gzip) => {
+                CompressedWriter::Gzip(GzEncoder::new(inner, flate2::Compression::default()))
+            }
+            Some(Compression::Zstd) => CompressedWriter::Zstd(zstd::Encoder::new(inner, 0)?),
+        })
+    }
+
+    /// Finish the compressed stream and return the inner writer
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            CompressedWriter::Plain(inner) => Ok(inner),
+            CompressedWriter::Gzip(encoder) => encoder.finish(),
+            CompressedWriter::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(inner) => inner.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(inner) => inner.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
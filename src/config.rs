@@ -1,13 +1,36 @@
 use anyhow::{anyhow, Result};
 use bytesize::ByteSize;
-use clap_config_file::ClapConfigFile;
+use clap::{ArgAction, Parser};
+use regex::Regex;
 use sha2::{Digest, Sha256};
 use std::io::IsTerminal;
-use std::{fs, path::Path, str::FromStr, time::UNIX_EPOCH};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
+use tracing::Level;
 
 use crate::{
-    defaults::{BINARY_FILE_EXTENSIONS, DEFAULT_IGNORE_PATTERNS, DEFAULT_OUTPUT_TEMPLATE},
-    priority::PriorityRule,
+    budget::{parse_budget_rule, BudgetRule},
+    cache::default_cache_dir,
+    compress::{compressed_path, Compression},
+    defaults::{
+        BINARY_FILE_EXTENSIONS, DEFAULT_GROUP_HEADER, DEFAULT_IGNORE_PATTERNS,
+        DEFAULT_OUTPUT_TEMPLATE, DEFAULT_REDACT_PATTERNS, DEFAULT_SEPARATOR,
+        MARKDOWN_OUTPUT_TEMPLATE, XML_OUTPUT_TEMPLATE,
+    },
+    encoding::encoding_for_label,
+    models::{find_model, supported_models, ModelPreset, TokenEstimator, Tokenizer},
+    parallel::parse_extensions,
+    priority::{parse_priority_weight, PriorityRule, PriorityWeight},
+    transform::{parse_transform_rule, TransformRule},
+    tree::{TreeConnectors, TreeDirsFirst, TreeOrder, TreeSort, TreeSortCase, TreeStyle},
+    truncate::{parse_truncate_rule, TruncateRule},
+    unescape_template,
 };
 
 #[derive(Clone, Debug, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
@@ -18,81 +41,823 @@ pub enum ConfigFormat {
     Json,
 }
 
-#[derive(ClapConfigFile, Clone)]
-#[config_file_name = "yek"]
-#[config_file_formats = "toml,yaml,json"]
+/// How `FILE_PATH` (and the JSON `filename`) is rendered for each file
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PathStyle {
+    /// Relative to the scanned input path (e.g. `src/main.rs`)
+    #[default]
+    Relative,
+    /// Canonicalized absolute path on disk
+    Absolute,
+    /// File name only (e.g. `main.rs`)
+    Basename,
+}
+
+/// Order of the files in the output
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputOrder {
+    /// `--priority` weights (highest first), `--git-recent` or the `--files-from` list when
+    /// given, otherwise by path with higher `priority_rules` scores last
+    #[default]
+    Priority,
+    /// By path
+    Path,
+    /// The order the directory tree lists the files in
+    Tree,
+    /// Largest first, ties by path
+    Size,
+}
+
+/// How files are chosen when they do not all fit in the `--max-size`/`--tokens` budget
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetFill {
+    /// In output order, stopping at the first file that does not fit
+    #[default]
+    Order,
+    /// Highest priority first (`--priority` weight, then `priority_rules` score), smaller
+    /// files first among equals, skipping any file that does not fit
+    Priority,
+}
+
+/// How `--group-by` partitions the serialized files
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// By file extension, groups ordered by extension name
+    #[default]
+    Ext,
+}
+
+/// What `--max-file-size` does with a file over the limit
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OversizeAction {
+    /// Leave the file out of the output; it is still listed in the tree
+    #[default]
+    Skip,
+    /// Keep the whole lines that fit within the limit, followed by a truncation marker
+    Truncate,
+}
+
+/// How the default template marks where each file starts (and ends)
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SeparatorStyle {
+    /// `>>>> path` before each file
+    #[default]
+    Prefix,
+    /// `>>>> path` before each file and `<<<< path` after it, so files can be split apart again
+    Fence,
+}
+
+/// When `--tree-only` draws the tree in color
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// When printing to a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    /// Whenever the tree is printed to stdout, even through a pipe (e.g. into `less -R`)
+    Always,
+    /// Never
+    Never,
+}
+
+/// What to do with a text file that is not valid in its detected or `--encoding` encoding
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidUtf8 {
+    /// Keep the file, with the bad bytes replaced by U+FFFD
+    Lossy,
+    /// Leave the file out with a warning
+    #[default]
+    Skip,
+    /// Abort the run
+    Error,
+}
+
+/// Preset output formats
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Use --output-template as given
+    #[default]
+    Text,
+    /// Each file under a heading, in a fenced code block tagged with FILE_LANG
+    Markdown,
+    /// A versioned JSON document with the tree, per-file metadata and summary stats
+    Json,
+    /// Each file in an escaped `<file path="...">` element inside a `<documents>` root,
+    /// with the tree in `<directory_structure>`
+    Xml,
+}
+
+/// The marker that closes a file with `--separator-style fence`: the separator reversed,
+/// with brackets mirrored, so ">>>>" becomes "<<<<" and "-->" becomes "<--"
+pub fn closing_separator(separator: &str) -> String {
+    separator
+        .chars()
+        .rev()
+        .map(|c| match c {
+            '>' => '<',
+            '<' => '>',
+            '(' => ')',
+            ')' => '(',
+            '[' => ']',
+            ']' => '[',
+            '{' => '}',
+            '}' => '{',
+            other => other,
+        })
+        .collect()
+}
+
+/// Config file names looked up in the current directory and at the scan root, in order
+pub const CONFIG_FILE_NAMES: [&str; 4] = ["yek.toml", "yek.yaml", "yek.yml", "yek.json"];
+
+/// Default for `--mtime-format`: ISO-8601 in UTC
+pub const DEFAULT_MTIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Default for `--max-total-files`
+pub const DEFAULT_MAX_TOTAL_FILES: usize = 100_000;
+
+/// Environment variable naming the config file to use when `--config` is not given
+pub const CONFIG_ENV_VAR: &str = "YEK_CONFIG";
+
+/// Environment variable holding extra ignore globs, separated by colons
+pub const IGNORE_ENV_VAR: &str = "YEK_IGNORE";
+
+/// Flags with a short form, which the command line may use instead of the long name
+const SHORT_FLAGS: [(char, &str); 5] = [
+    ('q', "quiet"),
+    ('v', "verbose"),
+    ('o', "output"),
+    ('t', "tree_header"),
+    ('j', "jobs"),
+];
+
+/// The settings given as flags on the command line (`--max-size 1MB`, `--json`, `-q`), by
+/// field name. Arguments after `--` are positional.
+pub fn command_line_settings(args: &[String]) -> HashSet<String> {
+    let mut settings = HashSet::new();
+    for arg in args.iter().skip(1) {
+        if arg == "--" {
+            break;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            let name = long.split('=').next().unwrap_or(long);
+            settings.insert(name.replace('-', "_"));
+        } else if let Some(shorts) = arg.strip_prefix('-') {
+            // Short flags can be grouped (-qt); -o and -j take the rest as their value
+            for c in shorts.chars() {
+                let Some((_, name)) = SHORT_FLAGS.iter().find(|(short, _)| *short == c) else {
+                    break;
+                };
+                settings.insert(name.to_string());
+                if matches!(c, 'o' | 'j') {
+                    break;
+                }
+            }
+        }
+    }
+    settings
+}
+
+/// The globs in a `YEK_IGNORE` value: split on `:`, trimmed, empty entries dropped
+pub fn env_ignore_patterns(value: &str) -> Vec<String> {
+    value
+        .split(':')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Parser, Clone, Debug, serde::Serialize)]
+#[command(name = "yek", about)]
 pub struct YekConfig {
     /// Input files and/or directories to process
-    #[config_arg(positional)]
+    #[arg(value_name = "input-paths")]
     pub input_paths: Vec<String>,
 
     /// Print version of yek
-    #[config_arg(long = "version", short = 'V')]
+    #[arg(long = "version", short = 'V')]
     pub version: bool,
 
+    /// Config file to use instead of discovering yek.toml/yek.yaml/yek.json
+    #[arg(long = "config", visible_alias = "config-file")]
+    pub config: Option<String>,
+
+    /// Do not read any config file
+    #[arg(long = "no-config")]
+    pub no_config: bool,
+
+    /// Serialize exactly the newline-delimited paths listed in this file ("-" for stdin),
+    /// in the given order. Input paths and ignore rules are not used
+    #[arg(long = "files-from")]
+    pub files_from: Option<String>,
+
     /// Max size per chunk. e.g. "10MB" or "128K" or when using token counting mode, "100" or "128K"
-    #[config_arg(default_value = "10MB")]
+    #[arg(long = "max-size", default_value = "10MB")]
     pub max_size: String,
 
+    /// Keep at most this many files (the first N in output order), on top of --max-size/--tokens
+    #[arg(long = "max-files")]
+    pub max_files: Option<usize>,
+
+    /// Stop with an error once the walk finds more than this many files, before any are
+    /// read, so pointing yek at `/` by mistake fails fast (0 = no limit)
+    #[arg(long = "max-total-files", default_value = "100000")]
+    pub max_total_files: usize,
+
+    /// Skip (or with --max-file-size-action truncate, cut down) any single file larger
+    /// than this, e.g. "1MB". Independent of --max-size
+    #[arg(long = "max-file-size")]
+    pub max_file_size: Option<String>,
+
+    /// What to do with files over --max-file-size: skip (default) or truncate
+    #[arg(long = "max-file-size-action", default_value = "skip")]
+    pub max_file_size_action: OversizeAction,
+
+    /// Skip files more than --outlier-factor times the --outlier-percentile file size
+    /// (the median by default). Skipped files stay in the tree
+    #[arg(long = "drop-outliers")]
+    pub drop_outliers: bool,
+
+    /// Percentile of file sizes that --drop-outliers compares against (50 = median)
+    #[arg(long = "outlier-percentile", default_value = "50")]
+    pub outlier_percentile: u8,
+
+    /// How many times the --outlier-percentile size a file may be before it is dropped
+    #[arg(long = "outlier-factor", default_value = "10")]
+    pub outlier_factor: f64,
+
+    /// Leave out files that look generated: minified names (".min."), an "@generated"
+    /// marker near the top, or very long lines on average. They stay in the tree
+    #[arg(long = "skip-generated")]
+    pub skip_generated: bool,
+
+    /// Average line length above which --skip-generated treats a file as generated
+    #[arg(long = "generated-line-length", default_value = "500")]
+    pub generated_line_length: usize,
+
+    /// Leave zero-byte files out of both the tree and the output (counted in --stats)
+    #[arg(long = "exclude-empty-files")]
+    pub exclude_empty_files: bool,
+
+    /// Pipe matching files through a shell command as <glob>:<command> (repeatable); its
+    /// output replaces the content. Several matching rules run in the order given
+    #[arg(long = "transform", num_args = 1..)]
+    pub transform: Vec<String>,
+
+    /// Keep a file's content as it was, with a warning, when its --transform command fails
+    #[arg(long = "transform-best-effort")]
+    pub transform_best_effort: bool,
+
+    /// Remove comments from source files (C-like, shell, Python, SQL and more, by
+    /// extension). Markers inside string literals are kept. Lossy, so reported in --stats
+    #[arg(long = "strip-comments")]
+    pub strip_comments: bool,
+
+    /// Remove empty and whitespace-only lines from text files. Lossy, so reported in --stats
+    #[arg(long = "strip-blank-lines")]
+    pub strip_blank_lines: bool,
+
+    /// Replace matches of this regex in file contents with [REDACTED] (repeatable)
+    #[arg(long = "redact", num_args = 1..)]
+    pub redact: Vec<String>,
+
+    /// Also redact common secrets: AWS keys, JWTs, private keys, GitHub and Slack tokens
+    #[arg(long = "redact-default")]
+    pub redact_default: bool,
+
     /// Use token mode instead of byte mode
-    #[config_arg()]
+    #[arg(long = "tokens", default_value = "")]
     pub tokens: String,
 
+    /// Model preset (e.g. gpt-4o, claude-3-5-sonnet) that sets the token budget and tokenizer.
+    /// An explicit --tokens value takes precedence over the preset's budget
+    #[arg(long = "model")]
+    pub model: Option<String>,
+
+    /// How to count tokens: tiktoken (exact, default) or heuristic (fast estimate)
+    #[arg(long = "token-estimator", default_value = "tiktoken")]
+    pub token_estimator: TokenEstimator,
+
     /// Enable JSON output
-    #[config_arg()]
+    #[arg(long = "json")]
     pub json: bool,
 
+    /// Emit one compact JSON object per file per line (NDJSON), preceded by a
+    /// {"type": "tree"} object with --tree-header
+    #[arg(long = "json-lines")]
+    pub json_lines: bool,
+
     /// Enable debug output
-    #[config_arg()]
+    #[arg(long = "debug")]
     pub debug: bool,
 
+    /// Only report hard errors on stderr: no progress, warnings or notices
+    #[arg(long = "quiet", short = 'q')]
+    pub quiet: bool,
+
+    /// Log more about which files are included or skipped and why (-vv for even more)
+    #[arg(long = "verbose", short = 'v', action = ArgAction::Count)]
+    pub verbose: u8,
+
     /// Output directory. If none is provided & stdout is a TTY, we pick a temp dir
-    #[config_arg()]
+    #[arg(long = "output-dir")]
     pub output_dir: Option<String>,
 
-    /// Output template. Defaults to ">>>> FILE_PATH\nFILE_CONTENT"
-    #[config_arg(default_value = ">>>> FILE_PATH\nFILE_CONTENT")]
+    /// Write the output to this file instead of stdout or the output directory. It is
+    /// written under a temporary name and renamed when complete, so a failed run leaves
+    /// the previous file intact
+    #[arg(long = "output", short = 'o')]
+    pub output: Option<String>,
+
+    /// Compress the output file with gzip or zstd, appending .gz/.zst to its name
+    #[arg(long = "compress")]
+    pub compress: Option<Compression>,
+
+    /// With --compress, also compress output streamed to stdout
+    #[arg(long = "compress-stdout")]
+    pub compress_stdout: bool,
+
+    /// Ask before writing output larger than this (e.g. "5MB"), showing its size and
+    /// tokens. Only asked on a terminal; without an interactive stdin the output is written
+    #[arg(long = "confirm-over")]
+    pub confirm_over: Option<String>,
+
+    /// Output template. Defaults to ">>>> FILE_PATH\nFILE_CONTENT".
+    /// Also supports FILE_INDEX (1-based position) and FILE_COUNT (files in the output),
+    /// FILE_SIZE (e.g. "1.2 KiB") and FILE_TOKENS (tokens in the content, counted even
+    /// without --tokens), FILE_MTIME (last-modified time, see --mtime-format), plus
+    /// FILE_AUTHOR and FILE_COMMIT with --show-authors.
+    /// Escapes \n, \t, \r, \0 and \\ are recognized; any other escape is an error
+    #[arg(
+        long = "output-template",
+        default_value = ">>>> FILE_PATH\nFILE_CONTENT"
+    )]
     pub output_template: String,
 
+    /// Template for the header line(s) before each file, e.g. "==> FILE_PATH (FILE_SIZE) <==".
+    /// Takes the placeholders and escapes of --output-template except FILE_CONTENT, and
+    /// replaces the separator line of --format text
+    #[arg(long = "header-template")]
+    pub header_template: Option<String>,
+
+    /// Template for each file's body, e.g. "```\nFILE_CONTENT\n```". It takes the place of
+    /// FILE_CONTENT in the --header-template or the --format preset
+    #[arg(long = "content-template")]
+    pub content_template: Option<String>,
+
+    /// Output format preset. markdown wraps each file in a fenced code block
+    /// (ignored when a custom --output-template is given); json emits a versioned
+    /// document with per-file metadata
+    #[arg(long = "format", default_value = "text")]
+    pub format: OutputFormat,
+
+    /// Marker put before each file's path by the default template
+    #[arg(long = "separator", default_value = ">>>>")]
+    pub separator: String,
+
+    /// prefix marks the start of each file; fence also closes it with the mirrored
+    /// separator (">>>>" becomes "<<<<") followed by the path
+    #[arg(long = "separator-style", default_value = "prefix")]
+    pub separator_style: SeparatorStyle,
+
+    /// Extension to Markdown language overrides for FILE_LANG, e.g. { "h" = "cpp" }
+    #[arg(skip)]
+    pub language_map: HashMap<String, String>,
+
+    /// Group the output by file extension, with a header before each group
+    #[arg(long = "group-by")]
+    pub group_by: Option<GroupBy>,
+
+    /// Header written before each group with --group-by. GROUP is replaced by the
+    /// group name (e.g. "*.rs")
+    #[arg(long = "group-header", default_value = "==== GROUP ====")]
+    pub group_header: String,
+
+    /// How FILE_PATH is rendered: relative (default), absolute or basename
+    #[arg(long = "path-style", default_value = "relative")]
+    pub path_style: PathStyle,
+
+    /// Show paths (in FILE_PATH and the tree) relative to this directory instead of the
+    /// scan root. Files outside it are shown with their absolute path
+    #[arg(long = "relative-to")]
+    pub relative_to: Option<String>,
+
+    /// Force the source encoding of every file (e.g. utf-16le, latin1).
+    /// By default it is detected from the BOM or the content
+    #[arg(long = "encoding")]
+    pub encoding: Option<String>,
+
+    /// What to do with a file that cannot be decoded: lossy (replace bad bytes with
+    /// U+FFFD), skip (leave it out with a warning) or error (abort the run)
+    #[arg(long = "on-invalid-utf8", default_value = "skip")]
+    pub on_invalid_utf8: InvalidUtf8,
+
     /// Ignore patterns
-    #[config_arg(long = "ignore-patterns", multi_value_behavior = "extend")]
+    #[arg(long = "ignore-patterns", num_args = 1..)]
     pub ignore_patterns: Vec<String>,
 
+    /// Only serialize files matching these globs (gitignore syntax, repeatable)
+    #[arg(long = "include", num_args = 1..)]
+    pub include: Vec<String>,
+
+    /// Never serialize files matching these globs (gitignore syntax, repeatable).
+    /// Excludes win over --include
+    #[arg(long = "exclude", num_args = 1..)]
+    pub exclude: Vec<String>,
+
+    /// Include and exclude globs in one ordered list (gitignore syntax, repeatable), as in
+    /// ripgrep's --glob: a leading ! excludes, and the last pattern matching a path decides.
+    /// If any pattern has no !, paths matching none of them are left out. Applies with
+    /// --include and --exclude, to the tree and the content alike
+    #[arg(long = "filter", num_args = 1..)]
+    pub filter: Vec<String>,
+
+    /// Serialize exactly the files matching these globs (gitignore syntax, repeatable):
+    /// built-in ignore patterns, .gitignore, .yekignore, export-ignore and the hidden-file
+    /// rule are all skipped. --exclude, --include and --ext still apply
+    #[arg(long = "only", num_args = 1..)]
+    pub only: Vec<String>,
+
+    /// Only serialize files with these extensions, comma-separated (e.g. "rs,toml").
+    /// Files must also pass --include when both are given
+    #[arg(long = "ext", num_args = 1..)]
+    pub ext: Vec<String>,
+
+    /// Never serialize files with these extensions, comma-separated (e.g. "lock,min.js").
+    /// Wins over --ext and --include
+    #[arg(long = "no-ext", num_args = 1..)]
+    pub no_ext: Vec<String>,
+
+    /// Do not read .yekignore files
+    #[arg(long = "no-yekignore")]
+    pub no_yekignore: bool,
+
+    /// Also serialize files marked export-ignore in .gitattributes (skipped by default)
+    #[arg(long = "no-export-ignore")]
+    pub no_export_ignore: bool,
+
     /// Unignore patterns. Yek has some built-in ignore patterns, but you can override them here.
-    #[config_arg(long = "unignore-patterns", multi_value_behavior = "extend")]
+    #[arg(long = "unignore-patterns", num_args = 1..)]
     pub unignore_patterns: Vec<String>,
 
+    /// Ordering weights as <glob>:<weight> (repeatable). Higher weights are emitted
+    /// earlier and are kept first when filling --max-size/--tokens; ties are broken by path
+    #[arg(long = "priority", num_args = 1..)]
+    pub priority: Vec<String>,
+
+    /// Reserve a share of the --max-size/--tokens budget as <glob>:<percent> (repeatable).
+    /// Matching files fill their share first; unused shares go back to the general pool
+    #[arg(long = "budget", num_args = 1..)]
+    pub budget: Vec<String>,
+
+    /// Emit these files first, in the order given (repeatable). Paths are relative to the
+    /// input directory. Pinned files are kept even when the --max-size/--tokens budget would
+    /// drop them, unless one alone is larger than the budget
+    #[arg(long = "pin", num_args = 1..)]
+    pub pin: Vec<String>,
+
+    /// How to choose files when they overflow the budget: order (fill in output order up to
+    /// the first file that does not fit, default) or priority (drop the lowest-priority files,
+    /// smallest first among equals, until the rest fit). Output order is kept either way
+    #[arg(long = "fill", default_value = "order")]
+    pub fill: BudgetFill,
+
+    /// Keep only the first lines of matching files, as <glob>:<lines> (repeatable).
+    /// A marker notes how many lines were cut; the tree still lists the file
+    #[arg(long = "truncate", num_args = 1..)]
+    pub truncate: Vec<String>,
+
+    /// Preview mode: keep only the first N lines of every file (combine with --tail)
+    #[arg(long = "head")]
+    pub head: Option<usize>,
+
+    /// Preview mode: keep only the last N lines of every file (combine with --head)
+    #[arg(long = "tail")]
+    pub tail: Option<usize>,
+
+    /// Cut content lines longer than N characters, marking them "…(truncated)"
+    #[arg(long = "max-line-length")]
+    pub max_line_length: Option<usize>,
+
+    /// With --max-line-length, wrap long lines instead of cutting them
+    #[arg(long = "wrap")]
+    pub wrap: bool,
+
+    /// Prefix each content line with its line number (e.g. "  12 | let x = 1;")
+    #[arg(long = "line-numbers")]
+    pub line_numbers: bool,
+
+    /// Emit identical file contents once; later copies become "(identical to <path>)"
+    #[arg(long = "dedupe")]
+    pub dedupe: bool,
+
     /// Priority rules
-    #[config_arg(accept_from = "config_only")]
+    #[arg(skip)]
     pub priority_rules: Vec<PriorityRule>,
 
+    /// Walk into symlinked directories and serialize symlinked files as their targets.
+    /// Otherwise symlinks are only listed in the tree as "name -> target"
+    #[arg(long = "follow-symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Include hidden files and directories (names starting with "."), which are skipped
+    /// by default. .git directories are skipped either way
+    #[arg(long = "hidden")]
+    pub hidden: bool,
+
+    /// Serialize binary files as base64 instead of skipping them
+    #[arg(long = "include-binary")]
+    pub include_binary: bool,
+
     /// Binary file extensions to ignore
-    #[config_arg(accept_from = "config_only", default_value = BINARY_FILE_EXTENSIONS)]
+    #[arg(skip)]
     pub binary_extensions: Vec<String>,
 
     /// Maximum additional boost from Git commit times (0..1000)
-    #[config_arg(accept_from = "config_only")]
+    #[arg(skip)]
     pub git_boost_max: Option<i32>,
 
+    /// Order files by their last commit, newest first. Untracked files come last
+    #[arg(long = "git-recent")]
+    pub git_recent: bool,
+
+    /// Look up each file's last commit for the FILE_AUTHOR and FILE_COMMIT template
+    /// variables (and "author"/"commit" in JSON output)
+    #[arg(long = "show-authors")]
+    pub show_authors: bool,
+
+    /// Show each file's last-modified time (from the filesystem) after its path in the
+    /// built-in templates, and as "mtime" in JSON output. Custom templates use FILE_MTIME
+    #[arg(long = "show-mtime")]
+    pub show_mtime: bool,
+
+    /// strftime-style format of FILE_MTIME, in UTC (default: ISO-8601)
+    #[arg(long = "mtime-format", default_value = DEFAULT_MTIME_FORMAT)]
+    pub mtime_format: String,
+
+    /// Only serialize files changed since this git ref (like `git diff --name-only <ref>`)
+    #[arg(long = "since")]
+    pub since: Option<String>,
+
+    /// With --since, keep every file in the tree header instead of only the changed ones
+    #[arg(long = "full-tree")]
+    pub full_tree: bool,
+
     /// Include directory tree header in output (incompatible with JSON output)
-    #[config_arg(long = "tree-header", short = 't')]
+    #[arg(long = "tree-header", short = 't')]
     pub tree_header: bool,
 
-    /// Show only the directory tree (no file contents, incompatible with JSON output)
-    #[config_arg(long = "tree-only")]
+    /// Show only the directory tree (no file contents). With --json, the tree is emitted as JSON
+    #[arg(long = "tree-only")]
     pub tree_only: bool,
 
+    /// Print only a tree comparing the input with this directory or git ref: entries
+    /// only in it are marked "-", entries only in the input "+"
+    #[arg(long = "diff-tree")]
+    pub diff_tree: Option<String>,
+
+    /// Emit each file's header from the output template, in output order, without its
+    /// contents: FILE_CONTENT is left empty, so separators appear exactly as in real output
+    #[arg(long = "headers-only")]
+    pub headers_only: bool,
+
+    /// With --format markdown, start the output with a list of links to each file's section
+    #[arg(long = "toc")]
+    pub toc: bool,
+
+    /// Start the output with a line naming the git origin, branch and HEAD commit of the
+    /// repository being serialized, and whether its working tree is dirty
+    #[arg(long = "git-header")]
+    pub git_header: bool,
+
+    /// Prune the tree to the files actually included in the output
+    /// (by default it shows every candidate file)
+    #[arg(long = "tree-matches-output")]
+    pub tree_matches_output: bool,
+
+    /// Mark files left out by --max-size/--tokens with "(dropped)" in the tree header
+    #[arg(long = "tree-mark-dropped")]
+    pub tree_mark_dropped: bool,
+
+    /// Show file sizes on disk in the directory tree (directories show the total of their
+    /// contents)
+    #[arg(long = "tree-sizes")]
+    pub tree_sizes: bool,
+
+    /// Show how many files and subdirectories each directory in the tree contains
+    #[arg(long = "tree-counts")]
+    pub tree_counts: bool,
+
+    /// Limit the directory tree to this many levels (0 = top-level entries only)
+    #[arg(long = "tree-depth")]
+    pub tree_depth: Option<usize>,
+
+    /// Tree drawing style: unicode (default), ascii or markdown
+    #[arg(long = "tree-style", default_value = "unicode")]
+    pub tree_style: TreeStyle,
+
+    /// Columns per tree level, at least 2 (default 4, or 2 with --tree-style markdown)
+    #[arg(long = "tree-indent")]
+    pub tree_indent: Option<usize>,
+
+    /// Custom tree connector strings, e.g. { branch = "+- ", last = "`- ", vertical = "|  ",
+    /// blank = "   " }. Unset ones come from --tree-style and --tree-indent
+    #[arg(skip)]
+    pub tree_connectors: Option<TreeConnectors>,
+
+    /// Show directories in the tree even when they contain no serialized files
+    #[arg(long = "tree-include-empty-dirs")]
+    pub tree_include_empty_dirs: bool,
+
+    /// Leave files smaller than this (e.g. "10B") out of the tree; their content is still
+    /// serialized. Directories left with nothing to show are hidden too
+    #[arg(long = "tree-min-size")]
+    pub tree_min_size: Option<String>,
+
+    /// Marker after directory names in the tree, e.g. "[dir]" or "" for none
+    #[arg(long = "tree-dir-marker", default_value = "/")]
+    pub tree_dir_marker: String,
+
+    /// Show directory names in the tree without a trailing "/" (same as --tree-dir-marker "")
+    #[arg(long = "no-tree-trailing-slash")]
+    pub no_tree_trailing_slash: bool,
+
+    /// Draw the tree under a root directory with this name, e.g. "myproject" (by default
+    /// the top-level entries have no root). Also names the root of JSON trees
+    #[arg(long = "root-name")]
+    pub root_name: Option<String>,
+
+    /// Color the --tree-only tree on stdout (directories bold blue, symlinks cyan): auto
+    /// (when stdout is a terminal), always or never. Files written with --output or
+    /// --output-dir are never colored
+    #[arg(long = "color", default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Merge directories that contain only a single directory into one tree node
+    /// (e.g. "src/main/java/com/example/")
+    #[arg(long = "tree-collapse")]
+    pub tree_collapse: bool,
+
+    /// Tree entry order: name (default), size (largest first) or ext
+    #[arg(long = "tree-sort", default_value = "name")]
+    pub tree_sort: TreeSort,
+
+    /// List directories before files in the tree (false lists files first)
+    #[arg(long = "tree-dirs-first", default_value = "true")]
+    pub tree_dirs_first: TreeDirsFirst,
+
+    /// Tree entry grouping: alpha keeps directories and files apart (default),
+    /// alpha-interleaved mixes them in one alphabetical list
+    #[arg(long = "tree-order", default_value = "alpha")]
+    pub tree_order: TreeOrder,
+
+    /// Compare names in the tree case-sensitively (default, uppercase first) or insensitively
+    #[arg(long = "tree-sort-case", default_value = "sensitive")]
+    pub tree_sort_case: TreeSortCase,
+
+    /// Line shown above the directory tree instead of "Directory structure:" (empty for none)
+    #[arg(long = "tree-header-text")]
+    pub tree_header_text: Option<String>,
+
+    /// Line shown below the directory tree, e.g. to close a fence opened by --tree-header-text
+    #[arg(long = "tree-footer-text")]
+    pub tree_footer_text: Option<String>,
+
+    /// Order files in the output by name, size (largest first) or ext instead of by priority
+    #[arg(long = "sort")]
+    pub sort: Option<TreeSort>,
+
+    /// Order of files in the output: priority (default; see --priority), path, tree (as
+    /// the directory tree lists them, following the --tree-* options) or size (largest first)
+    #[arg(long = "order", default_value = "priority")]
+    pub order: OutputOrder,
+
+    /// Write the output as numbered chunk files (output-001.txt, ...) in this directory,
+    /// each within --max-size or --tokens
+    #[arg(long = "split-output")]
+    pub split_output: Option<String>,
+
+    /// Repeat the --tree-header at the top of every chunk when splitting output
+    #[arg(long = "split-repeat-tree")]
+    pub split_repeat_tree: bool,
+
+    /// List the files that would be serialized, with sizes and token counts, then exit
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Print the total tokens and bytes of every file that passes the filters, then exit
+    #[arg(long = "count-only")]
+    pub count_only: bool,
+
+    /// Add a breakdown of the included files by language (files, bytes and share of the
+    /// bytes) to the --stats summary, turning the summary on if needed
+    #[arg(long = "lang-stats")]
+    pub lang_stats: bool,
+
+    /// Print the language breakdown of every file that passes the filters, then exit
+    #[arg(long = "lang-stats-only")]
+    pub lang_stats_only: bool,
+
+    /// Exit with status 3 when no file passes the filters, instead of succeeding with
+    /// empty output (for scripts and CI)
+    #[arg(long = "fail-on-empty")]
+    pub fail_on_empty: bool,
+
+    /// Also write the paths of the files in the output, one per line in output order, to
+    /// this file ("-" prints them to stdout instead of the output)
+    #[arg(long = "manifest")]
+    pub manifest: Option<String>,
+
+    /// Append a summary of what was included and skipped to the output
+    /// (printed to stderr with --json)
+    #[arg(long = "stats")]
+    pub stats: bool,
+
+    /// Print the --stats summary to stderr instead of appending it to the output
+    #[arg(long = "stats-stderr")]
+    pub stats_stderr: bool,
+
+    /// Number of threads for walking, reading and measuring files (default: logical cores)
+    #[arg(long = "jobs", short = 'j')]
+    pub jobs: Option<usize>,
+
+    /// Directory for the token count cache (default: $XDG_CACHE_HOME/yek or ~/.cache/yek)
+    #[arg(long = "cache-dir")]
+    pub cache_dir: Option<String>,
+
+    /// Count every file's tokens again instead of reusing counts from earlier runs
+    #[arg(long = "no-cache")]
+    pub no_cache: bool,
+
+    /// Delete the token count cache before running
+    #[arg(long = "clear-cache")]
+    pub clear_cache: bool,
+
+    /// Copy the output to the system clipboard instead of printing or writing it
+    #[arg(long = "clipboard")]
+    pub clipboard: bool,
+
+    /// Pick the files to serialize in a terminal UI, starting from everything yek would include
+    #[arg(long = "interactive")]
+    pub interactive: bool,
+
+    /// Keep running and regenerate the output whenever a file that would be serialized changes
+    #[arg(long = "watch")]
+    pub watch: bool,
+
+    /// With --watch, milliseconds to wait for changes to settle before regenerating
+    #[arg(long = "watch-debounce", default_value = "300")]
+    pub watch_debounce: u64,
+
     /// True if we should stream output to stdout (computed)
+    #[arg(skip)]
     pub stream: bool,
 
+    /// Files picked with --interactive, read like a --files-from list (computed)
+    #[arg(skip)]
+    pub picked_files: Option<Vec<String>>,
+
+    /// True if the tree is drawn with ANSI colors (computed from --color)
+    #[arg(skip)]
+    pub tree_color: bool,
+
     /// True if we should count tokens, not bytes (computed)
+    #[arg(skip)]
     pub token_mode: bool,
 
     /// Final resolved output file path (only used if not streaming)
+    #[arg(skip)]
     pub output_file_full_path: Option<String>,
 
     /// Maximum depth to search for Git commit times
-    #[config_arg(accept_from = "config_only", default_value = "100")]
+    #[arg(skip)]
     pub max_git_depth: i32,
 }
 
@@ -102,25 +867,140 @@ impl Default for YekConfig {
         Self {
             input_paths: Vec::new(),
             version: false,
+            config: None,
+            no_config: false,
             max_size: "10MB".to_string(),
+            max_files: None,
+            max_total_files: DEFAULT_MAX_TOTAL_FILES,
+            max_file_size: None,
+            max_file_size_action: OversizeAction::default(),
+            drop_outliers: false,
+            outlier_percentile: 50,
+            outlier_factor: 10.0,
+            skip_generated: false,
+            generated_line_length: 500,
+            exclude_empty_files: false,
+            transform: Vec::new(),
+            transform_best_effort: false,
+            strip_comments: false,
+            strip_blank_lines: false,
+            redact: Vec::new(),
+            redact_default: false,
             tokens: String::new(),
+            model: None,
+            token_estimator: TokenEstimator::default(),
             json: false,
+            json_lines: false,
             debug: false,
+            quiet: false,
+            verbose: 0,
             output_dir: None,
+            output: None,
+            compress: None,
+            compress_stdout: false,
+            confirm_over: None,
             output_template: DEFAULT_OUTPUT_TEMPLATE.to_string(),
+            header_template: None,
+            content_template: None,
+            format: OutputFormat::default(),
+            separator: DEFAULT_SEPARATOR.to_string(),
+            separator_style: SeparatorStyle::default(),
+            language_map: HashMap::new(),
+            group_by: None,
+            group_header: DEFAULT_GROUP_HEADER.to_string(),
+            path_style: PathStyle::default(),
+            relative_to: None,
+            encoding: None,
+            on_invalid_utf8: InvalidUtf8::Skip,
             ignore_patterns: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            filter: Vec::new(),
+            only: Vec::new(),
+            ext: Vec::new(),
+            no_ext: Vec::new(),
+            no_yekignore: false,
+            no_export_ignore: false,
             unignore_patterns: Vec::new(),
+            priority: Vec::new(),
+            budget: Vec::new(),
+            pin: Vec::new(),
+            fill: BudgetFill::default(),
+            truncate: Vec::new(),
+            head: None,
+            tail: None,
+            max_line_length: None,
+            wrap: false,
+            line_numbers: false,
+            dedupe: false,
             priority_rules: Vec::new(),
+            follow_symlinks: false,
+            hidden: false,
+            include_binary: false,
             binary_extensions: BINARY_FILE_EXTENSIONS
                 .iter()
                 .map(|s| s.to_string())
                 .collect(),
             git_boost_max: Some(100),
+            git_recent: false,
+            show_authors: false,
+            show_mtime: false,
+            mtime_format: DEFAULT_MTIME_FORMAT.to_string(),
+            since: None,
+            files_from: None,
+            full_tree: false,
 
             // computed fields
             tree_header: false,
             tree_only: false,
+            diff_tree: None,
+            headers_only: false,
+            toc: false,
+            git_header: false,
+            tree_matches_output: false,
+            tree_mark_dropped: false,
+            tree_sizes: false,
+            tree_counts: false,
+            tree_depth: None,
+            tree_style: TreeStyle::default(),
+            tree_indent: None,
+            tree_connectors: None,
+            tree_include_empty_dirs: false,
+            tree_min_size: None,
+            tree_dir_marker: "/".to_string(),
+            no_tree_trailing_slash: false,
+            root_name: None,
+            color: ColorChoice::default(),
+            tree_collapse: false,
+            tree_sort: TreeSort::default(),
+            tree_dirs_first: TreeDirsFirst::default(),
+            tree_order: TreeOrder::default(),
+            tree_sort_case: TreeSortCase::default(),
+            tree_header_text: None,
+            tree_footer_text: None,
+            sort: None,
+            order: OutputOrder::default(),
+            split_output: None,
+            split_repeat_tree: false,
+            dry_run: false,
+            count_only: false,
+            lang_stats: false,
+            lang_stats_only: false,
+            fail_on_empty: false,
+            manifest: None,
+            stats: false,
+            stats_stderr: false,
+            jobs: None,
+            cache_dir: None,
+            no_cache: false,
+            clear_cache: false,
+            clipboard: false,
+            interactive: false,
+            watch: false,
+            watch_debounce: 300,
             stream: false,
+            tree_color: false,
+            picked_files: None,
             token_mode: false,
             output_file_full_path: None,
             max_git_depth: 100,
@@ -139,6 +1019,450 @@ impl YekConfig {
 }
 
 impl YekConfig {
+    /// Whether the output is JSON (`--json`, `--format json` or `--json-lines`)
+    pub fn json_output(&self) -> bool {
+        self.json || self.format == OutputFormat::Json || self.json_lines
+    }
+
+    /// The template actually used for each file: a --format preset applies
+    /// unless the user supplied their own --output-template, and otherwise the
+    /// default template follows --separator and --separator-style.
+    /// --header-template and --content-template replace the parts of the preset
+    pub fn effective_output_template(&self) -> Cow<'_, str> {
+        if self.output_template != DEFAULT_OUTPUT_TEMPLATE {
+            return Cow::Borrowed(&self.output_template);
+        }
+        let content = self.content_template.as_deref();
+        if let Some(header) = &self.header_template {
+            return Cow::Owned(format!("{}\n{}", header, content.unwrap_or("FILE_CONTENT")));
+        }
+        let mut template = self.preset_template();
+        if self.show_mtime {
+            // --show-mtime puts the time next to the path
+            template = Cow::Owned(match self.format {
+                OutputFormat::Markdown => {
+                    template.replacen("## FILE_PATH\n", "## FILE_PATH\n\nModified FILE_MTIME\n", 1)
+                }
+                OutputFormat::Xml => {
+                    template.replacen("\"FILE_PATH\"", "\"FILE_PATH\" mtime=\"FILE_MTIME\"", 1)
+                }
+                _ => template.replacen("FILE_PATH", "FILE_PATH (modified FILE_MTIME)", 1),
+            });
+        }
+        match content {
+            Some(content) => Cow::Owned(template.replacen("FILE_CONTENT", content, 1)),
+            None => template,
+        }
+    }
+
+    /// The template of the `--format` and `--separator` options, before `--show-mtime`
+    fn preset_template(&self) -> Cow<'_, str> {
+        match self.format {
+            OutputFormat::Markdown => Cow::Borrowed(MARKDOWN_OUTPUT_TEMPLATE),
+            OutputFormat::Xml => Cow::Borrowed(XML_OUTPUT_TEMPLATE),
+            _ if !self.uses_custom_separator() => Cow::Borrowed(DEFAULT_OUTPUT_TEMPLATE),
+            _ => {
+                // The template is unescaped before use, so backslashes in the separator are doubled
+                let open = self.separator.replace('\\', "\\\\");
+                let mut template = format!("{} FILE_PATH\nFILE_CONTENT", open);
+                if self.separator_style == SeparatorStyle::Fence {
+                    let close = closing_separator(&self.separator).replace('\\', "\\\\");
+                    template.push_str(&format!("\n{} FILE_PATH", close));
+                }
+                Cow::Owned(template)
+            }
+        }
+    }
+
+    /// Whether --separator or --separator-style changes the default template
+    fn uses_custom_separator(&self) -> bool {
+        self.separator != DEFAULT_SEPARATOR || self.separator_style != SeparatorStyle::Prefix
+    }
+
+    /// The `--encoding` override, if set to a known label
+    pub fn source_encoding(&self) -> Option<&'static encoding_rs::Encoding> {
+        self.encoding
+            .as_deref()
+            .and_then(|label| encoding_for_label(label).ok())
+    }
+
+    /// Parsed `--priority` weights. Invalid specs are rejected by `validate`, so they are skipped here
+    pub fn priority_weights(&self) -> Vec<PriorityWeight> {
+        self.priority
+            .iter()
+            .filter_map(|spec| parse_priority_weight(spec).ok())
+            .collect()
+    }
+
+    /// `--pin` paths as they appear in file paths: forward slashes, no leading "./"
+    pub fn pinned_paths(&self) -> Vec<String> {
+        self.pin
+            .iter()
+            .map(|path| {
+                let path = path.trim().replace('\\', "/");
+                path.trim_start_matches("./").to_string()
+            })
+            .collect()
+    }
+
+    /// Parsed `--budget` rules. Invalid specs are rejected by `validate`, so they are skipped here
+    pub fn budget_rules(&self) -> Vec<BudgetRule> {
+        self.budget
+            .iter()
+            .filter_map(|spec| parse_budget_rule(spec).ok())
+            .collect()
+    }
+
+    /// Parsed `--truncate` rules. Invalid specs are rejected by `validate`, so they are skipped here
+    pub fn truncate_rules(&self) -> Vec<TruncateRule> {
+        self.truncate
+            .iter()
+            .filter_map(|spec| parse_truncate_rule(spec).ok())
+            .collect()
+    }
+
+    /// Parsed `--transform` rules. Invalid specs are rejected by `validate`, so they are skipped here
+    pub fn transform_rules(&self) -> Vec<TransformRule> {
+        self.transform
+            .iter()
+            .filter_map(|spec| parse_transform_rule(spec).ok())
+            .collect()
+    }
+
+    /// The file --output writes to, with the --compress extension added
+    pub fn output_file(&self) -> Option<String> {
+        let output = self.output.as_ref()?;
+        Some(match self.compress {
+            Some(compression) => compressed_path(output, compression),
+            None => output.clone(),
+        })
+    }
+
+    /// Files a run writes besides the output directory: --output and --manifest
+    pub fn written_files(&self) -> Vec<String> {
+        let manifest = self.manifest.clone().filter(|path| path != "-");
+        self.output_file().into_iter().chain(manifest).collect()
+    }
+
+    /// Whether ignore patterns, ignore files and export-ignore apply, which they do unless
+    /// an --only allowlist replaces them
+    pub fn uses_ignore_rules(&self) -> bool {
+        self.only.is_empty()
+    }
+
+    /// Whether hidden files are walked: with --hidden, or when --only picks the files
+    pub fn walks_hidden(&self) -> bool {
+        self.hidden || !self.uses_ignore_rules()
+    }
+
+    /// Whether an explicit file list (--files-from or the --interactive pick) replaces the walk
+    pub fn uses_file_list(&self) -> bool {
+        self.picked_files.is_some() || self.files_from.is_some()
+    }
+
+    /// `--max-file-size` in bytes. An invalid size is rejected by `validate`, so it is ignored here
+    pub fn max_file_size_bytes(&self) -> Option<u64> {
+        self.max_file_size
+            .as_ref()
+            .and_then(|limit| ByteSize::from_str(limit).ok())
+            .map(|size| size.as_u64())
+    }
+
+    /// The `--redact` regexes, after the `--redact-default` ones when that is set
+    pub fn redact_patterns(&self) -> Result<Vec<Regex>> {
+        let defaults = DEFAULT_REDACT_PATTERNS
+            .iter()
+            .filter(|_| self.redact_default)
+            .map(|pattern| pattern.to_string());
+        defaults
+            .chain(self.redact.iter().cloned())
+            .map(|pattern| {
+                Regex::new(&pattern)
+                    .map_err(|e| anyhow!("redact: Invalid pattern '{}': {}", pattern, e))
+            })
+            .collect()
+    }
+
+    /// `--tree-min-size` in bytes, ignored when invalid like [`Self::max_file_size_bytes`]
+    pub fn tree_min_size_bytes(&self) -> Option<u64> {
+        self.tree_min_size
+            .as_ref()
+            .and_then(|size| ByteSize::from_str(size).ok())
+            .map(|size| size.as_u64())
+    }
+
+    /// `--confirm-over` in bytes, ignored when invalid like [`Self::max_file_size_bytes`]
+    pub fn confirm_over_bytes(&self) -> Option<u64> {
+        self.confirm_over
+            .as_ref()
+            .and_then(|size| ByteSize::from_str(size).ok())
+            .map(|size| size.as_u64())
+    }
+
+    /// Most detailed log level shown: errors only with --quiet, info by default, then
+    /// debug and trace for each -v (--debug counts as one)
+    pub fn log_level(&self) -> Level {
+        if self.quiet {
+            return Level::ERROR;
+        }
+        match self.verbose.max(self.debug as u8) {
+            0 => Level::INFO,
+            1 => Level::DEBUG,
+            _ => Level::TRACE,
+        }
+    }
+
+    /// The preset selected with `--model`, if it names a known model
+    pub fn model_preset(&self) -> Option<&'static ModelPreset> {
+        self.model.as_deref().and_then(find_model)
+    }
+
+    /// Where token counts are cached, or None when caching is off (--no-cache, or no directory)
+    pub fn token_cache_dir(&self) -> Option<&Path> {
+        self.cache_dir
+            .as_deref()
+            .filter(|_| !self.no_cache)
+            .map(Path::new)
+    }
+
+    /// Tokenizer used for token counting, following the selected model
+    pub fn tokenizer(&self) -> Tokenizer {
+        self.model_preset()
+            .map(|preset| preset.tokenizer)
+            .unwrap_or_default()
+    }
+
+    /// Use the model's context window as the token budget when --tokens is not given
+    pub fn apply_model_preset(&mut self) {
+        if self.tokens.is_empty() {
+            if let Some(preset) = self.model_preset() {
+                self.tokens = preset.context_tokens.to_string();
+            }
+        }
+    }
+
+    /// Apply a config file (yek.toml, yek.yaml or yek.json) on top of this config.
+    /// Settings whose flag appears in `args` (the command line) are kept, so CLI flags win
+    /// even when they repeat the default. Unknown keys are an error so typos don't silently
+    /// do nothing.
+    pub fn apply_config_file(&mut self, path: &Path, args: &[String]) -> Result<()> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("config: cannot read '{}': {}", path.display(), e))?;
+        let parse_error =
+            |e: &dyn std::fmt::Display| anyhow!("config: cannot parse '{}': {}", path.display(), e);
+        let values: serde_json::Value = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&text).map_err(|e| parse_error(&e))?,
+            Some("yaml") | Some("yml") => {
+                serde_yaml::from_str(&text).map_err(|e| parse_error(&e))?
+            }
+            _ => toml::from_str(&text).map_err(|e| parse_error(&e))?,
+        };
+        let values = match values {
+            serde_json::Value::Object(map) => map,
+            serde_json::Value::Null => return Ok(()),
+            _ => return Err(parse_error(&"expected a table of settings")),
+        };
+
+        let mut given = command_line_settings(args);
+        // Input paths are positional, so they can't be told apart from flag values in `args`,
+        // but they can't be given empty either
+        if !self.input_paths.is_empty() {
+            given.insert("input_paths".to_string());
+        }
+        macro_rules! apply_fields {
+            ($($field:ident),* $(,)?; extend: $($list:ident),* $(,)?) => {
+                for (key, value) in &values {
+                    let invalid = |e: serde_json::Error| {
+                        anyhow!("config: Invalid value for '{}' in '{}': {}", key, path.display(), e)
+                    };
+                    match key.as_str() {
+                        $(stringify!($field) => {
+                            if !given.contains(key) {
+                                self.$field = serde_json::from_value(value.clone()).map_err(invalid)?;
+                            }
+                        })*
+                        // Lists the command line adds to rather than replaces
+                        $(stringify!($list) => {
+                            let mut list: Vec<String> =
+                                serde_json::from_value(value.clone()).map_err(invalid)?;
+                            if given.contains(key) {
+                                list.append(&mut self.$list);
+                            }
+                            self.$list = list;
+                        })*
+                        _ => {
+                            return Err(anyhow!(
+                                "config: Unknown key '{}' in '{}'",
+                                key,
+                                path.display()
+                            ))
+                        }
+                    }
+                }
+            };
+        }
+        apply_fields!(
+            input_paths,
+            max_size,
+            max_files,
+            max_total_files,
+            max_file_size,
+            max_file_size_action,
+            drop_outliers,
+            outlier_percentile,
+            outlier_factor,
+            skip_generated,
+            generated_line_length,
+            exclude_empty_files,
+            transform_best_effort,
+            strip_comments,
+            strip_blank_lines,
+            redact_default,
+            tokens,
+            model,
+            token_estimator,
+            json,
+            json_lines,
+            debug,
+            quiet,
+            verbose,
+            output_dir,
+            output,
+            compress,
+            compress_stdout,
+            confirm_over,
+            output_template,
+            header_template,
+            content_template,
+            format,
+            separator,
+            separator_style,
+            language_map,
+            group_by,
+            group_header,
+            path_style,
+            relative_to,
+            encoding,
+            on_invalid_utf8,
+            no_yekignore,
+            no_export_ignore,
+            fill,
+            head,
+            tail,
+            max_line_length,
+            wrap,
+            line_numbers,
+            dedupe,
+            priority_rules,
+            follow_symlinks,
+            hidden,
+            include_binary,
+            binary_extensions,
+            git_boost_max,
+            git_recent,
+            show_authors,
+            show_mtime,
+            mtime_format,
+            since,
+            files_from,
+            full_tree,
+            tree_header,
+            tree_only,
+            diff_tree,
+            headers_only,
+            toc,
+            git_header,
+            tree_matches_output,
+            tree_mark_dropped,
+            tree_sizes,
+            tree_counts,
+            tree_depth,
+            tree_style,
+            tree_indent,
+            tree_connectors,
+            tree_include_empty_dirs,
+            tree_min_size,
+            tree_dir_marker,
+            no_tree_trailing_slash,
+            root_name,
+            color,
+            tree_collapse,
+            tree_sort,
+            tree_dirs_first,
+            tree_order,
+            tree_sort_case,
+            tree_header_text,
+            tree_footer_text,
+            sort,
+            order,
+            split_output,
+            split_repeat_tree,
+            dry_run,
+            count_only,
+            lang_stats,
+            lang_stats_only,
+            fail_on_empty,
+            manifest,
+            stats,
+            stats_stderr,
+            jobs,
+            cache_dir,
+            no_cache,
+            clear_cache,
+            clipboard,
+            interactive,
+            watch,
+            watch_debounce,
+            max_git_depth;
+            extend:
+            transform,
+            redact,
+            ignore_patterns,
+            include,
+            exclude,
+            filter,
+            only,
+            ext,
+            no_ext,
+            unignore_patterns,
+            priority,
+            budget,
+            pin,
+            truncate,
+        );
+        Ok(())
+    }
+
+    /// The config file in effect: `--config`/`--config-file` if given, then `YEK_CONFIG`,
+    /// otherwise the first yek.toml, yek.yaml or yek.json in the current directory, then at
+    /// the scan root (the first input path). `None` with `--no-config` or when nothing is
+    /// found.
+    pub fn find_config_file(&self) -> Option<PathBuf> {
+        if self.no_config {
+            return None;
+        }
+        if let Some(path) = &self.config {
+            return Some(PathBuf::from(path));
+        }
+        if let Some(path) = std::env::var_os(CONFIG_ENV_VAR).filter(|v| !v.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+
+        let scan_root = self.input_paths.first().map(|input| {
+            let path = Path::new(input);
+            if path.is_dir() {
+                path
+            } else {
+                path.parent().unwrap_or(Path::new("."))
+            }
+        });
+        [Some(Path::new(".")), scan_root]
+            .into_iter()
+            .flatten()
+            .flat_map(|dir| CONFIG_FILE_NAMES.iter().map(move |name| dir.join(name)))
+            .find(|candidate| candidate.is_file())
+    }
+
     /// Ensure output directory exists and is valid. Returns the resolved output directory path.
     pub fn ensure_output_dir(&self) -> Result<String> {
         if self.stream {
@@ -177,17 +1501,53 @@ impl YekConfig {
             std::process::exit(0);
         }
 
+        // Layer the config file (explicit, current dir or scan root) under the CLI flags
+        let args: Vec<String> = std::env::args().collect();
+        if let Some(path) = cfg.find_config_file() {
+            if let Err(e) = cfg.apply_config_file(&path, &args) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+
+        // Ignore globs from the environment add to those from the flags and config file
+        if let Ok(value) = std::env::var(IGNORE_ENV_VAR) {
+            cfg.ignore_patterns.extend(env_ignore_patterns(&value));
+        }
+
         // 2) compute derived fields:
+        cfg.apply_model_preset();
         cfg.token_mode = !cfg.tokens.is_empty();
-        let force_tty = std::env::var("FORCE_TTY").is_ok();
+        let stdout_is_terminal =
+            std::env::var("FORCE_TTY").is_ok() || std::io::stdout().is_terminal();
 
-        cfg.stream = !std::io::stdout().is_terminal() && !force_tty;
+        // A tree is short enough to read in the terminal, so --tree-only prints it there
+        // unless told where to write it
+        let tree_to_terminal =
+            cfg.tree_only && stdout_is_terminal && cfg.output.is_none() && cfg.output_dir.is_none();
+        cfg.stream = (!stdout_is_terminal || tree_to_terminal) && cfg.output.is_none();
+
+        // Files are never colored
+        cfg.tree_color = cfg.tree_only
+            && !cfg.json_output()
+            && match cfg.color {
+                ColorChoice::Always => cfg.stream,
+                ColorChoice::Never => false,
+                ColorChoice::Auto => {
+                    tree_to_terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                }
+            };
 
         // default input dirs to current dir if none:
         if cfg.input_paths.is_empty() {
             cfg.input_paths.push(".".to_string());
         }
 
+        // Token counts are cached under the user's cache directory unless told otherwise
+        if cfg.cache_dir.is_none() {
+            cfg.cache_dir = default_cache_dir().map(|dir| dir.to_string_lossy().to_string());
+        }
+
         // Extend binary extensions with the built-in list:
         let mut merged_bins = BINARY_FILE_EXTENSIONS
             .iter()
@@ -212,8 +1572,8 @@ impl YekConfig {
         cfg.ignore_patterns
             .extend(cfg.unignore_patterns.iter().map(|pat| format!("!{}", pat)));
 
-        // Handle output directory setup
-        if !cfg.stream {
+        // Handle output directory setup (not needed when --output names the file)
+        if !cfg.stream && cfg.output.is_none() {
             match cfg.ensure_output_dir() {
                 Ok(dir) => cfg.output_dir = Some(dir),
                 Err(e) => {
@@ -302,6 +1662,181 @@ impl YekConfig {
                 "output_template: must contain FILE_PATH and FILE_CONTENT"
             ));
         }
+        unescape_template(&self.output_template)?;
+
+        for (field, template) in [
+            ("header_template", &self.header_template),
+            ("content_template", &self.content_template),
+        ] {
+            let Some(template) = template else { continue };
+            if self.output_template != DEFAULT_OUTPUT_TEMPLATE {
+                return Err(anyhow!(
+                    "{}: cannot be combined with --output-template",
+                    field
+                ));
+            }
+            if let Err(e) = unescape_template(template) {
+                // The message names the field that was checked
+                return Err(anyhow!(
+                    "{}",
+                    e.to_string().replacen("output_template", field, 1)
+                ));
+            }
+        }
+        if let Some(header) = &self.header_template {
+            if !header.contains("FILE_PATH") || header.contains("FILE_CONTENT") {
+                return Err(anyhow!(
+                    "header_template: must contain FILE_PATH and not FILE_CONTENT \
+                     (use --content-template for the body)"
+                ));
+            }
+            if self.format != OutputFormat::Text || self.json_output() {
+                return Err(anyhow!("header_template: only applies to --format text"));
+            }
+            if self.uses_custom_separator() {
+                return Err(anyhow!(
+                    "header_template: cannot be combined with --separator or --separator-style"
+                ));
+            }
+        }
+        if let Some(content) = &self.content_template {
+            if !content.contains("FILE_CONTENT") {
+                return Err(anyhow!("content_template: must contain FILE_CONTENT"));
+            }
+        }
+
+        if chrono::format::StrftimeItems::new(&self.mtime_format)
+            .any(|item| item == chrono::format::Item::Error)
+        {
+            return Err(anyhow!(
+                "mtime_format: Invalid format '{}'",
+                self.mtime_format
+            ));
+        }
+
+        if self.uses_custom_separator() {
+            if self.output_template != DEFAULT_OUTPUT_TEMPLATE {
+                return Err(anyhow!(
+                    "separator: cannot be combined with a custom --output-template"
+                ));
+            }
+            if self.format != OutputFormat::Text {
+                return Err(anyhow!("separator: only applies to --format text"));
+            }
+            if self.separator.trim().is_empty() || self.separator.contains('\n') {
+                return Err(anyhow!("separator: must be non-empty and fit on one line"));
+            }
+        }
+
+        if let Some(model) = &self.model {
+            if find_model(model).is_none() {
+                return Err(anyhow!(
+                    "model: Unknown model '{}'. Supported models: {}",
+                    model,
+                    supported_models()
+                ));
+            }
+        }
+
+        if self.jobs == Some(0) {
+            return Err(anyhow!("jobs: must be at least 1"));
+        }
+
+        if self.quiet && (self.verbose > 0 || self.debug) {
+            return Err(anyhow!("verbose: cannot be combined with --quiet"));
+        }
+
+        if self.tree_mark_dropped && self.tree_matches_output {
+            return Err(anyhow!(
+                "tree_mark_dropped: cannot be combined with --tree-matches-output"
+            ));
+        }
+
+        if self.interactive && self.watch {
+            return Err(anyhow!("interactive: cannot be combined with --watch"));
+        }
+
+        if self.fail_on_empty && self.watch {
+            return Err(anyhow!("fail_on_empty: cannot be combined with --watch"));
+        }
+
+        if self.watch && self.files_from.as_deref() == Some("-") {
+            return Err(anyhow!(
+                "watch: cannot re-read --files-from from stdin; pass a file instead"
+            ));
+        }
+
+        if self.max_files == Some(0) {
+            return Err(anyhow!("max_files: cannot be 0"));
+        }
+
+        if let Some(limit) = &self.max_file_size {
+            ByteSize::from_str(limit)
+                .map_err(|e| anyhow!("max_file_size: Invalid size format: {}", e))?;
+        }
+
+        if self.tree_dir_marker.contains('\n') {
+            return Err(anyhow!("tree_dir_marker: must fit on one line"));
+        }
+        if let Some(name) = &self.root_name {
+            if name.trim().is_empty() {
+                return Err(anyhow!("root_name: cannot be empty"));
+            }
+            if name.contains('\n') {
+                return Err(anyhow!("root_name: must fit on one line"));
+            }
+        }
+        if self.tree_indent.is_some_and(|indent| indent < 2) {
+            return Err(anyhow!("tree_indent: must be at least 2"));
+        }
+        if let Some(connectors) = &self.tree_connectors {
+            let strings = [
+                &connectors.branch,
+                &connectors.last,
+                &connectors.vertical,
+                &connectors.blank,
+            ];
+            if strings
+                .into_iter()
+                .flatten()
+                .any(|s| s.contains(['\n', '\r']))
+            {
+                return Err(anyhow!("tree_connectors: must fit on one line"));
+            }
+        }
+        if self.no_tree_trailing_slash && self.tree_dir_marker != "/" {
+            return Err(anyhow!(
+                "no_tree_trailing_slash: cannot be combined with --tree-dir-marker"
+            ));
+        }
+
+        if let Some(size) = &self.tree_min_size {
+            ByteSize::from_str(size)
+                .map_err(|e| anyhow!("tree_min_size: Invalid size format: {}", e))?;
+        }
+
+        if let Some(size) = &self.confirm_over {
+            ByteSize::from_str(size)
+                .map_err(|e| anyhow!("confirm_over: Invalid size format: {}", e))?;
+        }
+
+        if !(1..=100).contains(&self.outlier_percentile) {
+            return Err(anyhow!("outlier_percentile: must be between 1 and 100"));
+        }
+
+        if self.outlier_factor.is_nan() || self.outlier_factor <= 0.0 {
+            return Err(anyhow!("outlier_factor: must be greater than 0"));
+        }
+
+        if self.generated_line_length == 0 {
+            return Err(anyhow!("generated_line_length: cannot be 0"));
+        }
+
+        for spec in &self.transform {
+            parse_transform_rule(spec)?;
+        }
+
+        self.redact_patterns()?;
 
         if self.max_size == "0" {
             return Err(anyhow!("max_size: cannot be 0"));
@@ -330,7 +1865,7 @@ impl YekConfig {
         }
 
         // If not streaming, validate output directory
-        if !self.stream {
+        if !self.stream && self.output.is_none() {
             self.ensure_output_dir()?;
         }
 
@@ -340,6 +1875,70 @@ impl YekConfig {
                 .map_err(|e| anyhow!("ignore_patterns: Invalid pattern '{}': {}", pattern, e))?;
         }
 
+        if let Some(label) = &self.encoding {
+            encoding_for_label(label)?;
+        }
+
+        // Validate include/exclude/only globs
+        for (field, patterns) in [
+            ("include", &self.include),
+            ("exclude", &self.exclude),
+            ("filter", &self.filter),
+            ("only", &self.only),
+        ] {
+            let mut builder = ignore::gitignore::GitignoreBuilder::new("");
+            for pattern in patterns {
+                builder
+                    .add_line(None, pattern)
+                    .map_err(|e| anyhow!("{}: Invalid pattern '{}': {}", field, pattern, e))?;
+            }
+        }
+
+        for (field, values) in [("ext", &self.ext), ("no_ext", &self.no_ext)] {
+            if let Some(bad) = parse_extensions(values)
+                .into_iter()
+                .find(|ext| ext.contains(['/', '\\', '*', '?', '[']))
+            {
+                return Err(anyhow!("{}: Invalid extension '{}'", field, bad));
+            }
+        }
+
+        if self.sort.is_some() && self.order != OutputOrder::Priority {
+            return Err(anyhow!("order: cannot be combined with --sort"));
+        }
+
+        // Validate ordering weights
+        for spec in &self.priority {
+            parse_priority_weight(spec)?;
+        }
+
+        // Validate budget reservations, which cannot add up to more than the whole budget
+        let mut reserved = 0;
+        for spec in &self.budget {
+            reserved += parse_budget_rule(spec)?.percent;
+        }
+        if reserved > 100 {
+            return Err(anyhow!(
+                "budget: Reservations add up to {}%, more than 100%",
+                reserved
+            ));
+        }
+        if self.fill == BudgetFill::Priority && !self.budget.is_empty() {
+            return Err(anyhow!("fill: priority cannot be combined with --budget"));
+        }
+
+        // Validate truncation rules
+        for spec in &self.truncate {
+            parse_truncate_rule(spec)?;
+        }
+
+        if self.max_line_length == Some(0) {
+            return Err(anyhow!("max_line_length: cannot be 0"));
+        }
+        if self.wrap && self.max_line_length.is_none() {
+            return Err(anyhow!("wrap: requires --max-line-length"));
+        }
+
         // Validate priority rules
         for rule in &self.priority_rules {
             if rule.score < 0 || rule.score > 1000 {
@@ -358,13 +1957,136 @@ impl YekConfig {
             return Err(anyhow!("tree_header and tree_only cannot both be enabled"));
         }
 
+        if self.count_only && self.dry_run {
+            return Err(anyhow!("count_only: cannot be combined with --dry-run"));
+        }
+
+        if self.lang_stats_only && (self.count_only || self.dry_run || self.tree_only) {
+            return Err(anyhow!(
+                "lang_stats_only: cannot be combined with --count-only, --dry-run or --tree-only"
+            ));
+        }
+
+        if self.diff_tree.is_some() {
+            if self.tree_only || self.count_only || self.dry_run || self.lang_stats_only {
+                return Err(anyhow!(
+                    "diff_tree: cannot be combined with --tree-only, --count-only, --dry-run or \
+                     --lang-stats-only"
+                ));
+            }
+            if self.json_output() || self.split_output.is_some() || self.watch {
+                return Err(anyhow!(
+                    "diff_tree: cannot be combined with JSON output, split_output or --watch"
+                ));
+            }
+            if self.input_paths.len() > 1 || self.uses_file_list() {
+                return Err(anyhow!("diff_tree: requires a single input directory"));
+            }
+        }
+
+        if let Some(manifest) = &self.manifest {
+            if manifest.is_empty() {
+                return Err(anyhow!("manifest: cannot be empty (use - for stdout)"));
+            }
+            if self.count_only || self.dry_run || self.tree_only {
+                return Err(anyhow!(
+                    "manifest: cannot be combined with --count-only, --dry-run or --tree-only"
+                ));
+            }
+        }
+
+        if let Some(output) = &self.output {
+            if self.split_output.is_some() {
+                return Err(anyhow!("output: cannot be combined with split_output"));
+            }
+            if self.clipboard {
+                return Err(anyhow!("output: cannot be combined with --clipboard"));
+            }
+            if output.is_empty() || Path::new(output).is_dir() {
+                return Err(anyhow!("output: '{}' is not a file path", output));
+            }
+        }
+
+        if self.clipboard && self.split_output.is_some() {
+            return Err(anyhow!("clipboard: cannot be combined with split_output"));
+        }
+
+        if self.split_output.is_some() && self.tree_only {
+            return Err(anyhow!("split_output: cannot be combined with tree_only"));
+        }
+
+        if self.headers_only {
+            if self.tree_only {
+                return Err(anyhow!("headers_only: cannot be combined with --tree-only"));
+            }
+            if self.json_output() {
+                return Err(anyhow!("headers_only: cannot be combined with JSON output"));
+            }
+            if self.split_output.is_some() {
+                return Err(anyhow!(
+                    "headers_only: cannot be combined with split_output"
+                ));
+            }
+        }
+
         // Validate JSON output is not used with tree modes
         if self.json && self.tree_header {
             return Err(anyhow!("JSON output not supported with tree header mode"));
         }
 
-        if self.json && self.tree_only {
-            return Err(anyhow!("JSON output not supported in tree-only mode"));
+        if self.json_lines {
+            if self.json || self.format == OutputFormat::Json {
+                return Err(anyhow!(
+                    "json_lines: cannot be combined with --json or --format json"
+                ));
+            }
+            if self.split_output.is_some() {
+                return Err(anyhow!("json_lines: cannot be combined with split_output"));
+            }
+        }
+
+        if self.format == OutputFormat::Json {
+            if self.json {
+                return Err(anyhow!("format: json cannot be combined with --json"));
+            }
+            if self.split_output.is_some() {
+                return Err(anyhow!("format: json cannot be combined with split_output"));
+            }
+        }
+
+        if self.format == OutputFormat::Xml {
+            if self.json || self.json_lines {
+                return Err(anyhow!(
+                    "format: xml cannot be combined with --json or --json-lines"
+                ));
+            }
+            if self.split_output.is_some() {
+                return Err(anyhow!("format: xml cannot be combined with split_output"));
+            }
+        }
+
+        if self.git_header {
+            if self.json_output() || self.format == OutputFormat::Xml {
+                return Err(anyhow!(
+                    "git_header: cannot be combined with JSON or XML output"
+                ));
+            }
+            if self.split_output.is_some() {
+                return Err(anyhow!("git_header: cannot be combined with split_output"));
+            }
+        }
+
+        if self.toc {
+            // The links point at the headings of the Markdown template
+            if self.format != OutputFormat::Markdown || self.json_output() {
+                return Err(anyhow!("toc: requires --format markdown"));
+            }
+            if self.output_template != DEFAULT_OUTPUT_TEMPLATE {
+                return Err(anyhow!("toc: cannot be combined with --output-template"));
+            }
+            if self.split_output.is_some() {
+                return Err(anyhow!("toc: cannot be combined with split_output"));
+            }
         }
 
         Ok(())
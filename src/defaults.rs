@@ -152,4 +152,77 @@ pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
     "*~",
 ];
 
+/// Patterns scrubbed by `--redact-default`: AWS access keys and secret keys in
+/// assignments, JSON Web Tokens, private key blocks (or a lone header), and GitHub and
+/// Slack tokens
+pub const DEFAULT_REDACT_PATTERNS: &[&str] = &[
+    r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b",
+    r#"(?i)aws_secret_access_key["']?\s*[:=]\s*["']?[A-Za-z0-9/+=]{40}"#,
+    r"\beyJ[A-Za-z0-9_-]+\.eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+",
+    r"-----BEGIN [A-Z ]*PRIVATE KEY-----(?s:.*?-----END [A-Z ]*PRIVATE KEY-----)?",
+    r"\bgh[pousr]_[A-Za-z0-9]{36,}\b",
+    r"\bxox[abprs]-[A-Za-z0-9-]{10,}",
+];
+
+/// What each `--redact` match is replaced with
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Per-directory attributes file; paths marked `export-ignore` in it are skipped
+pub const GITATTRIBUTES_FILE_NAME: &str = ".gitattributes";
+
+/// Gitignore-syntax file that only affects yek, honored in every scanned directory
+pub const YEKIGNORE_FILE_NAME: &str = ".yekignore";
+
 pub const DEFAULT_OUTPUT_TEMPLATE: &str = ">>>> FILE_PATH\nFILE_CONTENT";
+
+/// Marker before each file's path in [`DEFAULT_OUTPUT_TEMPLATE`]
+pub const DEFAULT_SEPARATOR: &str = ">>>>";
+
+/// Separator written before each group with `--group-by`; GROUP becomes e.g. `*.rs`
+pub const DEFAULT_GROUP_HEADER: &str = "==== GROUP ====";
+
+/// Template used by `--format markdown`: a heading plus a fenced code block
+pub const MARKDOWN_OUTPUT_TEMPLATE: &str = "## FILE_PATH\n\n```FILE_LANG\nFILE_CONTENT\n```\n";
+
+/// Template used by `--format xml`; the path and content are escaped before substitution
+pub const XML_OUTPUT_TEMPLATE: &str = "<file path=\"FILE_PATH\">\nFILE_CONTENT\n</file>\n";
+
+/// File extension (or exact file name) to Markdown code fence language
+#[rustfmt::skip]
+pub const DEFAULT_LANGUAGE_MAP: &[(&str, &str)] = &[
+    // Systems
+    ("rs", "rust"), ("c", "c"), ("h", "c"), ("cc", "cpp"), ("cpp", "cpp"), ("cxx", "cpp"),
+    ("hpp", "cpp"), ("hh", "cpp"), ("go", "go"), ("zig", "zig"), ("swift", "swift"),
+    ("m", "objectivec"), ("mm", "objectivec"),
+
+    // JVM / .NET
+    ("java", "java"), ("kt", "kotlin"), ("kts", "kotlin"), ("scala", "scala"),
+    ("groovy", "groovy"), ("gradle", "groovy"), ("clj", "clojure"), ("cs", "csharp"),
+    ("fs", "fsharp"), ("vb", "vbnet"),
+
+    // Scripting
+    ("py", "python"), ("pyi", "python"), ("rb", "ruby"), ("php", "php"), ("pl", "perl"),
+    ("lua", "lua"), ("r", "r"), ("jl", "julia"), ("ex", "elixir"), ("exs", "elixir"),
+    ("erl", "erlang"), ("hs", "haskell"), ("ml", "ocaml"), ("dart", "dart"),
+
+    // Web
+    ("js", "javascript"), ("mjs", "javascript"), ("cjs", "javascript"), ("jsx", "jsx"),
+    ("ts", "typescript"), ("mts", "typescript"), ("cts", "typescript"), ("tsx", "tsx"),
+    ("html", "html"), ("htm", "html"), ("css", "css"), ("scss", "scss"), ("sass", "sass"),
+    ("less", "less"), ("vue", "vue"), ("svelte", "svelte"),
+
+    // Shell
+    ("sh", "bash"), ("bash", "bash"), ("zsh", "zsh"), ("fish", "fish"),
+    ("ps1", "powershell"), ("bat", "batch"), ("cmd", "batch"),
+
+    // Data & config
+    ("json", "json"), ("jsonc", "jsonc"), ("yaml", "yaml"), ("yml", "yaml"), ("toml", "toml"),
+    ("xml", "xml"), ("ini", "ini"), ("csv", "csv"), ("sql", "sql"), ("graphql", "graphql"),
+    ("gql", "graphql"), ("proto", "protobuf"), ("tf", "hcl"), ("hcl", "hcl"), ("nix", "nix"),
+
+    // Docs
+    ("md", "markdown"), ("markdown", "markdown"), ("rst", "rst"), ("tex", "latex"),
+
+    // Well-known file names without a useful extension
+    ("Dockerfile", "dockerfile"), ("Makefile", "makefile"), ("CMakeLists.txt", "cmake"),
+];
@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Result};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use std::borrow::Cow;
+
+/// Look up an encoding by its WHATWG label (e.g. "utf-16le", "latin1", "shift_jis")
+pub fn encoding_for_label(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| anyhow!("encoding: Unknown encoding '{}'", label))
+}
+
+/// Decode raw file bytes to UTF-8, stripping any byte-order mark.
+///
+/// With `forced`, the bytes are decoded as that encoding. Otherwise a BOM decides,
+/// then valid UTF-8 is taken as-is, and anything else is guessed with chardetng.
+/// Bytes that are not valid in the chosen encoding are an error rather than
+/// being replaced, so callers can skip the file instead of emitting mojibake.
+pub fn decode_content(bytes: &[u8], forced: Option<&'static Encoding>) -> Result<String> {
+    let (text, encoding, had_errors) = decode(bytes, forced);
+    if had_errors {
+        return Err(anyhow!("could not decode as {}", encoding.name()));
+    }
+    Ok(text.into_owned())
+}
+
+/// Like [`decode_content`], but bytes that are not valid in the chosen encoding
+/// become U+FFFD replacement characters instead of an error
+pub fn decode_content_lossy(bytes: &[u8], forced: Option<&'static Encoding>) -> String {
+    decode(bytes, forced).0.into_owned()
+}
+
+fn decode<'a>(
+    bytes: &'a [u8],
+    forced: Option<&'static Encoding>,
+) -> (Cow<'a, str>, &'static Encoding, bool) {
+    let (encoding, body) = match forced {
+        Some(encoding) => {
+            let bom_len = match Encoding::for_bom(bytes) {
+                Some((bom_encoding, len)) if bom_encoding == encoding => len,
+                _ => 0,
+            };
+            (encoding, &bytes[bom_len..])
+        }
+        None => match Encoding::for_bom(bytes) {
+            Some((encoding, bom_len)) => (encoding, &bytes[bom_len..]),
+            None if std::str::from_utf8(bytes).is_ok() => (UTF_8, bytes),
+            None => {
+                let mut detector = EncodingDetector::new();
+                detector.feed(bytes, true);
+                (detector.guess(None, true), bytes)
+            }
+        },
+    };
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(body);
+    (text, encoding, had_errors)
+}
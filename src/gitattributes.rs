@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use tracing::debug;
+
+use crate::{config::YekConfig, defaults::GITATTRIBUTES_FILE_NAME};
+
+/// The attribute that keeps a path out of `git archive`
+const EXPORT_IGNORE: &str = "export-ignore";
+
+/// Compile the `export-ignore` lines of a `.gitattributes` file in `dir` into a matcher.
+/// Paths with the attribute set match as ignored; paths where a later line unsets it
+/// (`-export-ignore` or `!export-ignore`) match as whitelisted. Returns `None` when the
+/// file never mentions the attribute. Lines git would reject are skipped.
+pub fn parse_export_ignore(dir: &Path, text: &str) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    let mut any = false;
+    for line in text.lines() {
+        let line = line.trim();
+        // Comments, and macro definitions such as "[attr]binary -diff -merge -text"
+        if line.is_empty() || line.starts_with('#') || line.starts_with("[attr]") {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let Some(pattern) = fields.next() else {
+            continue;
+        };
+        // Negative patterns are not allowed in .gitattributes
+        if pattern.starts_with('!') {
+            continue;
+        }
+        // The last mention of the attribute on a line wins
+        let mut set = None;
+        for attr in fields {
+            let (name, value) = match attr.strip_prefix(['-', '!']) {
+                Some(name) => (name, false),
+                None => (attr.split('=').next().unwrap_or(attr), true),
+            };
+            if name == EXPORT_IGNORE {
+                set = Some(value);
+            }
+        }
+        let line = match set {
+            Some(true) => pattern.to_string(),
+            Some(false) => format!("!{}", pattern),
+            None => continue,
+        };
+        match builder.add_line(None, &line) {
+            Ok(_) => any = true,
+            Err(e) => debug!("Skipping .gitattributes line '{}': {}", line, e),
+        }
+    }
+    if !any {
+        return None;
+    }
+    builder.build().ok()
+}
+
+/// The `export-ignore` attributes from the `.gitattributes` files under an input root.
+/// Each directory's file is read once, the first time a path below it is checked.
+pub struct ExportIgnore {
+    root: PathBuf,
+    enabled: bool,
+    matchers: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl ExportIgnore {
+    /// Attributes under `root`; with `--no-export-ignore` or `--only` nothing is ever matched
+    pub fn new(root: &Path, config: &YekConfig) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            enabled: !config.no_export_ignore && config.uses_ignore_rules(),
+            matchers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `path`, a file under the root, is marked `export-ignore`. As in git, the
+    /// `.gitattributes` closest to the file takes precedence, and within a file the last
+    /// matching line does. A directory marked `export-ignore` covers everything inside it.
+    pub fn ignores(&self, path: &Path) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let rel_dir = if self.root == Path::new(".") {
+            parent
+        } else {
+            match parent.strip_prefix(&self.root) {
+                Ok(rel) => rel,
+                Err(_) => return false,
+            }
+        };
+
+        let mut ignored = false;
+        let mut dir = self.root.clone();
+        let dirs = std::iter::once(None).chain(rel_dir.components().map(Some));
+        for component in dirs {
+            if let Some(component) = component {
+                dir.push(component);
+            }
+            let Some(matcher) = self.matcher(&dir) else {
+                continue;
+            };
+            match matcher.matched_path_or_any_parents(path, false) {
+                Match::Ignore(_) => ignored = true,
+                Match::Whitelist(_) => ignored = false,
+                Match::None => {}
+            }
+        }
+        ignored
+    }
+
+    fn matcher(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        let mut matchers = self.matchers.lock().unwrap_or_else(|e| e.into_inner());
+        matchers
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let text = fs::read_to_string(dir.join(GITATTRIBUTES_FILE_NAME)).ok()?;
+                parse_export_ignore(dir, &text).map(Arc::new)
+            })
+            .clone()
+    }
+}
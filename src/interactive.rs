@@ -0,0 +1,288 @@
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal},
+    ops::Range,
+};
+
+use anyhow::{anyhow, Result};
+use bytesize::ByteSize;
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout},
+    style::{Style, Stylize},
+    widgets::{List, ListItem, ListState, Paragraph},
+    Terminal,
+};
+
+use crate::{
+    collect_files, config::YekConfig, file_tokens_estimate, is_serialized,
+    parallel::to_forward_slashes,
+};
+
+/// One line of the picker: a directory, or a file by its index in [`Picker::files`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Row {
+    Dir { path: String, depth: usize },
+    File { index: usize, depth: usize },
+}
+
+/// A file offered by the picker, with what it adds to the running total
+#[derive(Debug, Clone)]
+pub struct PickerFile {
+    /// Path as it would appear in a `--files-from` list
+    pub path: String,
+    pub size: u64,
+    pub tokens: usize,
+    pub selected: bool,
+}
+
+/// What a key press asks the picker to do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Confirm,
+    Cancel,
+}
+
+/// State of the `--interactive` file picker, kept apart from the terminal so it can be tested
+pub struct Picker {
+    pub files: Vec<PickerFile>,
+    rows: Vec<Row>,
+    /// Indices of the files under each directory row, by path
+    dir_files: HashMap<String, Range<usize>>,
+    cursor: usize,
+}
+
+impl Picker {
+    /// A picker over `files`, all selected, laid out as a tree sorted by path
+    pub fn new(mut files: Vec<PickerFile>) -> Self {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut rows = Vec::new();
+        let mut dir_files: HashMap<String, Range<usize>> = HashMap::new();
+        let mut open_dirs: Vec<String> = Vec::new();
+        for (index, file) in files.iter().enumerate() {
+            let parts: Vec<&str> = file.path.split('/').collect();
+            let dirs = &parts[..parts.len() - 1];
+            // Keep the directories shared with the previous file, open the new ones
+            let shared = open_dirs
+                .iter()
+                .enumerate()
+                .take_while(|(depth, open)| {
+                    *depth < dirs.len() && **open == dirs[..=*depth].join("/")
+                })
+                .count();
+            open_dirs.truncate(shared);
+            for depth in shared..dirs.len() {
+                let path = dirs[..=depth].join("/");
+                rows.push(Row::Dir {
+                    path: path.clone(),
+                    depth,
+                });
+                dir_files.insert(path.clone(), index..index);
+                open_dirs.push(path);
+            }
+            // Sorted by path, so each directory's files follow one another
+            for dir in &open_dirs {
+                if let Some(range) = dir_files.get_mut(dir) {
+                    range.end = index + 1;
+                }
+            }
+            rows.push(Row::File {
+                index,
+                depth: dirs.len(),
+            });
+        }
+
+        Self {
+            files,
+            rows,
+            dir_files,
+            cursor: 0,
+        }
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Indices of the files at or under a row
+    fn files_under(&self, row: &Row) -> Range<usize> {
+        match row {
+            Row::File { index, .. } => *index..*index + 1,
+            Row::Dir { path, .. } => self.dir_files.get(path).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Check or uncheck the row under the cursor. A directory is checked when all of its
+    /// files are, and toggling it selects all of them, or clears them if all were selected
+    pub fn toggle(&mut self) {
+        let Some(row) = self.rows.get(self.cursor).cloned() else {
+            return;
+        };
+        let range = self.files_under(&row);
+        let files = &mut self.files[range];
+        let select = !files.iter().all(|f| f.selected);
+        for file in files {
+            file.selected = select;
+        }
+    }
+
+    pub fn select_all(&mut self, selected: bool) {
+        for file in &mut self.files {
+            file.selected = selected;
+        }
+    }
+
+    /// Apply a key press; returns the outcome once the user confirms or cancels
+    pub fn handle_key(&mut self, key: KeyCode) -> Option<Outcome> {
+        let last = self.rows.len().saturating_sub(1);
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.cursor = (self.cursor + 1).min(last),
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(10),
+            KeyCode::PageDown => self.cursor = (self.cursor + 10).min(last),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = last,
+            KeyCode::Char(' ') => self.toggle(),
+            KeyCode::Char('a') => self.select_all(true),
+            KeyCode::Char('n') => self.select_all(false),
+            KeyCode::Enter => return Some(Outcome::Confirm),
+            KeyCode::Esc | KeyCode::Char('q') => return Some(Outcome::Cancel),
+            _ => {}
+        }
+        None
+    }
+
+    /// Checkbox and name for a row, indented by depth
+    pub fn label(&self, row: &Row) -> String {
+        let files = &self.files[self.files_under(row)];
+        let selected = files.iter().filter(|f| f.selected).count();
+        let mark = if selected == 0 {
+            "[ ]"
+        } else if selected == files.len() {
+            "[x]"
+        } else {
+            "[-]"
+        };
+        let (name, depth) = match row {
+            Row::Dir { path, depth } => (
+                format!("{}/", path.rsplit('/').next().unwrap_or(path)),
+                *depth,
+            ),
+            Row::File { index, depth } => {
+                let path = &self.files[*index].path;
+                (path.rsplit('/').next().unwrap_or(path).to_string(), *depth)
+            }
+        };
+        format!("{}{} {}", "  ".repeat(depth), mark, name)
+    }
+
+    /// Running total, e.g. "3/5 files, 1.2 KiB, ~310 tokens"
+    pub fn summary(&self) -> String {
+        let selected: Vec<&PickerFile> = self.files.iter().filter(|f| f.selected).collect();
+        format!(
+            "{}/{} files, {}, ~{} tokens",
+            selected.len(),
+            self.files.len(),
+            ByteSize::b(selected.iter().map(|f| f.size).sum()),
+            selected.iter().map(|f| f.tokens).sum::<usize>()
+        )
+    }
+
+    /// Paths of the selected files, in tree order
+    pub fn selected_paths(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|f| f.selected)
+            .map(|f| f.path.clone())
+            .collect()
+    }
+}
+
+/// The picker's starting point: every file yek would serialize with the current config
+pub fn picker_files(config: &YekConfig) -> Result<Vec<PickerFile>> {
+    Ok(collect_files(config)?
+        .iter()
+        .filter(|f| is_serialized(f, config))
+        .map(|f| {
+            let path = to_forward_slashes(&f.disk_path.to_string_lossy());
+            PickerFile {
+                path: path.strip_prefix("./").unwrap_or(&path).to_string(),
+                size: f.content.len() as u64,
+                tokens: file_tokens_estimate(&f.content, config),
+                selected: true,
+            }
+        })
+        .collect())
+}
+
+/// Run the picker on the terminal. It draws on stderr so stdout stays free for the output.
+/// Returns the chosen paths, or `None` if the user cancelled.
+pub fn pick_files(config: &YekConfig) -> Result<Option<Vec<String>>> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(anyhow!("interactive: needs a terminal on stdin and stderr"));
+    }
+    let mut picker = Picker::new(picker_files(config)?);
+
+    enable_raw_mode()?;
+    execute!(io::stderr(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stderr()))?;
+    let outcome = run_picker(&mut terminal, &mut picker);
+    disable_raw_mode()?;
+    execute!(io::stderr(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    Ok(match outcome? {
+        Outcome::Confirm => Some(picker.selected_paths()),
+        Outcome::Cancel => None,
+    })
+}
+
+fn run_picker(
+    terminal: &mut Terminal<CrosstermBackend<io::Stderr>>,
+    picker: &mut Picker,
+) -> Result<Outcome> {
+    let mut state = ListState::default();
+    loop {
+        state.select(Some(picker.cursor()));
+        terminal.draw(|frame| {
+            let [header, body, footer] = Layout::vertical([
+                Constraint::Length(1),
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ])
+            .areas(frame.area());
+
+            frame.render_widget(Paragraph::new(picker.summary()).bold(), header);
+            let items: Vec<ListItem> = picker
+                .rows()
+                .iter()
+                .map(|row| ListItem::new(picker.label(row)))
+                .collect();
+            let list = List::new(items).highlight_style(Style::new().reversed());
+            frame.render_stateful_widget(list, body, &mut state);
+            frame.render_widget(
+                Paragraph::new("↑/↓ move  space toggle  a all  n none  enter serialize  q cancel")
+                    .dim(),
+                footer,
+            );
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                if let Some(outcome) = picker.handle_key(key.code) {
+                    return Ok(outcome);
+                }
+            }
+        }
+    }
+}
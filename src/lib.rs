@@ -2,35 +2,72 @@ use anyhow::anyhow;
 use anyhow::Result;
 use bytesize::ByteSize;
 use content_inspector::{inspect, ContentType};
+use normalize_path::NormalizePath;
 use rayon::prelude::*;
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, Read},
-    path::Path,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
     str::FromStr,
-    sync::OnceLock,
+    sync::{atomic::AtomicUsize, OnceLock},
+    time::SystemTime,
 };
 use tiktoken_rs::CoreBPE;
 
+pub mod atomic;
+pub mod budget;
+pub mod cache;
+pub mod clipboard;
+pub mod compress;
 pub mod config;
 pub mod defaults;
+pub mod encoding;
+pub mod gitattributes;
+pub mod interactive;
+pub mod models;
 pub mod parallel;
 pub mod priority;
+pub mod progress;
+pub mod split;
+pub mod strip;
+pub mod toc;
+pub mod transform;
 pub mod tree;
+pub mod truncate;
+pub mod unpack;
+pub mod watch;
 
-use config::YekConfig;
-use parallel::{process_files_parallel, ProcessedFile};
-use priority::compute_recentness_boost;
-use tree::generate_tree;
+use budget::{budget_group, reservations, BudgetRule};
+use config::{BudgetFill, OutputFormat, OutputOrder, OversizeAction, PathStyle, YekConfig};
+use defaults::{DEFAULT_LANGUAGE_MAP, REDACTED};
+use models::{estimate_tokens, TokenEstimator, Tokenizer};
+use parallel::{
+    collect_directories, glob_root, is_walked_file, process_file_list,
+    process_files_parallel_counting, to_forward_slashes, ProcessedFile,
+};
+use priority::{
+    compute_recentness_boost, get_changed_paths, get_last_commits, repo_header, LastCommit,
+};
+use progress::ScanProgress;
+use tree::{
+    generate_diff_tree, generate_tree_json, generate_tree_with_options, TreeDirsFirst, TreeEntry,
+    TreeOptions, TreeSort,
+};
 
-// Add a static BPE encoder for reuse
-static TOKENIZER: OnceLock<CoreBPE> = OnceLock::new();
+// Static BPE encoders for reuse, one per encoding
+static CL100K_TOKENIZER: OnceLock<CoreBPE> = OnceLock::new();
+static O200K_TOKENIZER: OnceLock<CoreBPE> = OnceLock::new();
 
-fn get_tokenizer() -> &'static CoreBPE {
-    TOKENIZER.get_or_init(|| {
-        tiktoken_rs::get_bpe_from_model("gpt-3.5-turbo").expect("Failed to load tokenizer")
-    })
+fn get_tokenizer(tokenizer: Tokenizer) -> &'static CoreBPE {
+    match tokenizer {
+        Tokenizer::Cl100k => CL100K_TOKENIZER
+            .get_or_init(|| tiktoken_rs::cl100k_base().expect("Failed to load tokenizer")),
+        Tokenizer::O200k => O200K_TOKENIZER
+            .get_or_init(|| tiktoken_rs::o200k_base().expect("Failed to load tokenizer")),
+    }
 }
 
 /// Check if a file is likely text or binary by reading only a small chunk.
@@ -55,6 +92,47 @@ pub fn is_text_file(path: &Path, user_binary_extensions: &[String]) -> io::Resul
 
 /// Main entrypoint for serialization, used by CLI and tests
 pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)> {
+    let (mut files, ignored) = collect_files_counting_ignored(config)?;
+
+    // Build the final output string (with the --stats footer, if any)
+    let mut output = Vec::new();
+    write_output(&files, ignored, config, &mut output)?;
+    let output_string = String::from_utf8(output)?;
+
+    // Binaries (and unchanged files with --full-tree) only feed the tree
+    report_skipped_binaries(&files, config);
+    files.retain(|f| is_serialized(f, config));
+
+    // Only count tokens if debug logging is enabled
+    if tracing::Level::DEBUG <= tracing::level_filters::STATIC_MAX_LEVEL {
+        tracing::debug!(
+            "{} tokens generated",
+            count_config_tokens(&output_string, config)
+        );
+    }
+
+    Ok((output_string, files))
+}
+
+/// Like [`serialize_repo`], but streams the output to `out` instead of returning it
+pub fn serialize_repo_to<W: Write>(config: &YekConfig, out: &mut W) -> Result<Vec<ProcessedFile>> {
+    let (mut files, ignored) = collect_files_counting_ignored(config)?;
+    write_output(&files, ignored, config, out)?;
+
+    report_skipped_binaries(&files, config);
+    files.retain(|f| is_serialized(f, config));
+    Ok(files)
+}
+
+/// Gather, read and prioritize every candidate file from the configured input paths.
+/// Binary files are included (flagged) so the tree can list them.
+pub fn collect_files(config: &YekConfig) -> Result<Vec<ProcessedFile>> {
+    Ok(collect_files_counting_ignored(config)?.0)
+}
+
+/// Same as [`collect_files`], also returning how many files were skipped by ignore
+/// patterns and `--include`/`--exclude` filters (for `--stats`)
+pub fn collect_files_counting_ignored(config: &YekConfig) -> Result<(Vec<ProcessedFile>, usize)> {
     // Gather commit times from each input path that is a directory
     let combined_commit_times = config
         .input_paths
@@ -78,19 +156,106 @@ pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)
         compute_recentness_boost(&combined_commit_times, config.git_boost_max.unwrap_or(100));
 
     // Process files in parallel for each input path
-    let merged_files = config
-        .input_paths
-        .par_iter()
-        .map(|path_str| {
-            let path = Path::new(path_str);
-            process_files_parallel(path, config, &recentness_boost)
-        })
-        .collect::<Result<Vec<Vec<ProcessedFile>>>>()?
-        .into_iter()
-        .flatten()
-        .collect::<Vec<ProcessedFile>>();
+    let ignored = AtomicUsize::new(0);
+    let progress = ScanProgress::new(config);
+    let listed = match (&config.picked_files, &config.files_from) {
+        (Some(picked), _) => Some(picked.clone()),
+        (None, Some(source)) => Some(read_file_list(source)?),
+        (None, None) => None,
+    };
+    // Only several input paths or a file list can name the same file twice
+    let may_overlap = listed.is_some() || config.input_paths.len() > 1;
+    let files = match listed {
+        Some(paths) => process_file_list(&paths, config, &progress),
+        None => config
+            .input_paths
+            .par_iter()
+            .zip(root_prefixes(&config.input_paths))
+            .map(|(path_str, prefix)| {
+                let path = Path::new(path_str);
+                let mut files = process_files_parallel_counting(
+                    path,
+                    config,
+                    &recentness_boost,
+                    &ignored,
+                    &progress,
+                )?;
+                if let Some(prefix) = prefix {
+                    for file in &mut files {
+                        file.rel_path = format!("{}/{}", prefix, file.rel_path);
+                    }
+                }
+                Ok(files)
+            })
+            .collect::<Result<Vec<Vec<ProcessedFile>>>>()
+            .map(|files| files.into_iter().flatten().collect::<Vec<ProcessedFile>>()),
+    };
+    progress.finish();
+    let mut files = files?;
+
+    if may_overlap {
+        let collapsed = dedupe_overlapping_inputs(&mut files);
+        if collapsed > 0 {
+            tracing::debug!(
+                "Collapsed {} file(s) covered by more than one input path",
+                collapsed
+            );
+        }
+    }
+
+    if let Some(reference) = &config.since {
+        apply_since(&mut files, reference, config)?;
+    }
+
+    if config.git_recent || config.show_authors {
+        apply_last_commits(&mut files, config);
+    }
+
+    // Before transforms, which may empty a file that was not
+    if config.exclude_empty_files {
+        apply_exclude_empty(&mut files);
+    }
+
+    apply_transforms(&mut files, config)?;
+
+    if config.strip_comments || config.strip_blank_lines {
+        apply_stripping(&mut files, config);
+    }
+
+    // Redact before anything measures or renders the content
+    apply_redaction(&mut files, config)?;
+
+    if config.skip_generated {
+        apply_skip_generated(&mut files, config);
+    }
+
+    if let Some(limit) = config.max_file_size_bytes() {
+        apply_max_file_size(&mut files, limit, config);
+    }
+
+    if config.drop_outliers {
+        apply_drop_outliers(&mut files, config);
+    }
+
+    // Number lines before truncating, so kept lines keep their original numbers
+    if config.line_numbers {
+        files
+            .par_iter_mut()
+            .filter(|f| !f.binary)
+            .for_each(|file| file.content = number_lines(&file.content));
+    }
+
+    apply_truncation(&mut files, config);
+
+    if config.dedupe {
+        apply_dedupe(&mut files, config);
+    }
 
-    let mut files = merged_files;
+    for pin in config.pinned_paths() {
+        if !files.iter().any(|f| f.rel_path == pin) {
+            tracing::warn!("--pin {}: not among the input files", pin);
+        }
+    }
 
     // Sort final (priority asc, then file_index asc)
     files.par_sort_by(|a, b| {
@@ -99,147 +264,2259 @@ pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)
             .then_with(|| a.rel_path.cmp(&b.rel_path))
     });
 
-    // Build the final output string
-    let output_string = concat_files(&files, config)?;
+    Ok((files, ignored.into_inner()))
+}
 
-    // Only count tokens if debug logging is enabled
-    if tracing::Level::DEBUG <= tracing::level_filters::STATIC_MAX_LEVEL {
-        tracing::debug!("{} tokens generated", count_tokens(&output_string));
+/// With several input paths, files under each input directory are listed under that
+/// directory's name (`frontend/src/main.rs`), so roots cannot collide in the output or the
+/// tree. Roots that share a name use the path as given instead. Single files and a lone
+/// input keep their plain relative paths.
+pub fn root_prefixes(input_paths: &[String]) -> Vec<Option<String>> {
+    let names: Vec<Option<String>> = input_paths
+        .iter()
+        .map(|input| {
+            let path = Path::new(input);
+            if input_paths.len() < 2 || !path.is_dir() {
+                return None;
+            }
+            std::path::absolute(path)
+                .ok()?
+                .normalize()
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .collect();
+
+    names
+        .iter()
+        .zip(input_paths)
+        .map(|(name, input)| {
+            let name = name.as_ref()?;
+            if names.iter().filter(|n| n.as_ref() == Some(name)).count() > 1 {
+                let given = Path::new(input).normalize();
+                Some(to_forward_slashes(&given.to_string_lossy()))
+            } else {
+                Some(name.clone())
+            }
+        })
+        .collect()
+}
+
+/// Read the `--files-from` list: one path per line from a file, or stdin for "-".
+/// Blank lines are skipped.
+pub fn read_file_list(source: &str) -> Result<Vec<String>> {
+    let list = if source == "-" {
+        let mut list = String::new();
+        io::stdin()
+            .read_to_string(&mut list)
+            .map_err(|e| anyhow!("files_from: cannot read stdin: {}", e))?;
+        list
+    } else {
+        std::fs::read_to_string(source)
+            .map_err(|e| anyhow!("files_from: cannot read '{}': {}", source, e))?
+    };
+    Ok(list
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Prefix every line with its right-aligned number, e.g. `  12 | let x = 1;`.
+/// The width follows the file's line count.
+pub fn number_lines(content: &str) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let width = lines.len().to_string().len();
+    let mut numbered = String::with_capacity(content.len() + lines.len() * (width + 3));
+    for (i, line) in lines.iter().enumerate() {
+        numbered.push_str(&format!("{:>width$} | {}", i + 1, line));
     }
+    numbered
+}
 
-    Ok((output_string, files))
+/// Cut matching files down to their `--truncate` line limit, then apply the
+/// `--head`/`--tail` preview to every file
+fn apply_truncation(files: &mut [ProcessedFile], config: &YekConfig) {
+    let rules = config.truncate_rules();
+    let preview = config.head.is_some() || config.tail.is_some();
+    if rules.is_empty() && !preview && config.max_line_length.is_none() {
+        return;
+    }
+    files.par_iter_mut().filter(|f| !f.binary).for_each(|file| {
+        if let Some(lines) = truncate::truncate_limit(&file.rel_path, &rules) {
+            file.content = truncate::truncate_lines(&file.content, lines);
+        }
+        if preview {
+            file.content = truncate::head_tail_lines(
+                &file.content,
+                config.head.unwrap_or(0),
+                config.tail.unwrap_or(0),
+            );
+        }
+        if let Some(max) = config.max_line_length {
+            file.content = truncate::limit_line_length(&file.content, max, config.wrap);
+        }
+    });
 }
 
-pub fn concat_files(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<String> {
-    // Generate tree header if requested
-    let tree_header = if config.tree_header || config.tree_only {
-        let file_paths: Vec<std::path::PathBuf> = files
+/// Keep one entry per file when input paths overlap (a directory and a file inside it, or
+/// the same path given twice): the first, in input order. Files are matched by their
+/// canonical directory and name, so separate symlinks to one file are still kept apart.
+/// Returns how many entries were removed.
+fn dedupe_overlapping_inputs(files: &mut Vec<ProcessedFile>) -> usize {
+    let identities: Vec<PathBuf> = files
+        .par_iter()
+        .map(|f| {
+            let dir = match f.disk_path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            match (dir.canonicalize(), f.disk_path.file_name()) {
+                (Ok(dir), Some(name)) => dir.join(name),
+                _ => f.disk_path.clone(),
+            }
+        })
+        .collect();
+    let before = files.len();
+    let mut seen = HashSet::new();
+    let mut first = identities.into_iter().map(|identity| seen.insert(identity));
+    files.retain(|_| first.next().unwrap_or(true));
+    before - files.len()
+}
+
+/// Pipe text files through the `--transform` commands whose globs match them, in the
+/// order given. A failing command fails the run, or with `--transform-best-effort` leaves
+/// that file's original content in place and logs a warning.
+fn apply_transforms(files: &mut [ProcessedFile], config: &YekConfig) -> Result<()> {
+    let rules = config.transform_rules();
+    if rules.is_empty() {
+        return Ok(());
+    }
+    files
+        .par_iter_mut()
+        .filter(|f| !f.binary && f.symlink_target.is_none())
+        .try_for_each(|file| {
+            // Only replace the content once every matching command has succeeded
+            let mut transformed: Option<String> = None;
+            for rule in rules.iter().filter(|r| r.pattern.matches(&file.rel_path)) {
+                let input = transformed.as_deref().unwrap_or(&file.content);
+                match transform::run_transform(&rule.command, &file.rel_path, input) {
+                    Ok(output) => transformed = Some(output),
+                    Err(e) if config.transform_best_effort => {
+                        tracing::warn!("{}; keeping the original content", e);
+                        return Ok(());
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            if let Some(content) = transformed {
+                file.content = content;
+            }
+            Ok(())
+        })
+}
+
+/// Remove comments (`--strip-comments`, for languages with known comment syntax) and
+/// blank lines (`--strip-blank-lines`) from text files, recording the bytes removed
+fn apply_stripping(files: &mut [ProcessedFile], config: &YekConfig) {
+    files
+        .par_iter_mut()
+        .filter(|f| !f.binary && f.symlink_target.is_none())
+        .for_each(|file| {
+            let before = file.content.len();
+            if config.strip_comments {
+                if let Some(syntax) = strip::comment_syntax(Path::new(&file.rel_path)) {
+                    file.content = strip::strip_comments(&file.content, &syntax);
+                }
+            }
+            if config.strip_blank_lines {
+                file.content = strip::strip_blank_lines(&file.content);
+            }
+            file.stripped_bytes += before.saturating_sub(file.content.len());
+        });
+}
+
+/// Replace every `--redact` match in text files with [REDACTED], counting the matches
+/// per file. Patterns run in order, each over the previous one's result.
+fn apply_redaction(files: &mut [ProcessedFile], config: &YekConfig) -> Result<()> {
+    let patterns = config.redact_patterns()?;
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    files.par_iter_mut().filter(|f| !f.binary).for_each(|file| {
+        for pattern in &patterns {
+            let matches = pattern.find_iter(&file.content).count();
+            if matches > 0 {
+                file.content = pattern.replace_all(&file.content, REDACTED).into_owned();
+                file.redactions += matches;
+            }
+        }
+        if file.redactions > 0 {
+            tracing::debug!("Redacted {} matches in {}", file.redactions, file.rel_path);
+        }
+    });
+    Ok(())
+}
+
+/// Replace files whose content matches an earlier file (in output order) with a
+/// reference line, so the content is emitted once. Hashes pick candidates and the
+/// full contents are compared to confirm.
+fn apply_dedupe(files: &mut [ProcessedFile], config: &YekConfig) {
+    // Content hash -> indices of the first file seen with each distinct content
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for i in output_order_indices(files, config) {
+        if files[i].content.is_empty() {
+            continue;
+        }
+        let mut hasher = DefaultHasher::new();
+        files[i].content.hash(&mut hasher);
+        let originals = seen.entry(hasher.finish()).or_default();
+        match originals
             .iter()
-            .map(|f| std::path::PathBuf::from(&f.rel_path))
-            .collect();
-        generate_tree(&file_paths)
-    } else {
-        String::new()
+            .find(|&&j| files[j].content == files[i].content)
+        {
+            Some(&original) => duplicates.push((i, original)),
+            None => originals.push(i),
+        }
+    }
+
+    for (i, original) in duplicates {
+        files[i].content = format!(
+            "(identical to {})\n",
+            display_path(&files[original], config)
+        );
+        files[i].duplicate_of = Some(files[original].rel_path.clone());
+    }
+}
+
+/// Fill in each file's last commit for `--git-recent` and `--show-authors`.
+/// History is walked once per repository rather than once per file.
+/// With `--git-recent`, input paths outside a git repository get a warning and keep
+/// the default ordering; FILE_AUTHOR/FILE_COMMIT simply stay empty for them.
+fn apply_last_commits(files: &mut [ProcessedFile], config: &YekConfig) {
+    let (repos, outside) = input_repos(config);
+    if config.git_recent {
+        for path_str in outside {
+            tracing::warn!(
+                "Warning: --git-recent: '{}' is not inside a git repository; using the default order",
+                path_str
+            );
+        }
+    }
+
+    for (workdir, repo) in &repos {
+        let keys = repo_keys(files, workdir);
+        let paths: Vec<String> = keys.iter().map(|(_, key)| key.clone()).collect();
+        let mut commits = get_last_commits(repo, &paths);
+        for (i, key) in keys {
+            if let Some(commit) = commits.remove(&key) {
+                files[i].last_commit = Some(commit);
+            }
+        }
+    }
+}
+
+/// Lines at the top of a file searched for an `@generated` marker
+const GENERATED_MARKER_LINES: usize = 10;
+
+/// Why `--skip-generated` treats a file as generated, if it does: a minified name,
+/// an `@generated` marker in its first lines, or an average line length over `max_line_length`
+pub fn generated_reason(path: &str, content: &str, max_line_length: usize) -> Option<&'static str> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if name.contains(".min.") {
+        return Some("minified name");
+    }
+    if content
+        .lines()
+        .take(GENERATED_MARKER_LINES)
+        .any(|line| line.contains("@generated"))
+    {
+        return Some("@generated marker");
+    }
+    let lines = content.lines().count();
+    if lines > 0 && (content.len() - content.matches('\n').count()) / lines > max_line_length {
+        return Some("long lines");
+    }
+    None
+}
+
+/// Leave out zero-byte files (`--exclude-empty-files`), from the tree as well
+fn apply_exclude_empty(files: &mut [ProcessedFile]) {
+    for file in files.iter_mut() {
+        if file.symlink_target.is_none() && !file.binary && file.content.is_empty() {
+            tracing::debug!("Skipping {}: empty", file.rel_path);
+            file.empty = true;
+        }
+    }
+}
+
+/// Leave out files that look generated (`--skip-generated`); the tree still lists them
+fn apply_skip_generated(files: &mut [ProcessedFile], config: &YekConfig) {
+    files
+        .par_iter_mut()
+        .filter(|f| is_serialized(f, config) && !f.binary)
+        .for_each(|file| {
+            if let Some(reason) =
+                generated_reason(&file.rel_path, &file.content, config.generated_line_length)
+            {
+                tracing::debug!("Skipping {}: looks generated ({})", file.rel_path, reason);
+                file.generated = true;
+            }
+        });
+}
+
+/// Skip files over `--max-file-size`, or with the truncate action cut them down to the
+/// whole lines that fit. Files that are not serialized anyway are left alone.
+fn apply_max_file_size(files: &mut [ProcessedFile], limit: u64, config: &YekConfig) {
+    files
+        .par_iter_mut()
+        .filter(|f| is_serialized(f, config) && file_size(f) > limit)
+        .for_each(|file| match config.max_file_size_action {
+            OversizeAction::Skip => {
+                tracing::debug!("Skipping {}: larger than --max-file-size", file.rel_path);
+                file.too_large = true;
+            }
+            // Base64 binaries cannot be cut at a line, so they are skipped too
+            OversizeAction::Truncate if file.binary => {
+                tracing::debug!(
+                    "Skipping {}: binary and larger than --max-file-size",
+                    file.rel_path
+                );
+                file.too_large = true;
+            }
+            OversizeAction::Truncate => {
+                tracing::debug!("Truncating {} to --max-file-size", file.rel_path);
+                let mut fitting = 0;
+                let mut kept = 0;
+                for line in file.content.split_inclusive('\n') {
+                    if (kept + line.len()) as u64 > limit {
+                        break;
+                    }
+                    kept += line.len();
+                    fitting += 1;
+                }
+                file.content = truncate::truncate_lines(&file.content, fitting);
+            }
+        });
+}
+
+/// Size above which `--drop-outliers` skips a file: `--outlier-factor` times the
+/// `--outlier-percentile` size (nearest rank) of `sizes`. `None` when there is nothing to
+/// compare against, including when that percentile is an empty file.
+pub fn outlier_threshold(sizes: &[u64], percentile: u8, factor: f64) -> Option<u64> {
+    let mut sorted = sizes.to_vec();
+    sorted.sort_unstable();
+    let rank = (sorted.len() * percentile as usize).div_ceil(100).max(1);
+    let baseline = *sorted.get(rank - 1)?;
+    (baseline > 0).then_some((baseline as f64 * factor) as u64)
+}
+
+/// Skip files far larger than is typical for this selection (`--drop-outliers`). They are
+/// marked too large, so the tree still lists them and `--stats` counts them.
+fn apply_drop_outliers(files: &mut [ProcessedFile], config: &YekConfig) {
+    let sizes: Vec<u64> = files
+        .iter()
+        .filter(|f| is_serialized(f, config))
+        .map(file_size)
+        .collect();
+    let Some(threshold) =
+        outlier_threshold(&sizes, config.outlier_percentile, config.outlier_factor)
+    else {
+        return;
     };
+    for file in files
+        .iter_mut()
+        .filter(|f| is_serialized(f, config) && file_size(f) > threshold)
+    {
+        tracing::debug!(
+            "Skipping {}: {} is over the outlier threshold of {}",
+            file.rel_path,
+            ByteSize::b(file_size(file)),
+            ByteSize::b(threshold)
+        );
+        file.too_large = true;
+    }
+}
 
-    // If tree_only is requested, return just the tree
-    if config.tree_only {
-        return Ok(tree_header);
+/// Keep only files changed since the `--since` ref. With `--full-tree` the other
+/// files stay (marked unchanged) so the tree still lists them.
+fn apply_since(files: &mut Vec<ProcessedFile>, reference: &str, config: &YekConfig) -> Result<()> {
+    let (repos, outside) = input_repos(config);
+    if let Some(path_str) = outside.first() {
+        return Err(anyhow!(
+            "since: '{}' is not inside a git repository",
+            path_str
+        ));
+    }
+
+    let mut changed = vec![false; files.len()];
+    for (workdir, repo) in &repos {
+        let changed_paths = get_changed_paths(repo, reference)?;
+        for (i, key) in repo_keys(files, workdir) {
+            if changed_paths.contains(&key) {
+                changed[i] = true;
+            }
+        }
     }
 
-    let mut accumulated = 0_usize;
-    let cap = if config.token_mode {
-        parse_token_limit(&config.tokens)?
+    if config.full_tree {
+        for (file, changed) in files.iter_mut().zip(changed) {
+            file.unchanged = !changed;
+        }
     } else {
-        ByteSize::from_str(&config.max_size)
-            .map_err(|e| anyhow!("max_size: Invalid size format: {}", e))?
-            .as_u64() as usize
-    };
+        let mut changed = changed.into_iter();
+        files.retain(|_| changed.next().unwrap_or(false));
+    }
+    Ok(())
+}
 
-    // Account for tree header size in capacity calculations
-    let tree_header_size = if config.tree_header {
-        if config.token_mode {
-            count_tokens(&tree_header)
+/// Open each distinct repository containing an input path, keyed by its canonical workdir.
+/// Also returns the input paths that are not inside any repository.
+fn input_repos(config: &YekConfig) -> (Vec<(PathBuf, git2::Repository)>, Vec<&str>) {
+    let mut repos: Vec<(PathBuf, git2::Repository)> = Vec::new();
+    let mut outside = Vec::new();
+    for path_str in &config.input_paths {
+        let root = glob_root(path_str).unwrap_or_else(|| Path::new(path_str).to_path_buf());
+        let start = if root.is_file() {
+            root.parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+                .to_path_buf()
         } else {
-            tree_header.len()
+            root
+        };
+        let Ok(repo) = git2::Repository::discover(&start) else {
+            outside.push(path_str.as_str());
+            continue;
+        };
+        let Some(workdir) = repo.workdir().and_then(|w| w.canonicalize().ok()) else {
+            outside.push(path_str.as_str());
+            continue;
+        };
+        if !repos.iter().any(|(w, _)| *w == workdir) {
+            repos.push((workdir, repo));
         }
-    } else {
-        0
+    }
+    (repos, outside)
+}
+
+/// The `--git-header` line for the first input path's repository, with a blank line
+/// after it. Empty without `--git-header`, or (with a warning) outside a git repository.
+fn git_header(config: &YekConfig) -> String {
+    if !config.git_header {
+        return String::new();
+    }
+    match input_repos(config).0.first() {
+        Some((_, repo)) => format!("{}\n\n", repo_header(repo)),
+        None => {
+            tracing::warn!("--git-header: the input is not inside a git repository");
+            String::new()
+        }
+    }
+}
+
+/// Repo-relative, `/`-separated path of every file under `workdir`, with its index in `files`
+fn repo_keys(files: &[ProcessedFile], workdir: &Path) -> Vec<(usize, String)> {
+    files
+        .iter()
+        .enumerate()
+        .filter_map(|(i, f)| {
+            let abs = f.disk_path.canonicalize().ok()?;
+            let rel = abs.strip_prefix(workdir).ok()?;
+            let key = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            Some((i, key))
+        })
+        .collect()
+}
+
+/// Serialize `files` into a single string. See [`write_files`] to stream to a writer instead.
+pub fn concat_files(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<String> {
+    let mut output = Vec::new();
+    write_files(files, config, &mut output)?;
+    Ok(String::from_utf8(output)?)
+}
+
+/// Serialize `files` to `out`, then append the `--stats` footer (or print it to stderr
+/// with `--stats-stderr`). `ignored` is the count from [`collect_files_counting_ignored`].
+pub fn write_output<W: Write>(
+    files: &[ProcessedFile],
+    ignored: usize,
+    config: &YekConfig,
+    out: &mut W,
+) -> Result<OutputStats> {
+    let stats = OutputStats {
+        ignored,
+        ..write_files(files, config, out)?
     };
+    // A footer would make JSON output invalid, so it goes to stderr there
+    let footer = config.stats || config.lang_stats;
+    if config.stats_stderr || (footer && config.json_output()) {
+        eprint!("{}", stats.footer());
+    } else if footer {
+        write!(out, "\n\n{}", stats.footer())?;
+    }
+    Ok(stats)
+}
 
-    accumulated += tree_header_size;
+/// Serialize `files` to `out`: the tree header first, then each file's block as soon as
+/// it is rendered, so the full output is never held in memory. JSON output is still
+/// rendered as a whole. Returns what was written (`ignored` is left at 0).
+pub fn write_files<W: Write>(
+    files: &[ProcessedFile],
+    config: &YekConfig,
+    out: &mut W,
+) -> Result<OutputStats> {
+    render_files(files, config, CountingWriter::new(out, config), true)
+}
 
-    // Sort by priority (asc) and file_index (asc)
-    let mut sorted_files: Vec<_> = files.iter().collect();
-    sorted_files.sort_by(|a, b| {
-        a.priority
-            .cmp(&b.priority)
-            .then_with(|| a.rel_path.cmp(&b.rel_path))
-    });
+/// Size of the output [`write_files`] would produce, with its tokens (counted with
+/// `--tokens`/`--model`, estimated otherwise). The output is rendered into a sink, so it
+/// is never held in memory, and no `--manifest` is written.
+pub fn measure_output(files: &[ProcessedFile], config: &YekConfig) -> Result<OutputStats> {
+    let mut sink = io::sink();
+    render_files(
+        files,
+        config,
+        CountingWriter::with_tokens(&mut sink, config, true),
+        false,
+    )
+}
 
-    let mut files_to_include = Vec::new();
-    for file in sorted_files {
-        let content_size = if config.token_mode {
-            // Format the file content with template first, then count tokens
-            let formatted = if config.json {
-                serde_json::to_string(&serde_json::json!({
-                    "filename": &file.rel_path,
-                    "content": &file.content,
-                }))
-                .map_err(|e| anyhow!("Failed to serialize JSON: {}", e))?
-            } else {
-                config
-                    .output_template
-                    .replace("FILE_PATH", &file.rel_path)
-                    .replace("FILE_CONTENT", &file.content)
-            };
-            count_tokens(&formatted)
+/// Serialize `files` through `out` for [`write_files`], writing the `--manifest` file
+/// too when `manifest` is set
+fn render_files<W: Write>(
+    files: &[ProcessedFile],
+    config: &YekConfig,
+    mut out: CountingWriter<'_, W>,
+    manifest: bool,
+) -> Result<OutputStats> {
+    let git_header = git_header(config);
+    // In tree-only mode, emit just the tree (as JSON with --json or --format json)
+    if config.tree_only {
+        let entries = if config.tree_matches_output {
+            tree_entries(select_files(files, config, 0)?, config)?
         } else {
-            file.content.len()
+            tree_entries(files, config)?
         };
-
-        if accumulated + content_size <= cap {
-            accumulated += content_size;
-            files_to_include.push(file);
+        if config.json_output() {
+            let tree = generate_tree_json(&entries, &tree_options(config));
+            out.write_all(serde_json::to_string_pretty(&tree)?.as_bytes())?;
         } else {
-            break;
+            out.write_all(git_header.as_bytes())?;
+            out.write_all(generate_tree_with_options(&entries, &tree_options(config)).as_bytes())?;
         }
+        return Ok(out.stats(0, 0, 0));
+    }
+
+    let (tree_header, files_to_include) = plan_output(files, config, &git_header)?;
+
+    // Everything serializable that did not make it in was dropped by the size/file caps
+    let candidates = files.iter().filter(|f| is_serialized(f, config)).count();
+    let binary = files
+        .iter()
+        .filter(|f| f.binary && !config.include_binary)
+        .count();
+    let over_file_limit = files.iter().filter(|f| f.too_large).count();
+    let generated = files.iter().filter(|f| f.generated).count();
+    let empty = files.iter().filter(|f| f.empty).count();
+    let included = files_to_include.len();
+    let too_large = candidates - included + over_file_limit;
+
+    if config.format == OutputFormat::Xml {
+        out.write_all(b"<documents>\n")?;
     }
 
-    let main_content = if config.json {
+    out.write_all(git_header.as_bytes())?;
+
+    if config.toc {
+        out.write_all(markdown_toc(&files_to_include, &tree_header, config).as_bytes())?;
+    }
+
+    // The tree header goes out before any file content
+    if config.tree_header && !config.json_output() {
+        out.write_all(tree_header.as_bytes())?;
+    }
+
+    if config.json_lines {
+        // One compact object per line, written as each file is rendered
+        if config.tree_header {
+            let tree = serde_json::json!({ "type": "tree", "tree": tree_header });
+            writeln!(out, "{}", serde_json::to_string(&tree)?)?;
+        }
+        for f in &files_to_include {
+            let mut entry = file_metadata_json(f, config);
+            entry["type"] = "file".into();
+            writeln!(out, "{}", serde_json::to_string(&entry)?)?;
+        }
+    } else if config.format == OutputFormat::Json {
+        // The versioned document, which carries the tree itself
+        let tree = config.tree_header.then_some(tree_header.as_str());
+        let skipped = (binary, too_large, generated, empty);
+        let document = json_document(&files_to_include, tree, skipped, config);
+        out.write_all(serde_json::to_string_pretty(&document)?.as_bytes())?;
+    } else if config.json {
         // JSON array of objects
-        serde_json::to_string_pretty(
+        let json = serde_json::to_string_pretty(
             &files_to_include
                 .iter()
-                .map(|f| {
-                    serde_json::json!({
-                        "filename": &f.rel_path,
-                        "content": &f.content,
-                    })
-                })
+                .map(|f| file_json(f, &display_path(f, config), &f.content, config))
                 .collect::<Vec<_>>(),
-        )?
+        )?;
+        out.write_all(json.as_bytes())?;
     } else {
-        // Use the user-defined template
-        files_to_include
-            .iter()
-            .map(|f| {
-                config
-                    .output_template
-                    .replace("FILE_PATH", &f.rel_path)
-                    .replace("FILE_CONTENT", &f.content)
-                    // Handle both literal "\n" and escaped "\\n"
-                    .replace("\\\\\n", "\n") // First handle escaped newline
-                    .replace("\\\\n", "\n") // Then handle escaped \n sequence
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-    };
+        // Use the user-defined template, one file at a time
+        let count = files_to_include.len();
+        for (i, f) in files_to_include.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b"\n")?;
+            }
+            let previous = i.checked_sub(1).map(|p| files_to_include[p]);
+            if let Some(header) = group_header(f, previous, config) {
+                out.write_all(header.as_bytes())?;
+            }
+            // With --headers-only the file still counts toward the budget, but its body is left out
+            let content = if config.headers_only { "" } else { &f.content };
+            let block = format_file(
+                &TemplateVars {
+                    path: &display_path(f, config),
+                    content,
+                    index: i + 1,
+                    count,
+                    lang: &file_language(&f.rel_path, config),
+                    commit: f.last_commit.as_ref(),
+                    size: f.content.len(),
+                    tokens: template_tokens(f, &f.content, config),
+                    mtime: template_mtime(f, config),
+                },
+                config,
+            );
+            out.write_all(block.as_bytes())?;
+        }
+        if config.format == OutputFormat::Xml {
+            out.write_all(b"</documents>\n")?;
+        }
+    }
 
-    // Combine tree header with main content
-    if config.tree_header {
-        Ok(format!("{}{}", tree_header, main_content))
-    } else {
-        Ok(main_content)
+    if manifest {
+        write_manifest_file(&manifest_lines(&files_to_include, config), config)?;
+    }
+
+    let mut stats = out.stats(included, binary, too_large);
+    stats.generated = generated;
+    stats.empty = empty;
+    stats.redacted = files_to_include.iter().map(|f| f.redactions).sum();
+    stats.stripped_bytes = files_to_include.iter().map(|f| f.stripped_bytes).sum();
+    if config.lang_stats {
+        stats.languages = language_stats(&files_to_include, config);
+    }
+
+    // Savings from --dedupe: each included copy would otherwise repeat its original
+    let sizes: HashMap<&str, usize> = files
+        .iter()
+        .map(|f| (f.rel_path.as_str(), f.content.len()))
+        .collect();
+    for file in &files_to_include {
+        if let Some(original) = &file.duplicate_of {
+            stats.deduplicated += 1;
+            stats.dedupe_saved_bytes += sizes
+                .get(original.as_str())
+                .map_or(0, |size| size.saturating_sub(file.content.len()));
+        }
     }
+    Ok(stats)
 }
 
-/// Parse a token limit string like "800k" or "1000" into a number
-pub fn parse_token_limit(limit: &str) -> anyhow::Result<usize> {
-    if limit.to_lowercase().ends_with('k') {
-        limit[..limit.len() - 1]
-            .trim()
-            .parse::<usize>()
-            .map(|n| n * 1000)
-            .map_err(|e| anyhow!("tokens: Invalid token size: {}", e))
-    } else {
-        limit
-            .parse::<usize>()
-            .map_err(|e| anyhow!("tokens: Invalid token size: {}", e))
+/// What a run emitted, reported by `--stats`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputStats {
+    /// Files serialized into the output
+    pub files: usize,
+    /// Bytes written, excluding the stats footer
+    pub bytes: usize,
+    /// Tokens written, when counting tokens (`--tokens`/`--model`) for `--stats`
+    pub tokens: Option<usize>,
+    /// Files skipped by ignore patterns or `--include`/`--exclude`
+    pub ignored: usize,
+    /// Binary files skipped (listed in the tree only)
+    pub binary: usize,
+    /// Files that did not fit within `--max-size`/`--tokens`/`--max-files`,
+    /// or were skipped by `--max-file-size` or `--drop-outliers`
+    pub too_large: usize,
+    /// Files that looked generated (`--skip-generated`)
+    pub generated: usize,
+    /// Zero-byte files (`--exclude-empty-files`)
+    pub empty: usize,
+    /// Included files replaced by a reference to an identical earlier file (`--dedupe`)
+    pub deduplicated: usize,
+    /// Bytes not repeated thanks to `--dedupe`
+    pub dedupe_saved_bytes: usize,
+    /// `--redact` matches replaced in the serialized files
+    pub redacted: usize,
+    /// Bytes of comments and blank lines removed from the serialized files
+    /// (`--strip-comments`, `--strip-blank-lines`)
+    pub stripped_bytes: usize,
+    /// Serialized files by language, with `--lang-stats` (see [`language_stats`])
+    pub languages: Vec<LanguageStats>,
+}
+
+impl OutputStats {
+    /// The footer printed by `--stats`
+    pub fn footer(&self) -> String {
+        let mut footer = format!(
+            "Stats:\n  Files: {}\n  Bytes: {} ({})\n",
+            self.files,
+            self.bytes,
+            ByteSize::b(self.bytes as u64)
+        );
+        if let Some(tokens) = self.tokens {
+            footer.push_str(&format!("  Tokens: {}\n", tokens));
+        }
+        footer.push_str(&format!(
+            "  Skipped: {} ignored, {} binary, {} too large",
+            self.ignored, self.binary, self.too_large
+        ));
+        if self.generated > 0 {
+            footer.push_str(&format!(", {} generated", self.generated));
+        }
+        if self.empty > 0 {
+            footer.push_str(&format!(", {} empty", self.empty));
+        }
+        footer.push('\n');
+        if self.deduplicated > 0 {
+            footer.push_str(&format!(
+                "  Deduplicated: {} files ({} saved)\n",
+                self.deduplicated,
+                ByteSize::b(self.dedupe_saved_bytes as u64)
+            ));
+        }
+        if self.redacted > 0 {
+            footer.push_str(&format!("  Redacted: {} matches\n", self.redacted));
+        }
+        if self.stripped_bytes > 0 {
+            footer.push_str(&format!(
+                "  Stripped: {} of comments and blank lines\n",
+                ByteSize::b(self.stripped_bytes as u64)
+            ));
+        }
+        if !self.languages.is_empty() {
+            footer.push_str("  Languages:\n");
+            footer.push_str(&language_rows(&self.languages, "    "));
+        }
+        footer
     }
 }
 
-/// Count tokens using tiktoken's GPT-3.5-Turbo tokenizer for accuracy
-pub fn count_tokens(text: &str) -> usize {
-    get_tokenizer().encode_with_special_tokens(text).len()
+/// Counts bytes (and, for `--stats` in token mode or [`measure_output`], tokens) passing through to the real
+/// writer. Tokens are counted per write, i.e. per tree header, separator and file block.
+struct CountingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    config: &'a YekConfig,
+    count_tokens: bool,
+    bytes: usize,
+    tokens: usize,
+}
+
+impl<'a, W: Write> CountingWriter<'a, W> {
+    fn new(inner: &'a mut W, config: &'a YekConfig) -> Self {
+        let count_tokens =
+            config.token_mode && (config.stats || config.stats_stderr || config.lang_stats);
+        Self::with_tokens(inner, config, count_tokens)
+    }
+
+    /// Count tokens too when `count_tokens` is set: exactly with `--tokens`/`--model`,
+    /// estimated otherwise
+    fn with_tokens(inner: &'a mut W, config: &'a YekConfig, count_tokens: bool) -> Self {
+        Self {
+            inner,
+            config,
+            count_tokens,
+            bytes: 0,
+            tokens: 0,
+        }
+    }
+
+    fn stats(&self, files: usize, binary: usize, too_large: usize) -> OutputStats {
+        OutputStats {
+            files,
+            bytes: self.bytes,
+            tokens: self.count_tokens.then_some(self.tokens),
+            ignored: 0,
+            binary,
+            too_large,
+            generated: 0,
+            empty: 0,
+            deduplicated: 0,
+            dedupe_saved_bytes: 0,
+            redacted: 0,
+            stripped_bytes: 0,
+            languages: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write_all(buf)?;
+        self.bytes += buf.len();
+        if self.count_tokens {
+            self.tokens += file_tokens_estimate(&String::from_utf8_lossy(buf), self.config);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The tree header (empty without `--tree-header`) and the files that fit in the output
+/// alongside it and the `preamble` written before everything else
+fn plan_output<'a>(
+    files: &'a [ProcessedFile],
+    config: &YekConfig,
+    preamble: &str,
+) -> Result<(String, Vec<&'a ProcessedFile>)> {
+    // Generate tree header if requested
+    let mut tree_header = if config.tree_header {
+        generate_tree_with_options(&tree_entries(files, config)?, &tree_options(config))
+    } else {
+        String::new()
+    };
+
+    // Account for tree header size in capacity calculations
+    let tree_header_size = if config.tree_header {
+        if config.token_mode {
+            count_config_tokens(&tree_header, config)
+        } else {
+            tree_header.len()
+        }
+    } else {
+        0
+    };
+
+    // Room for the --toc list: listing every candidate takes at least as much as listing
+    // the files that end up selected
+    let toc_size = if config.toc {
+        let candidates: Vec<&ProcessedFile> =
+            files.iter().filter(|f| is_serialized(f, config)).collect();
+        let toc = markdown_toc(&candidates, &tree_header, config);
+        if config.token_mode {
+            count_config_tokens(&toc, config)
+        } else {
+            toc.len()
+        }
+    } else {
+        0
+    };
+
+    let preamble_size = if config.token_mode {
+        count_config_tokens(preamble, config)
+    } else {
+        preamble.len()
+    };
+    let fixed = preamble_size + toc_size;
+
+    let mut files_to_include = select_files(files, config, tree_header_size + fixed)?;
+
+    // A pruned tree is never larger than the full one, so the budget still holds
+    if config.tree_header && config.tree_matches_output {
+        tree_header = generate_tree_with_options(
+            &tree_entries(files_to_include.iter().copied(), config)?,
+            &tree_options(config),
+        );
+    }
+
+    // Marks make the tree longer, which can push more files out, so select again
+    // with the marked tree's size until the selection settles
+    if config.tree_header && config.tree_mark_dropped {
+        let mut reserved = tree_header_size;
+        loop {
+            tree_header = generate_tree_with_options(
+                &marked_tree_entries(files, &files_to_include, config)?,
+                &tree_options(config),
+            );
+            let size = if config.token_mode {
+                count_config_tokens(&tree_header, config)
+            } else {
+                tree_header.len()
+            };
+            if size <= reserved {
+                break;
+            }
+            reserved = size;
+            files_to_include = select_files(files, config, reserved + fixed)?;
+        }
+    }
+
+    Ok((tree_header, files_to_include))
+}
+
+/// The `--toc` list of links to each file's section. Anchors are derived as GitHub
+/// derives them from headings, in document order: the list's own heading, any headings
+/// in the tree header and `--group-by` headers count toward repeated anchors.
+fn markdown_toc(files: &[&ProcessedFile], tree_header: &str, config: &YekConfig) -> String {
+    let mut anchors = toc::Anchors::default();
+    anchors.next(toc::TOC_HEADING);
+    for text in tree_header.lines().filter_map(toc::heading_text) {
+        anchors.next(text);
+    }
+    let mut list = format!("## {}\n\n", toc::TOC_HEADING);
+    for (i, file) in files.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|p| files[p]);
+        if let Some(header) = group_header(file, previous, config) {
+            for text in header.lines().filter_map(toc::heading_text) {
+                anchors.next(text);
+            }
+        }
+        let path = display_path(file, config);
+        list.push_str(&format!(
+            "- [{}](#{})\n",
+            toc::escape_link_text(&path),
+            anchors.next(&path)
+        ));
+    }
+    list.push('\n');
+    list
+}
+
+/// A `--dry-run` preview: the files that would be serialized, in output order, with
+/// their sizes and token counts (estimated unless a token budget is active), and a total.
+pub fn dry_run_report(files: &[ProcessedFile], config: &YekConfig) -> Result<String> {
+    let git_header = git_header(config);
+    let (tree_header, selected) = plan_output(files, config, &git_header)?;
+    let candidates = files.iter().filter(|f| is_serialized(f, config)).count();
+    // Estimated counts are marked with "~"
+    let approx = if config.token_mode { "" } else { "~" };
+
+    let mut report = format!(
+        "Dry run: {} of {} files would be serialized\n",
+        selected.len(),
+        candidates
+    );
+    let mut total_bytes = 0;
+    let mut total_tokens = 0;
+    if !git_header.is_empty() {
+        let tokens = file_tokens_estimate(&git_header, config);
+        report.push_str(&format!(
+            "  (git header)  {}  {}{} tokens\n",
+            ByteSize::b(git_header.len() as u64),
+            approx,
+            tokens
+        ));
+        total_bytes += git_header.len();
+        total_tokens += tokens;
+    }
+    if config.tree_header {
+        let tokens = file_tokens_estimate(&tree_header, config);
+        report.push_str(&format!(
+            "  (tree header)  {}  {}{} tokens\n",
+            ByteSize::b(tree_header.len() as u64),
+            approx,
+            tokens
+        ));
+        total_bytes += tree_header.len();
+        total_tokens += tokens;
+    }
+    for (i, file) in selected.iter().enumerate() {
+        let tokens = if config.token_mode {
+            measure_file(file, i + 1, selected.len(), config)?
+        } else {
+            file_tokens_estimate(&file.content, config)
+        };
+        report.push_str(&format!(
+            "  {}. {}  {}  {}{} tokens\n",
+            i + 1,
+            display_path(file, config),
+            ByteSize::b(file.content.len() as u64),
+            approx,
+            tokens
+        ));
+        total_bytes += file.content.len();
+        total_tokens += tokens;
+    }
+    report.push_str(&format!(
+        "Total: {} files, {}, {}{} tokens\n",
+        selected.len(),
+        ByteSize::b(total_bytes as u64),
+        approx,
+        total_tokens
+    ));
+    Ok(report)
+}
+
+/// The `--count-only` line: tokens (with the active tokenizer) and bytes of every file
+/// that passes the filters, whatever the output budget. Files are measured as they are,
+/// without rendering them through the template.
+pub fn count_report(files: &[ProcessedFile], config: &YekConfig) -> String {
+    let (count, bytes, tokens) = files
+        .par_iter()
+        .filter(|f| is_serialized(f, config))
+        .map(|f| (1, f.content.len(), count_file_tokens(f, &f.content, config)))
+        .reduce(|| (0, 0, 0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+    format!(
+        "{} tokens, {} bytes ({}) in {} files\n",
+        tokens,
+        bytes,
+        ByteSize::b(bytes as u64),
+        count
+    )
+}
+
+/// Files and bytes of one language, for `--lang-stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageStats {
+    /// As `FILE_LANG` names it, or "other" for files it has no name for
+    pub language: String,
+    pub files: usize,
+    pub bytes: usize,
+}
+
+/// Group `files` by language (as [`file_language`] detects it), largest share of the
+/// bytes first, ties by name
+pub fn language_stats(files: &[&ProcessedFile], config: &YekConfig) -> Vec<LanguageStats> {
+    let mut by_language: HashMap<String, (usize, usize)> = HashMap::new();
+    for file in files {
+        let language = match file_language(&file.rel_path, config) {
+            language if language.is_empty() => "other".to_string(),
+            language => language,
+        };
+        let entry = by_language.entry(language).or_default();
+        entry.0 += 1;
+        entry.1 += file.content.len();
+    }
+    let mut stats: Vec<LanguageStats> = by_language
+        .into_iter()
+        .map(|(language, (files, bytes))| LanguageStats {
+            language,
+            files,
+            bytes,
+        })
+        .collect();
+    stats.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.language.cmp(&b.language))
+    });
+    stats
+}
+
+/// The `--lang-stats-only` report: the [`language_stats`] table and a total
+pub fn language_report(stats: &[LanguageStats]) -> String {
+    let files: usize = stats.iter().map(|s| s.files).sum();
+    let bytes: usize = stats.iter().map(|s| s.bytes).sum();
+    format!(
+        "Languages:\n{}Total: {} files, {}\n",
+        language_rows(stats, "  "),
+        files,
+        ByteSize::b(bytes as u64)
+    )
+}
+
+/// One line per language with its files, bytes and share of the bytes, in columns
+fn language_rows(stats: &[LanguageStats], indent: &str) -> String {
+    let total: usize = stats.iter().map(|s| s.bytes).sum();
+    let rows: Vec<(&str, String, String, String)> = stats
+        .iter()
+        .map(|s| {
+            let share = if total == 0 {
+                0.0
+            } else {
+                s.bytes as f64 * 100.0 / total as f64
+            };
+            (
+                s.language.as_str(),
+                format!(
+                    "{} {}",
+                    s.files,
+                    if s.files == 1 { "file" } else { "files" }
+                ),
+                ByteSize::b(s.bytes as u64).to_string(),
+                format!("{:.1}%", share),
+            )
+        })
+        .collect();
+    let width = |column: fn(&(&str, String, String, String)) -> usize| {
+        rows.iter().map(column).max().unwrap_or(0)
+    };
+    let language_width = width(|r| r.0.chars().count());
+    let files_width = width(|r| r.1.len());
+    let bytes_width = width(|r| r.2.len());
+    let share_width = width(|r| r.3.len());
+
+    let mut report = String::new();
+    for (language, files, bytes, share) in &rows {
+        report.push_str(&format!(
+            "{}{:<language_width$}  {:>files_width$}  {:>bytes_width$}  {:>share_width$}\n",
+            indent, language, files, bytes, share
+        ));
+    }
+    report
+}
+
+/// The `--manifest` list: the path of every file in the output, in output order, one per
+/// line, as the output shows it (see `--path-style`). With `--split-output` that is every
+/// file across all chunks.
+pub fn manifest(files: &[ProcessedFile], config: &YekConfig) -> Result<String> {
+    Ok(if config.split_output.is_some() {
+        manifest_lines(&output_order(files, config), config)
+    } else {
+        manifest_lines(&plan_output(files, config, &git_header(config))?.1, config)
+    })
+}
+
+fn manifest_lines(files: &[&ProcessedFile], config: &YekConfig) -> String {
+    files
+        .iter()
+        .map(|file| format!("{}\n", display_path(file, config)))
+        .collect()
+}
+
+/// Write `manifest` to the `--manifest` file, replacing it only once complete.
+/// Does nothing without `--manifest` or with `--manifest -`, which the caller prints.
+pub fn write_manifest_file(manifest: &str, config: &YekConfig) -> Result<()> {
+    if let Some(path) = config.manifest.as_deref().filter(|path| *path != "-") {
+        let mut file = atomic::AtomicFile::create(path)?;
+        file.write_all(manifest.as_bytes())?;
+        file.commit()?;
+    }
+    Ok(())
+}
+
+/// Token count used by `--dry-run` and `--confirm-over`: exact in token mode, the fast
+/// heuristic otherwise
+pub fn file_tokens_estimate(text: &str, config: &YekConfig) -> usize {
+    if config.token_mode {
+        count_config_tokens(text, config)
+    } else {
+        estimate_tokens(text)
+    }
+}
+
+/// Pick the files that fit in the output, in output order, stopping at the first file
+/// that would exceed `--max-size`/`--tokens` (after `reserved` is taken by the tree header).
+/// `--pin` files lead the order and are kept first (see [`select_pinned`]).
+/// With `--budget` rules, reservations are filled first (see [`select_with_budget`]).
+fn select_files<'a>(
+    files: &'a [ProcessedFile],
+    config: &YekConfig,
+    reserved: usize,
+) -> Result<Vec<&'a ProcessedFile>> {
+    let cap = output_capacity(config)?;
+    let sorted_files = output_order(files, config);
+
+    let pins = config.pinned_paths();
+    let pinned_count = sorted_files
+        .iter()
+        .take_while(|f| pin_rank(f, &pins) != usize::MAX)
+        .count();
+    let (pinned, sorted_files) = sorted_files.split_at(pinned_count);
+    let (mut files_to_include, reserved) = select_pinned(pinned, config, cap, reserved)?;
+    files_to_include.extend(select_unpinned(sorted_files, config, cap, reserved)?);
+    Ok(files_to_include)
+}
+
+/// `--pin` files, in the order given: each is kept whether or not the ones before it left
+/// room, unless it alone does not fit next to `reserved`. Also returns `reserved` plus
+/// the size of the kept files, which may exceed the budget.
+fn select_pinned<'a>(
+    pinned: &[&'a ProcessedFile],
+    config: &YekConfig,
+    cap: usize,
+    reserved: usize,
+) -> Result<(Vec<&'a ProcessedFile>, usize)> {
+    let mut accumulated = reserved;
+    let mut files_to_include = Vec::new();
+    for (i, file) in pinned.iter().enumerate() {
+        let size = measure_file(file, i + 1, pinned.len(), config)?;
+        if reserved + size > cap {
+            tracing::warn!(
+                "--pin {}: larger than the whole budget ({}), left out",
+                file.rel_path,
+                size
+            );
+            continue;
+        }
+        accumulated += size;
+        files_to_include.push(*file);
+    }
+    if accumulated > cap {
+        tracing::warn!("Pinned files exceed the budget; no other files fit");
+    }
+    Ok((files_to_include, accumulated))
+}
+
+/// [`select_files`] for the files after the pinned ones, in output order
+fn select_unpinned<'a>(
+    sorted_files: &[&'a ProcessedFile],
+    config: &YekConfig,
+    cap: usize,
+    reserved: usize,
+) -> Result<Vec<&'a ProcessedFile>> {
+    let mut accumulated = reserved;
+
+    let rules = config.budget_rules();
+    if !rules.is_empty() {
+        return select_with_budget(sorted_files, config, cap, reserved, &rules);
+    }
+    if config.fill == BudgetFill::Priority {
+        return select_by_priority(sorted_files, config, cap, reserved);
+    }
+
+    let candidate_count = sorted_files.len();
+    // Measure in parallel, one batch at a time so a small budget doesn't pay
+    // for tokenizing every candidate. Results are consumed in output order.
+    let batch_size = rayon::current_num_threads() * 4;
+    let mut files_to_include = Vec::new();
+    for (batch_index, batch) in sorted_files.chunks(batch_size).enumerate() {
+        let sizes = batch
+            .par_iter()
+            .enumerate()
+            .map(|(i, file)| {
+                measure_file(
+                    file,
+                    batch_index * batch_size + i + 1,
+                    candidate_count,
+                    config,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (file, mut content_size) in batch.iter().zip(sizes) {
+            if let Some(header) = group_header(file, files_to_include.last().copied(), config) {
+                content_size += if config.token_mode {
+                    count_config_tokens(&header, config)
+                } else {
+                    header.len()
+                };
+            }
+            if accumulated + content_size <= cap {
+                tracing::trace!(
+                    "Including {} ({} toward the budget)",
+                    file.rel_path,
+                    content_size
+                );
+                accumulated += content_size;
+                files_to_include.push(*file);
+            } else {
+                tracing::debug!(
+                    "Budget reached at {}: it and {} later file(s) left out",
+                    file.rel_path,
+                    candidate_count - files_to_include.len() - 1
+                );
+                return Ok(files_to_include);
+            }
+        }
+    }
+
+    Ok(files_to_include)
+}
+
+/// `--budget` selection: each rule first fills its reservation with its own files (in output
+/// order, up to the first that does not fit), then the rest of the budget, including unused
+/// reservations, is filled in output order as usual. The result keeps output order.
+fn select_with_budget<'a>(
+    sorted_files: &[&'a ProcessedFile],
+    config: &YekConfig,
+    cap: usize,
+    reserved: usize,
+    rules: &[BudgetRule],
+) -> Result<Vec<&'a ProcessedFile>> {
+    let candidate_count = sorted_files.len();
+    let sizes = sorted_files
+        .par_iter()
+        .enumerate()
+        .map(|(i, file)| measure_file(file, i + 1, candidate_count, config))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut selected = vec![false; candidate_count];
+    let mut accumulated = reserved;
+
+    let shares = reservations(cap.saturating_sub(reserved), rules);
+    for (rule_index, share) in shares.into_iter().enumerate() {
+        let mut used = 0;
+        for (i, file) in sorted_files.iter().enumerate() {
+            if budget_group(&file.rel_path, rules) != Some(rule_index) {
+                continue;
+            }
+            if used + sizes[i] > share {
+                break;
+            }
+            used += sizes[i];
+            selected[i] = true;
+        }
+        accumulated += used;
+    }
+
+    for i in 0..candidate_count {
+        if selected[i] {
+            continue;
+        }
+        if accumulated + sizes[i] > cap {
+            tracing::debug!("Budget reached at {}", sorted_files[i].rel_path);
+            break;
+        }
+        accumulated += sizes[i];
+        selected[i] = true;
+    }
+
+    let files_to_include: Vec<(&ProcessedFile, usize)> = sorted_files
+        .iter()
+        .zip(sizes)
+        .zip(&selected)
+        .filter(|(_, selected)| **selected)
+        .map(|((file, size), _)| (*file, size))
+        .collect();
+    Ok(fit_group_headers(files_to_include, config, cap, reserved))
+}
+
+/// `--fill priority` selection: files are taken by descending `--priority` weight, then
+/// `priority_rules` score, then ascending size (so more of them fit), then path. A file that
+/// does not fit is dropped and the next one tried. The result keeps output order.
+fn select_by_priority<'a>(
+    sorted_files: &[&'a ProcessedFile],
+    config: &YekConfig,
+    cap: usize,
+    reserved: usize,
+) -> Result<Vec<&'a ProcessedFile>> {
+    let candidate_count = sorted_files.len();
+    let sizes = sorted_files
+        .par_iter()
+        .enumerate()
+        .map(|(i, file)| measure_file(file, i + 1, candidate_count, config))
+        .collect::<Result<Vec<_>>>()?;
+
+    let weights = config.priority_weights();
+    let mut ranked: Vec<usize> = (0..candidate_count).collect();
+    ranked.sort_by_cached_key(|&i| {
+        let file = sorted_files[i];
+        (
+            std::cmp::Reverse(priority::get_file_weight(&file.rel_path, &weights)),
+            std::cmp::Reverse(file.priority),
+            sizes[i],
+            file.rel_path.clone(),
+        )
+    });
+
+    let mut selected = vec![false; candidate_count];
+    let mut accumulated = reserved;
+    let mut dropped = Vec::new();
+    for i in ranked {
+        if accumulated + sizes[i] > cap {
+            tracing::debug!(
+                "Dropping {} ({} toward the budget)",
+                sorted_files[i].rel_path,
+                sizes[i]
+            );
+            dropped.push(sorted_files[i].rel_path.as_str());
+            continue;
+        }
+        accumulated += sizes[i];
+        selected[i] = true;
+    }
+    if !dropped.is_empty() {
+        tracing::info!(
+            "Dropped {} lowest-priority file(s) to fit the budget: {}",
+            dropped.len(),
+            summarize_paths(&dropped, 5)
+        );
+    }
+
+    let files_to_include: Vec<(&ProcessedFile, usize)> = sorted_files
+        .iter()
+        .zip(sizes)
+        .zip(&selected)
+        .filter(|(_, selected)| **selected)
+        .map(|((file, size), _)| (*file, size))
+        .collect();
+    Ok(fit_group_headers(files_to_include, config, cap, reserved))
+}
+
+/// The first `limit` paths joined with commas, noting how many more were left out
+fn summarize_paths(paths: &[&str], limit: usize) -> String {
+    let mut summary = paths[..paths.len().min(limit)].join(", ");
+    if paths.len() > limit {
+        summary.push_str(&format!(" and {} more", paths.len() - limit));
+    }
+    summary
+}
+
+/// Group headers depend on which neighbours made it into a selection, so drop trailing
+/// files (with their measured sizes) until the headers fit in the budget as well
+fn fit_group_headers<'a>(
+    mut files_to_include: Vec<(&'a ProcessedFile, usize)>,
+    config: &YekConfig,
+    cap: usize,
+    reserved: usize,
+) -> Vec<&'a ProcessedFile> {
+    if config.group_by.is_some() {
+        loop {
+            let mut total = reserved;
+            let mut previous = None;
+            for (file, size) in &files_to_include {
+                total += size;
+                if let Some(header) = group_header(file, previous, config) {
+                    total += if config.token_mode {
+                        count_config_tokens(&header, config)
+                    } else {
+                        header.len()
+                    };
+                }
+                previous = Some(*file);
+            }
+            if total <= cap || files_to_include.pop().is_none() {
+                break;
+            }
+        }
+    }
+
+    files_to_include.into_iter().map(|(file, _)| file).collect()
+}
+
+/// Size of a file toward the budget: its rendered token count in token mode, else its byte length.
+/// The final FILE_COUNT is not known yet, so it is measured with the candidate total.
+fn measure_file(
+    file: &ProcessedFile,
+    index: usize,
+    candidate_count: usize,
+    config: &YekConfig,
+) -> Result<usize> {
+    if !config.token_mode {
+        return Ok(file.content.len());
+    }
+
+    // Format the file content with template first, then count tokens
+    let formatted = if config.json_output() {
+        serde_json::to_string(&file_json(
+            file,
+            &display_path(file, config),
+            &file.content,
+            config,
+        ))
+        .map_err(|e| anyhow!("Failed to serialize JSON: {}", e))?
+    } else {
+        format_file(
+            &TemplateVars {
+                path: &display_path(file, config),
+                content: &file.content,
+                index,
+                count: candidate_count,
+                lang: &file_language(&file.rel_path, config),
+                commit: file.last_commit.as_ref(),
+                size: file.content.len(),
+                tokens: template_tokens(file, &file.content, config),
+                mtime: template_mtime(file, config),
+            },
+            config,
+        )
+    };
+    Ok(count_file_tokens(file, &formatted, config))
+}
+
+/// The order files are emitted (and considered when filling the size budget).
+/// Binary files are left out unless `--include-binary` is set, as are files kept only
+/// for the tree by `--since --full-tree`. Only the first
+/// `--max-files` files are kept.
+/// With `--priority` weights: highest weight first, ties broken by path.
+/// With `--git-recent`: newest commit first (after any weights), untracked files last.
+/// With `--files-from`: the order of the list.
+/// Otherwise: priority ascending, then path, so the most important files come last.
+/// `--sort` overrides all of these with name, size (largest first) or extension order,
+/// as does `--order` with path, size or the order the directory tree lists the files in.
+/// `--group-by ext` then gathers the files into groups by extension, keeping that order
+/// within each group. `--pin` files come before all of them, in the order given.
+pub(crate) fn output_order<'a>(
+    files: &'a [ProcessedFile],
+    config: &YekConfig,
+) -> Vec<&'a ProcessedFile> {
+    output_order_indices(files, config)
+        .into_iter()
+        .map(|i| &files[i])
+        .collect()
+}
+
+/// [`output_order`] as indices into `files`
+fn output_order_indices(files: &[ProcessedFile], config: &YekConfig) -> Vec<usize> {
+    // Skipped files only appear in the tree
+    let mut sorted: Vec<usize> = (0..files.len())
+        .filter(|&i| is_serialized(&files[i], config))
+        .collect();
+    let weights = config.priority_weights();
+    // Recency only applies when some commit times were found
+    let by_recency = config.git_recent && files.iter().any(|f| f.last_commit.is_some());
+    let sort = config.sort.or(match config.order {
+        OutputOrder::Path => Some(TreeSort::Name),
+        OutputOrder::Size => Some(TreeSort::Size),
+        OutputOrder::Priority | OutputOrder::Tree => None,
+    });
+    if config.order == OutputOrder::Tree {
+        // Every file goes in, so sizes add up and files hidden from the tree keep their place
+        let entries: Vec<TreeEntry> = files.iter().map(|f| tree_entry(f, config)).collect();
+        let rank: HashMap<String, usize> = tree::tree_file_order(&entries, &tree_options(config))
+            .into_iter()
+            .enumerate()
+            .map(|(i, path)| (path, i))
+            .collect();
+        sorted.sort_by_cached_key(|&i| {
+            let path = Path::new(&entries[i].path);
+            rank.get(&tree::clean_path_components(path).join("/"))
+                .copied()
+                .unwrap_or(usize::MAX)
+        });
+    } else if let Some(sort) = sort {
+        sorted.sort_by_cached_key(|&i| {
+            let f = &files[i];
+            let key = match sort {
+                TreeSort::Name => (0, ""),
+                TreeSort::Size => (u64::MAX - file_size(f), ""),
+                TreeSort::Ext => (0, tree::extension(&f.rel_path)),
+            };
+            (key, f.rel_path.clone())
+        });
+    } else if config.uses_file_list() {
+        // The list's own order
+        sorted.sort_by_key(|&i| files[i].file_index);
+    } else if weights.is_empty() && !by_recency {
+        sorted.sort_by(|&a, &b| {
+            let (a, b) = (&files[a], &files[b]);
+            a.priority
+                .cmp(&b.priority)
+                .then_with(|| a.rel_path.cmp(&b.rel_path))
+        });
+    } else {
+        sorted.sort_by_cached_key(|&i| {
+            let f = &files[i];
+            let recency = match &f.last_commit {
+                Some(commit) if by_recency => Some(commit.time),
+                _ => None,
+            };
+            (
+                std::cmp::Reverse(priority::get_file_weight(&f.rel_path, &weights)),
+                std::cmp::Reverse(recency),
+                f.rel_path.clone(),
+            )
+        });
+    }
+    // Stable, so the rest keep the order above; before truncating, so --max-files keeps them
+    let pins = config.pinned_paths();
+    if !pins.is_empty() {
+        sorted.sort_by_key(|&i| pin_rank(&files[i], &pins));
+    }
+    if let Some(max_files) = config.max_files {
+        sorted.truncate(max_files);
+    }
+    // Stable, so each group keeps the order above
+    if config.group_by.is_some() {
+        sorted.sort_by_cached_key(|&i| (pin_rank(&files[i], &pins), group_key(&files[i])));
+    }
+    sorted
+}
+
+/// Position of `file` in the `--pin` list, or `usize::MAX` if it is not pinned
+fn pin_rank(file: &ProcessedFile, pins: &[String]) -> usize {
+    pins.iter()
+        .position(|pin| *pin == file.rel_path)
+        .unwrap_or(usize::MAX)
+}
+
+/// Group of a file with `--group-by ext`: its extension, with files that have none last
+fn group_key(file: &ProcessedFile) -> (bool, String) {
+    let ext = tree::extension(&file.rel_path);
+    (ext.is_empty(), ext.to_string())
+}
+
+/// The `--group-header` line to write before `file`, if it starts a new group
+/// (it is the first file, or `previous` is in a different group). JSON output has none.
+pub(crate) fn group_header(
+    file: &ProcessedFile,
+    previous: Option<&ProcessedFile>,
+    config: &YekConfig,
+) -> Option<String> {
+    if config.group_by.is_none() || config.json_output() {
+        return None;
+    }
+    let key = group_key(file);
+    if previous.is_some_and(|p| group_key(p) == key) {
+        return None;
+    }
+    let name = if key.0 {
+        "(no extension)".to_string()
+    } else {
+        format!("*.{}", key.1)
+    };
+    Some(format!("{}\n", config.group_header.replace("GROUP", &name)))
+}
+
+/// The path shown for a file in the output, following `--path-style`, always with
+/// forward slashes. The directory tree always uses relative paths.
+pub fn display_path(file: &ProcessedFile, config: &YekConfig) -> String {
+    let path = match config.path_style {
+        PathStyle::Relative => relative_path(file, config),
+        PathStyle::Basename => Path::new(&file.rel_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| file.rel_path.clone()),
+        PathStyle::Absolute => std::fs::canonicalize(&file.disk_path)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| file.rel_path.clone()),
+    };
+    to_forward_slashes(&path)
+}
+
+/// A file's path relative to the scan root, or to `--relative-to` when given.
+/// Files outside the `--relative-to` directory get their absolute path.
+fn relative_path(file: &ProcessedFile, config: &YekConfig) -> String {
+    let Some(base) = &config.relative_to else {
+        return file.rel_path.clone();
+    };
+    let absolute = |path: &Path| std::path::absolute(path).map(|p| p.normalize());
+    match (absolute(&file.disk_path), absolute(Path::new(base))) {
+        (Ok(path), Ok(base)) => path
+            .strip_prefix(&base)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into_owned(),
+        _ => file.rel_path.clone(),
+    }
+}
+
+/// Markdown code fence language for a file, from its extension or well-known file name.
+/// Entries in the config's `language_map` override the built-in table; unknown files get "".
+pub fn file_language(rel_path: &str, config: &YekConfig) -> String {
+    let path = Path::new(rel_path);
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    for key in [&file_name, &extension] {
+        if key.is_empty() {
+            continue;
+        }
+        let user = config
+            .language_map
+            .iter()
+            .find(|(k, _)| k.trim_start_matches('.').eq_ignore_ascii_case(key));
+        if let Some((_, lang)) = user {
+            return lang.clone();
+        }
+        if let Some((_, lang)) = DEFAULT_LANGUAGE_MAP
+            .iter()
+            .find(|(k, _)| *k == key.as_str())
+        {
+            return lang.to_string();
+        }
+    }
+    String::new()
+}
+
+/// Values substituted into the output template for one file
+pub(crate) struct TemplateVars<'a> {
+    /// Replaces `FILE_PATH`
+    pub path: &'a str,
+    /// Replaces `FILE_CONTENT`
+    pub content: &'a str,
+    /// Replaces `FILE_INDEX` (1-based position in the output)
+    pub index: usize,
+    /// Replaces `FILE_COUNT` (files emitted in the output)
+    pub count: usize,
+    /// Replaces `FILE_LANG` (Markdown code fence language, may be empty)
+    pub lang: &'a str,
+    /// Replaces `FILE_AUTHOR` and `FILE_COMMIT` (empty when unknown)
+    pub commit: Option<&'a LastCommit>,
+    /// Replaces `FILE_SIZE`: bytes in `content`, before any escaping
+    pub size: usize,
+    /// Replaces `FILE_TOKENS` (see [`template_tokens`])
+    pub tokens: Option<usize>,
+    /// Replaces `FILE_MTIME`, formatted with `--mtime-format` (see [`template_mtime`])
+    pub mtime: Option<SystemTime>,
+}
+
+/// Tokens in `content`, rendered from `file`, for `FILE_TOKENS`: counted with the configured
+/// tokenizer whether or not a token budget is set, or with the heuristic under
+/// `--token-estimator heuristic`. None, without counting, when the template has no
+/// `FILE_TOKENS`.
+pub(crate) fn template_tokens(
+    file: &ProcessedFile,
+    content: &str,
+    config: &YekConfig,
+) -> Option<usize> {
+    config
+        .effective_output_template()
+        .contains("FILE_TOKENS")
+        .then(|| count_file_tokens(file, content, config))
+}
+
+/// The file's last-modified time for `FILE_MTIME`, read from the filesystem. None, without
+/// reading it, when the template has no `FILE_MTIME`.
+pub(crate) fn template_mtime(file: &ProcessedFile, config: &YekConfig) -> Option<SystemTime> {
+    if !config.effective_output_template().contains("FILE_MTIME") {
+        return None;
+    }
+    file_mtime(file)
+}
+
+fn file_mtime(file: &ProcessedFile) -> Option<SystemTime> {
+    fs::metadata(&file.disk_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// `time` in UTC, formatted with `--mtime-format`
+pub fn format_mtime(time: SystemTime, config: &YekConfig) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time)
+        .format(&config.mtime_format)
+        .to_string()
+}
+
+/// Escape `&`, `<` and `>` so text can sit inside an XML element
+pub fn xml_escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>']) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;"),
+    )
+}
+
+/// [`xml_escape`], plus double quotes, for text inside a quoted XML attribute
+pub fn xml_escape_attr(text: &str) -> Cow<'_, str> {
+    match xml_escape(text) {
+        escaped if escaped.contains('"') => Cow::Owned(escaped.replace('"', "&quot;")),
+        escaped => escaped,
+    }
+}
+
+/// Render one file through the output template.
+/// Placeholders are substituted in a single pass, so any that appear inside a file's
+/// path or contents are left alone.
+/// With `--format xml` the substituted values are escaped first.
+pub(crate) fn format_file(vars: &TemplateVars, config: &YekConfig) -> String {
+    if config.format == OutputFormat::Xml {
+        let path = xml_escape_attr(vars.path);
+        let content = xml_escape(vars.content);
+        let commit = vars.commit.map(|commit| LastCommit {
+            author: xml_escape(&commit.author).into_owned(),
+            ..commit.clone()
+        });
+        return render_template(
+            &TemplateVars {
+                path: &path,
+                content: &content,
+                commit: commit.as_ref(),
+                ..*vars
+            },
+            config,
+        );
+    }
+    render_template(vars, config)
+}
+
+/// Placeholders recognized in output templates
+const TEMPLATE_PLACEHOLDERS: [&str; 10] = [
+    "FILE_INDEX",
+    "FILE_COUNT",
+    "FILE_LANG",
+    "FILE_AUTHOR",
+    "FILE_COMMIT",
+    "FILE_SIZE",
+    "FILE_TOKENS",
+    "FILE_MTIME",
+    "FILE_PATH",
+    "FILE_CONTENT",
+];
+
+/// Resolve the escape sequences in an output template: `\n`, `\t`, `\r`, `\0` and `\\`.
+/// Any other backslash sequence, including a trailing lone backslash, is an error.
+pub fn unescape_template(template: &str) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('t') => output.push('\t'),
+            Some('r') => output.push('\r'),
+            Some('0') => output.push('\0'),
+            Some('\\') => output.push('\\'),
+            Some(other) => {
+                return Err(anyhow!(
+                    "output_template: Unknown escape sequence '\\{}' (use \\\\ for a backslash)",
+                    other.escape_debug()
+                ))
+            }
+            None => {
+                return Err(anyhow!(
+                    "output_template: Trailing backslash (use \\\\ for a backslash)"
+                ))
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn render_template(vars: &TemplateVars, config: &YekConfig) -> String {
+    // Invalid escapes are rejected by `validate`; such a template is used as given
+    let template = config.effective_output_template();
+    let template = unescape_template(&template).unwrap_or_else(|_| template.to_string());
+
+    let value = |placeholder: &str| -> Cow<'_, str> {
+        match placeholder {
+            "FILE_INDEX" => vars.index.to_string().into(),
+            "FILE_COUNT" => vars.count.to_string().into(),
+            "FILE_LANG" => vars.lang.into(),
+            "FILE_AUTHOR" => vars
+                .commit
+                .map_or("", |commit| commit.author.as_str())
+                .into(),
+            "FILE_COMMIT" => vars
+                .commit
+                .map_or("", |commit| commit.short_id.as_str())
+                .into(),
+            "FILE_SIZE" => ByteSize::b(vars.size as u64).to_string().into(),
+            "FILE_TOKENS" => vars
+                .tokens
+                .map_or_else(String::new, |tokens| tokens.to_string())
+                .into(),
+            "FILE_MTIME" => {
+                let time = vars
+                    .mtime
+                    .map_or_else(String::new, |time| format_mtime(time, config));
+                if config.format == OutputFormat::Xml {
+                    xml_escape_attr(&time).into_owned().into()
+                } else {
+                    time.into()
+                }
+            }
+            "FILE_PATH" => vars.path.into(),
+            _ => vars.content.into(),
+        }
+    };
+
+    // One pass over the template: substituted values are copied as-is, never rescanned
+    let mut output = String::with_capacity(template.len() + vars.content.len());
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find("FILE_") {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match TEMPLATE_PLACEHOLDERS.iter().find(|p| rest.starts_with(**p)) {
+            Some(placeholder) => {
+                output.push_str(&value(placeholder));
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                output.push_str("FILE_");
+                rest = &rest["FILE_".len()..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// The JSON object for one file. `--show-authors` adds its last commit's author and hash,
+/// `--show-mtime` its last-modified time
+pub(crate) fn file_json(
+    file: &ProcessedFile,
+    path: &str,
+    content: &str,
+    config: &YekConfig,
+) -> serde_json::Value {
+    let mut value = serde_json::json!({
+        "filename": path,
+        "content": content,
+    });
+    if config.show_authors {
+        let commit = file.last_commit.as_ref();
+        value["author"] = commit.map(|c| c.author.as_str()).unwrap_or_default().into();
+        value["commit"] = commit
+            .map(|c| c.short_id.as_str())
+            .unwrap_or_default()
+            .into();
+    }
+    if config.show_mtime {
+        value["mtime"] = mtime_json(file, config);
+    }
+    value
+}
+
+/// A file's formatted last-modified time, or null when it cannot be read
+fn mtime_json(file: &ProcessedFile, config: &YekConfig) -> serde_json::Value {
+    file_mtime(file).map_or(serde_json::Value::Null, |time| {
+        format_mtime(time, config).into()
+    })
+}
+
+/// Version of the `--format json` document, bumped on any incompatible change
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// The `--format json` document: `schema_version`, the `tree` (null without
+/// `--tree-header`), one [`file_metadata_json`] object per file and summary `stats`
+fn json_document(
+    files: &[&ProcessedFile],
+    tree: Option<&str>,
+    (binary, too_large, generated, empty): (usize, usize, usize, usize),
+    config: &YekConfig,
+) -> serde_json::Value {
+    let entries: Vec<serde_json::Value> = files
+        .par_iter()
+        .map(|f| file_metadata_json(f, config))
+        .collect();
+
+    let bytes: u64 = entries.iter().filter_map(|e| e["size"].as_u64()).sum();
+    let tokens: u64 = entries.iter().filter_map(|e| e["tokens"].as_u64()).sum();
+    serde_json::json!({
+        "schema_version": JSON_SCHEMA_VERSION,
+        "tree": tree,
+        "files": entries,
+        "stats": {
+            "files": files.len(),
+            "bytes": bytes,
+            "tokens": tokens,
+            "binary": binary,
+            "too_large": too_large,
+            "generated": generated,
+            "empty": empty,
+            "redacted": files.iter().map(|f| f.redactions).sum::<usize>(),
+            "stripped_bytes": files.iter().map(|f| f.stripped_bytes).sum::<usize>(),
+        },
+    })
+}
+
+/// One file in `--format json` and `--json-lines` output: `path`, `size`, `tokens`,
+/// `language` and `content`, plus `author`/`commit` with `--show-authors` and `mtime`
+/// with `--show-mtime`
+fn file_metadata_json(file: &ProcessedFile, config: &YekConfig) -> serde_json::Value {
+    let mut entry = serde_json::json!({
+        "path": display_path(file, config),
+        "size": file.content.len(),
+        "tokens": count_file_tokens(file, &file.content, config),
+        "language": file_language(&file.rel_path, config),
+        "content": file.content,
+    });
+    if config.show_authors {
+        let commit = file.last_commit.as_ref();
+        entry["author"] = commit.map(|c| c.author.as_str()).unwrap_or_default().into();
+        entry["commit"] = commit
+            .map(|c| c.short_id.as_str())
+            .unwrap_or_default()
+            .into();
+    }
+    if config.show_mtime {
+        entry["mtime"] = mtime_json(file, config);
+    }
+    entry
+}
+
+/// The output size limit, in tokens when counting tokens and in bytes otherwise
+pub(crate) fn output_capacity(config: &YekConfig) -> Result<usize> {
+    if config.token_mode {
+        parse_token_limit(&config.tokens)
+    } else {
+        Ok(ByteSize::from_str(&config.max_size)
+            .map_err(|e| anyhow!("max_size: Invalid size format: {}", e))?
+            .as_u64() as usize)
+    }
+}
+
+/// Build tree entries for every candidate file (except those under `--tree-min-size`).
+/// Sizes cover every candidate file, including ones later dropped by the size cap.
+pub(crate) fn tree_entries<'a>(
+    files: impl IntoIterator<Item = &'a ProcessedFile>,
+    config: &YekConfig,
+) -> Result<Vec<TreeEntry>> {
+    let mut entries: Vec<TreeEntry> = files
+        .into_iter()
+        .filter(|f| shown_in_tree(f, config))
+        .map(|f| tree_entry(f, config))
+        .collect();
+
+    // Directories that already hold files are unaffected; the rest show up empty.
+    // A `--files-from` list is shown exactly as given
+    if config.tree_include_empty_dirs && !config.uses_file_list() {
+        let prefixes = root_prefixes(&config.input_paths);
+        for (input, prefix) in config.input_paths.iter().zip(prefixes) {
+            for dir in collect_directories(Path::new(input), config)? {
+                entries.push(TreeEntry::dir(match &prefix {
+                    Some(prefix) => format!("{}/{}", prefix, dir),
+                    None => dir,
+                }));
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Tree entries for every candidate, with the serializable files that did not make it
+/// into `included` marked as dropped
+fn marked_tree_entries(
+    files: &[ProcessedFile],
+    included: &[&ProcessedFile],
+    config: &YekConfig,
+) -> Result<Vec<TreeEntry>> {
+    let included: HashSet<*const ProcessedFile> = included
+        .iter()
+        .map(|f| *f as *const ProcessedFile)
+        .collect();
+    let mut entries = tree_entries(files, config)?;
+    // File entries come first, one per shown file and in the same order
+    let shown = files.iter().filter(|f| shown_in_tree(f, config));
+    for (entry, file) in entries.iter_mut().zip(shown) {
+        if is_serialized(file, config) && !included.contains(&(file as *const ProcessedFile)) {
+            entry.dropped = true;
+        }
+    }
+    Ok(entries)
+}
+
+/// The `--diff-tree` view: the tree of `files` combined with that of the `--diff-tree`
+/// directory or git ref, entries only in the input marked `+` and entries only in the
+/// other `-`. The other side is listed with the same ignore rules and filters, and both
+/// sides' paths are relative to their own root.
+pub fn diff_tree_report(files: &[ProcessedFile], config: &YekConfig) -> Result<String> {
+    let Some(other) = &config.diff_tree else {
+        return Ok(String::new());
+    };
+    let config = &YekConfig {
+        relative_to: None,
+        ..config.clone()
+    };
+    let new = tree_entries(files, config)?;
+    let old = if Path::new(other).is_dir() {
+        let other_config = YekConfig {
+            input_paths: vec![other.clone()],
+            diff_tree: None,
+            ..config.clone()
+        };
+        tree_entries(&collect_files(&other_config)?, &other_config)?
+    } else {
+        ref_tree_entries(other, config)?
+    };
+    Ok(generate_diff_tree(&old, &new, &tree_options(config)))
+}
+
+/// Tree entries for the files tracked at git ref `reference` below the input path, kept
+/// or left out by the input's ignore rules and filters
+fn ref_tree_entries(reference: &str, config: &YekConfig) -> Result<Vec<TreeEntry>> {
+    let input = config.input_paths.first().map_or(".", String::as_str);
+    let (repos, _) = input_repos(config);
+    let Some((workdir, repo)) = repos.first() else {
+        return Err(anyhow!(
+            "diff_tree: '{}' is not a directory, and '{}' is not inside a git repository",
+            reference,
+            input
+        ));
+    };
+    let tree = repo
+        .revparse_single(reference)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|e| {
+            anyhow!(
+                "diff_tree: '{}' is neither a directory nor a git ref: {}",
+                reference,
+                e.message()
+            )
+        })?;
+    let root = Path::new(input);
+    let prefix = root
+        .canonicalize()
+        .ok()
+        .and_then(|abs| abs.strip_prefix(workdir).ok().map(Path::to_path_buf))
+        .unwrap_or_default();
+
+    // Blobs below the input, with their mode and id
+    let mut blobs = Vec::new();
+    tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(git2::ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                let path = Path::new(dir).join(name);
+                if let Ok(rel) = path.strip_prefix(&prefix) {
+                    blobs.push((rel.to_path_buf(), entry.filemode(), entry.id()));
+                }
+            }
+        }
+        git2::TreeWalkResult::Ok
+    })
+    .map_err(|e| anyhow!("diff_tree: cannot read '{}': {}", reference, e.message()))?;
+
+    let odb = repo.odb().ok();
+    let mut entries = Vec::new();
+    for (rel, mode, id) in blobs {
+        if !is_walked_file(root, &root.join(&rel), config)? {
+            continue;
+        }
+        let path = to_forward_slashes(&rel.to_string_lossy());
+        // Symlinks are stored as blobs holding their target
+        let target = (mode == 0o120000 && !config.follow_symlinks)
+            .then(|| repo.find_blob(id).ok())
+            .flatten();
+        entries.push(match target {
+            Some(blob) => TreeEntry::link(path, String::from_utf8_lossy(blob.content())),
+            None => {
+                let size = odb
+                    .as_ref()
+                    .and_then(|odb| odb.read_header(id).ok())
+                    .map(|(size, _)| size as u64);
+                TreeEntry::file(path, size)
+            }
+        });
+    }
+    Ok(entries)
+}
+
+/// The tree entry for one file: a link for symlinks that were not followed, else the
+/// file with its size on disk
+fn tree_entry(file: &ProcessedFile, config: &YekConfig) -> TreeEntry {
+    let path = to_forward_slashes(&relative_path(file, config));
+    match &file.symlink_target {
+        Some(target) => TreeEntry::link(path, target),
+        None => TreeEntry::file(path, Some(disk_size(file))),
+    }
+}
+
+/// Whether a file is listed in the tree: everything but empty files with
+/// `--exclude-empty-files` and files under `--tree-min-size`. Symlinks have no size of
+/// their own and are always listed.
+fn shown_in_tree(file: &ProcessedFile, config: &YekConfig) -> bool {
+    if file.empty {
+        return false;
+    }
+    file.symlink_target.is_some()
+        || config
+            .tree_min_size_bytes()
+            .is_none_or(|min| file_size(file) >= min)
+}
+
+/// Size of a file's serialized content; for binaries, the size on disk
+fn file_size(file: &ProcessedFile) -> u64 {
+    if file.binary {
+        disk_size(file)
+    } else {
+        file.content.len() as u64
+    }
+}
+
+/// Size of a file on disk, before decoding, stripping, redaction or truncation; the size
+/// of its content when it is not on disk
+fn disk_size(file: &ProcessedFile) -> u64 {
+    std::fs::metadata(&file.disk_path)
+        .map(|m| m.len())
+        .unwrap_or(file.content.len() as u64)
+}
+
+/// Whether a file's content goes in the output, rather than only being listed in the tree
+pub fn is_serialized(file: &ProcessedFile, config: &YekConfig) -> bool {
+    !file.unchanged
+        && !file.too_large
+        && !file.generated
+        && !file.empty
+        && file.symlink_target.is_none()
+        && (!file.binary || config.include_binary)
+}
+
+/// Print how many binary files were found but left out of the serialized content
+pub fn report_skipped_binaries(files: &[ProcessedFile], config: &YekConfig) {
+    if config.include_binary {
+        return;
+    }
+    let skipped = files.iter().filter(|f| f.binary).count();
+    if skipped > 0 {
+        tracing::info!("Skipped {skipped} binary file(s); use --include-binary to include them");
+    }
+}
+
+/// Translate tree-related config flags into rendering options
+pub(crate) fn tree_options(config: &YekConfig) -> TreeOptions {
+    TreeOptions {
+        show_sizes: config.tree_sizes,
+        show_counts: config.tree_counts,
+        max_depth: config.tree_depth,
+        style: config.tree_style,
+        collapse: config.tree_collapse,
+        sort: config.tree_sort,
+        files_first: config.tree_dirs_first == TreeDirsFirst::False,
+        order: config.tree_order,
+        sort_case: config.tree_sort_case,
+        header_text: config.tree_header_text.clone().or_else(|| {
+            (config.format == OutputFormat::Xml).then(|| "<directory_structure>".to_string())
+        }),
+        footer_text: config.tree_footer_text.clone().or_else(|| {
+            (config.format == OutputFormat::Xml).then(|| "</directory_structure>".to_string())
+        }),
+        escape_xml: config.format == OutputFormat::Xml,
+        dir_marker: Some(if config.no_tree_trailing_slash {
+            String::new()
+        } else {
+            config.tree_dir_marker.clone()
+        }),
+        root_name: config.root_name.clone(),
+        color: config.tree_color,
+        indent: config.tree_indent,
+        connectors: config.tree_connectors.clone(),
+    }
+}
+
+/// Parse a token limit string like "800k" or "1000" into a number
+pub fn parse_token_limit(limit: &str) -> anyhow::Result<usize> {
+    if limit.to_lowercase().ends_with('k') {
+        limit[..limit.len() - 1]
+            .trim()
+            .parse::<usize>()
+            .map(|n| n * 1000)
+            .map_err(|e| anyhow!("tokens: Invalid token size: {}", e))
+    } else {
+        limit
+            .parse::<usize>()
+            .map_err(|e| anyhow!("tokens: Invalid token size: {}", e))
+    }
+}
+
+/// Count tokens using tiktoken's GPT-3.5-Turbo tokenizer for accuracy
+pub fn count_tokens(text: &str) -> usize {
+    count_tokens_with(text, Tokenizer::default())
+}
+
+/// Count tokens using a specific tokenizer encoding
+pub fn count_tokens_with(text: &str, tokenizer: Tokenizer) -> usize {
+    get_tokenizer(tokenizer)
+        .encode_with_special_tokens(text)
+        .len()
+}
+
+/// Count tokens the way the config asks: exactly with the model's tokenizer, or estimated
+pub fn count_config_tokens(text: &str, config: &YekConfig) -> usize {
+    match config.token_estimator {
+        TokenEstimator::Tiktoken => count_tokens_with(text, config.tokenizer()),
+        TokenEstimator::Heuristic => estimate_tokens(text),
+    }
+}
+
+/// Count tokens in text rendered from `file`, like [`count_config_tokens`], reusing the
+/// count from an earlier run when the file is unchanged (see [`cache::TokenCache`]).
+/// The heuristic is cheaper than a cache lookup, so only tokenizer counts are cached.
+pub fn count_file_tokens(file: &ProcessedFile, text: &str, config: &YekConfig) -> usize {
+    match config.token_estimator {
+        TokenEstimator::Tiktoken => match cache::token_cache(config) {
+            Some(cache) => cache.tokens(&file.disk_path, text, config.tokenizer(), |text| {
+                count_tokens_with(text, config.tokenizer())
+            }),
+            None => count_tokens_with(text, config.tokenizer()),
+        },
+        TokenEstimator::Heuristic => estimate_tokens(text),
+    }
 }
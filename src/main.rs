@@ -1,92 +1,368 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bytesize::ByteSize;
+use clap::Parser;
 use rayon::join;
-use std::path::Path;
-use tracing::{debug, Level};
-use tracing_subscriber::fmt;
-use yek::{config::YekConfig, serialize_repo};
+use std::{
+    io::{self, BufWriter, IsTerminal, Write},
+    path::Path,
+};
+use tracing::{debug, info, warn, Event, Level, Subscriber};
+use tracing_subscriber::{
+    fmt::{self, format, FmtContext, FormatEvent, FormatFields},
+    registry::LookupSpan,
+};
+use yek::{
+    atomic::AtomicFile,
+    cache::{clear_token_cache, save_token_cache},
+    clipboard::{clipboard_summary, copy_to_clipboard},
+    collect_files, collect_files_counting_ignored,
+    compress::{compressed_path, CompressedWriter},
+    config::{OutputFormat, YekConfig},
+    count_report, diff_tree_report, dry_run_report, file_tokens_estimate,
+    interactive::pick_files,
+    is_serialized, language_report, language_stats, manifest, measure_output,
+    parallel::ProcessedFile,
+    report_skipped_binaries, serialize_repo,
+    split::{split_into_chunks, write_chunks},
+    unpack::{unpack, UnpackArgs},
+    watch::watch,
+    write_manifest_file, write_output,
+};
+
+/// Exit status with `--fail-on-empty` when no file passes the filters
+const EMPTY_EXIT_CODE: i32 = 3;
 
 fn main() -> Result<()> {
+    // `yek unpack` reverses a previous run and takes its own arguments
+    if std::env::args().nth(1).as_deref() == Some("unpack") {
+        let args = UnpackArgs::parse_from(std::env::args().skip(1));
+        let written = unpack(&args)?;
+        println!("Unpacked {} file(s) into {}", written.len(), args.into);
+        return Ok(());
+    }
+
     // 1) Parse CLI + config files:
     let mut full_config = YekConfig::init_config();
 
-    let env_filter = if full_config.debug {
-        "yek=debug,ignore=off"
+    // 2) Initialize tracing on stderr, so stdout only ever carries the output.
+    // Messages print as-is by default, with warnings and errors labelled as such;
+    // with -v/--debug they get timestamps and levels.
+    let level = full_config.log_level();
+    let env_filter = format!("yek={},ignore=off", level.as_str().to_lowercase());
+    let verbose = level > Level::INFO;
+    let subscriber = fmt::Subscriber::builder()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_env_filter(env_filter);
+    if verbose {
+        subscriber
+            .with_target(false)
+            .with_thread_ids(false)
+            .with_thread_names(false)
+            .with_file(false)
+            .with_line_number(false)
+            .compact()
+            .init();
     } else {
-        "yek=info,ignore=off"
-    };
+        subscriber.event_format(PlainFormat).init();
+    }
 
-    // 2) Initialize tracing:
-    fmt::Subscriber::builder()
-        .with_max_level(if full_config.debug {
-            Level::DEBUG
-        } else {
-            Level::INFO
-        })
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_thread_names(false)
-        .with_file(false)
-        .with_line_number(false)
-        .with_level(true)
-        .with_env_filter(env_filter)
-        .compact()
-        .init();
-
-    if full_config.debug {
+    if verbose {
         let config_str = serde_json::to_string_pretty(&full_config)?;
         debug!("Configuration:\n{}", config_str);
     }
 
-    // If streaming => skip checksum + read. Just do single-thread call to serialize_repo.
-    // If not streaming => run checksum + repo serialization in parallel.
+    // Cap the shared thread pool used for reading and measuring files
+    if let Some(jobs) = full_config.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
+
+    if full_config.clear_cache {
+        if let Some(dir) = &full_config.cache_dir {
+            if clear_token_cache(Path::new(dir))? {
+                info!("Cleared the token cache in {}", dir);
+            }
+        }
+    }
+
+    // The picker narrows the selection once; the output is then built from that list
+    if full_config.interactive {
+        match pick_files(&full_config)? {
+            Some(paths) => full_config.picked_files = Some(paths),
+            None => return Ok(()),
+        }
+    }
+
+    run(&mut full_config)?;
+
+    // Watch mode regenerates the same way on every relevant change
+    if full_config.watch {
+        watch(&mut full_config, run)?;
+    }
+
+    Ok(())
+}
+
+/// Log format without -v/--debug: the message alone, after "Warning: " or "Error: "
+/// for those levels
+struct PlainFormat;
+
+impl<S, N> FormatEvent<S, N> for PlainFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        match *event.metadata().level() {
+            Level::ERROR => write!(writer, "Error: ")?,
+            Level::WARN => write!(writer, "Warning: ")?,
+            _ => {}
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// Produce the output once, then keep the token counts for the next run
+fn run(full_config: &mut YekConfig) -> Result<()> {
+    let serialized = produce_output(full_config)?;
+    // A cache that cannot be written only costs speed next time
+    if let Err(e) = save_token_cache(full_config) {
+        warn!("{}", e);
+    }
+    if full_config.fail_on_empty && serialized == 0 {
+        eprintln!("Error: no files passed the filters (--fail-on-empty)");
+        std::process::exit(EMPTY_EXIT_CODE);
+    }
+    Ok(())
+}
+
+/// Produce the output once, in whichever mode the config selects. Returns how many
+/// files passed the filters.
+fn produce_output(full_config: &mut YekConfig) -> Result<usize> {
+    let serialized = |files: &[ProcessedFile], config: &YekConfig| {
+        files.iter().filter(|f| is_serialized(f, config)).count()
+    };
+
+    // Only the totals are printed, nothing is formatted or written
+    if full_config.count_only {
+        let files = collect_files(full_config)?;
+        print!("{}", count_report(&files, full_config));
+        return Ok(serialized(&files, full_config));
+    }
+
+    // Only the language breakdown is printed, whatever the output budget
+    if full_config.lang_stats_only {
+        let files = collect_files(full_config)?;
+        let serialized: Vec<_> = files
+            .iter()
+            .filter(|f| is_serialized(f, full_config))
+            .collect();
+        print!(
+            "{}",
+            language_report(&language_stats(&serialized, full_config))
+        );
+        return Ok(serialized.len());
+    }
+
+    // Only the comparison tree is printed
+    if full_config.diff_tree.is_some() {
+        let files = collect_files(full_config)?;
+        print!("{}", diff_tree_report(&files, full_config)?);
+        return Ok(serialized(&files, full_config));
+    }
+
+    // Dry run only previews the selection, nothing is written
+    if full_config.dry_run {
+        let files = collect_files(full_config)?;
+        print!("{}", dry_run_report(&files, full_config)?);
+        return Ok(serialized(&files, full_config));
+    }
+
+    // A manifest on stdout replaces the output
+    if full_config.manifest.as_deref() == Some("-") {
+        let files = collect_files(full_config)?;
+        print!("{}", manifest(&files, full_config)?);
+        return Ok(serialized(&files, full_config));
+    }
+
+    // Split mode writes numbered chunk files instead of a single output
+    if let Some(split_dir) = &full_config.split_output {
+        let files = collect_files(full_config)?;
+        let chunks = split_into_chunks(&files, full_config)?;
+        report_skipped_binaries(&files, full_config);
+        let extension = if full_config.json_output() {
+            "json"
+        } else {
+            "txt"
+        };
+        let paths = write_chunks(Path::new(split_dir), &chunks, extension)?;
+        write_manifest_file(&manifest(&files, full_config)?, full_config)?;
+
+        println!("Wrote {} chunk(s) to {}", chunks.len(), split_dir);
+        for (path, chunk) in paths.iter().zip(&chunks) {
+            println!(
+                "  {} ({}, {} entries)",
+                path.display(),
+                ByteSize::b(chunk.content.len() as u64),
+                chunk.entry_count
+            );
+        }
+        return Ok(serialized(&files, full_config));
+    }
+
+    // Clipboard mode replaces both stdout and the output file
+    if full_config.clipboard {
+        let (output, files) = serialize_repo(full_config)?;
+        confirm_output(full_config, || {
+            Ok((output.len(), file_tokens_estimate(&output, full_config)))
+        })?;
+        copy_to_clipboard(&output)?;
+        info!("{}", clipboard_summary(files.len(), output.len()));
+        return Ok(files.len());
+    }
+
+    // If streaming => skip checksum, write straight to stdout as files are rendered.
+    // If not streaming => collect files & compute checksum in parallel, then stream to the file.
     if full_config.stream {
-        let (output, files) = serialize_repo(&full_config)?;
-        // We print actual text to stdout:
-        println!("{}", output);
+        let (files, ignored) = collect_files_counting_ignored(full_config)?;
+        confirm_output(full_config, || measure(&files, full_config))?;
 
-        if full_config.debug {
-            debug!("{} files processed (streaming).", files.len());
-            debug!("Output lines: {}", output.lines().count());
+        let compression = full_config.compress.filter(|_| full_config.compress_stdout);
+        let mut out = CompressedWriter::new(BufWriter::new(io::stdout().lock()), compression)?;
+        write_output(&files, ignored, full_config, &mut out)?;
+        // JSON Lines records already end with a newline
+        if !full_config.json_lines {
+            writeln!(out)?;
         }
+        out.finish()?.flush()?;
+        report_skipped_binaries(&files, full_config);
+
+        let serialized = serialized(&files, full_config);
+        if full_config.log_level() >= Level::DEBUG {
+            debug!("{} files processed (streaming).", serialized);
+        }
+        Ok(serialized)
     } else {
-        // Not streaming => run repo serialization & checksum in parallel
-        let (serialization_res, checksum_res) = join(
-            || serialize_repo(&full_config),
+        // Not streaming => run file collection & checksum in parallel
+        let (files_res, checksum_res) = join(
+            || collect_files_counting_ignored(full_config),
             || YekConfig::get_checksum(&full_config.input_paths),
         );
 
         // Handle both results
-        let (output_string, files) = serialization_res?;
+        let (files, ignored) = files_res?;
         let checksum = checksum_res;
 
         // Now set the final output file with the computed checksum
-        let extension = if full_config.json { "json" } else { "txt" };
-        let output_dir = full_config.output_dir.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Output directory is required when not in streaming mode. This may indicate a configuration validation error.")
-        })?;
+        let extension = if full_config.json_lines {
+            "jsonl"
+        } else if full_config.json_output() {
+            "json"
+        } else if full_config.format == OutputFormat::Xml {
+            "xml"
+        } else {
+            "txt"
+        };
+        let final_path = match full_config.output_file() {
+            Some(path) => path,
+            None => {
+                let output_dir = full_config.output_dir.as_ref().ok_or_else(|| {
+                    anyhow::anyhow!("Output directory is required when not in streaming mode. This may indicate a configuration validation error.")
+                })?;
 
-        let final_path = Path::new(output_dir)
-            .join(format!("yek-output-{}.{}", checksum, extension))
-            .to_string_lossy()
-            .to_string();
+                // Watch mode rewrites one fixed file, so it can stay open in an editor or viewer
+                let name = if full_config.watch {
+                    format!("yek-output.{}", extension)
+                } else {
+                    format!("yek-output-{}.{}", checksum, extension)
+                };
+                let mut final_path = Path::new(output_dir)
+                    .join(name)
+                    .to_string_lossy()
+                    .to_string();
+                if let Some(compression) = full_config.compress {
+                    final_path = compressed_path(&final_path, compression);
+                }
+                final_path
+            }
+        };
         full_config.output_file_full_path = Some(final_path.clone());
+        confirm_output(full_config, || measure(&files, full_config))?;
+
+        // Stream the output into a temporary file, compressing on the fly with --compress,
+        // and move it into place once complete so an error never leaves a partial file
+        let mut out = CompressedWriter::new(
+            BufWriter::new(AtomicFile::create(&final_path)?),
+            full_config.compress,
+        )?;
+        write_output(&files, ignored, full_config, &mut out)?;
+        out.finish()?
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .commit()?;
+        report_skipped_binaries(&files, full_config);
 
         // If debug, show stats
-        if full_config.debug {
-            let size = ByteSize::b(output_string.len() as u64);
-            debug!("{} files processed", files.len());
+        let serialized = serialized(&files, full_config);
+        if full_config.log_level() >= Level::DEBUG {
+            let size = ByteSize::b(std::fs::metadata(&final_path)?.len());
+            debug!("{} files processed", serialized);
             debug!("{} generated", size);
-            debug!("{} lines generated", output_string.lines().count());
         }
 
-        // Actually write the final output file.
-        // We'll do it right here (instead of inside `serialize_repo`) to ensure we use our new final_path:
-        std::fs::write(&final_path, output_string.as_bytes())?;
-
         // Print path to stdout (like original code did)
         println!("{}", final_path);
+        Ok(serialized)
     }
+}
 
-    Ok(())
+/// With `--confirm-over`, ask on the terminal before writing output larger than the
+/// threshold. `measure` gives the output's size in bytes and its tokens, and is only
+/// called when there is a terminal to ask on; otherwise the output is written without
+/// asking.
+fn confirm_output(
+    config: &YekConfig,
+    measure: impl FnOnce() -> Result<(usize, usize)>,
+) -> Result<()> {
+    let Some(limit) = config.confirm_over_bytes() else {
+        return Ok(());
+    };
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Ok(());
+    }
+    let (bytes, tokens) = measure()?;
+    if bytes as u64 <= limit {
+        return Ok(());
+    }
+
+    let approx = if config.token_mode { "" } else { "~" };
+    eprint!(
+        "About to write {} / {}{} tokens, continue? [y/N] ",
+        ByteSize::b(bytes as u64),
+        approx,
+        tokens
+    );
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        Err(anyhow!("confirm_over: cancelled, nothing was written"))
+    }
+}
+
+/// Bytes and tokens of the output for `files`, measured without writing it
+fn measure(files: &[ProcessedFile], config: &YekConfig) -> Result<(usize, usize)> {
+    let stats = measure_output(files, config)?;
+    Ok((stats.bytes, stats.tokens.unwrap_or(0)))
 }
@@ -0,0 +1,75 @@
+/// Tokenizer encoding used to count tokens for a model
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// cl100k_base, used by GPT-3.5/GPT-4 (and a close approximation for non-OpenAI models)
+    #[default]
+    Cl100k,
+    /// o200k_base, used by GPT-4o and the o1 family
+    O200k,
+}
+
+/// How token counts are computed in `--tokens` mode
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenEstimator {
+    /// Exact counts from the model's BPE tokenizer
+    #[default]
+    Tiktoken,
+    /// Fast approximation of roughly four characters per token
+    Heuristic,
+}
+
+/// Approximate token count: one token per four characters, rounded up
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// A named model with its context window and tokenizer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModelPreset {
+    pub name: &'static str,
+    pub context_tokens: usize,
+    pub tokenizer: Tokenizer,
+}
+
+const fn preset(name: &'static str, context_tokens: usize, tokenizer: Tokenizer) -> ModelPreset {
+    ModelPreset {
+        name,
+        context_tokens,
+        tokenizer,
+    }
+}
+
+/// Built-in model presets for `--model`
+pub const MODEL_PRESETS: &[ModelPreset] = &[
+    preset("gpt-4o", 128_000, Tokenizer::O200k),
+    preset("gpt-4o-mini", 128_000, Tokenizer::O200k),
+    preset("o1", 200_000, Tokenizer::O200k),
+    preset("o1-mini", 128_000, Tokenizer::O200k),
+    preset("gpt-4-turbo", 128_000, Tokenizer::Cl100k),
+    preset("gpt-4", 8_192, Tokenizer::Cl100k),
+    preset("gpt-3.5-turbo", 16_385, Tokenizer::Cl100k),
+    preset("claude-3-5-sonnet", 200_000, Tokenizer::Cl100k),
+    preset("claude-3-5-haiku", 200_000, Tokenizer::Cl100k),
+    preset("claude-3-opus", 200_000, Tokenizer::Cl100k),
+    preset("gemini-1.5-pro", 2_000_000, Tokenizer::Cl100k),
+    preset("gemini-1.5-flash", 1_000_000, Tokenizer::Cl100k),
+];
+
+/// Look up a preset by name (case-insensitive)
+pub fn find_model(name: &str) -> Option<&'static ModelPreset> {
+    MODEL_PRESETS
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Comma-separated list of preset names, for error messages
+pub fn supported_models() -> String {
+    MODEL_PRESETS
+        .iter()
+        .map(|preset| preset.name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,49 +1,142 @@
-use crate::{config::YekConfig, priority::get_file_priority, Result};
+use crate::{
+    config::{InvalidUtf8, YekConfig},
+    defaults::YEKIGNORE_FILE_NAME,
+    encoding::{decode_content, decode_content_lossy},
+    gitattributes::ExportIgnore,
+    priority::{get_file_priority, LastCommit},
+    progress::ScanProgress,
+    Result,
+};
+use anyhow::anyhow;
+use base64::prelude::*;
 use content_inspector::{inspect, ContentType};
-use glob::glob;
-use ignore::gitignore::GitignoreBuilder;
+use encoding_rs::Encoding;
+use glob::{glob_with, MatchOptions};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
 use path_slash::PathBufExt;
 use rayon::prelude::*;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     path::Path,
-    sync::{mpsc, Arc},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
 };
-use tracing::debug;
+use tracing::{debug, warn};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ProcessedFile {
     pub priority: i32,
     pub file_index: usize,
     pub rel_path: String,
     pub content: String,
+    /// Path on disk as discovered (the input path joined with the walked entry)
+    pub disk_path: std::path::PathBuf,
+    /// Binary file. Its content is base64 with `--include-binary` and empty otherwise,
+    /// in which case it is listed in the tree but not serialized.
+    pub binary: bool,
+    /// The file's last commit, filled in by `--git-recent`/`--show-authors` (None if untracked)
+    pub last_commit: Option<LastCommit>,
+    /// Not changed since the `--since` ref; kept only so `--full-tree` can list it
+    pub unchanged: bool,
+    /// Over `--max-file-size` (skip action); kept only so the tree can list it
+    pub too_large: bool,
+    /// Looks generated or minified (`--skip-generated`); kept only so the tree can list it
+    pub generated: bool,
+    /// Zero bytes (`--exclude-empty-files`); left out of the tree as well, and kept only
+    /// so `--stats` can count it
+    pub empty: bool,
+    /// Target of a symlink that was not followed (no `--follow-symlinks`).
+    /// The link is listed in the tree as `name -> target` and not serialized
+    pub symlink_target: Option<String>,
+    /// With `--dedupe`, the relative path of an earlier file with identical content.
+    /// The content is then just a reference line
+    pub duplicate_of: Option<String>,
+    /// Matches of `--redact` patterns replaced in the content
+    pub redactions: usize,
+    /// Bytes removed by `--strip-comments` and `--strip-blank-lines`
+    pub stripped_bytes: usize,
+}
+
+/// Config needed to turn raw bytes into file content, cheap to copy into worker threads
+#[derive(Clone, Copy)]
+struct ContentOptions {
+    encoding: Option<&'static encoding_rs::Encoding>,
+    include_binary: bool,
+    on_invalid: InvalidUtf8,
+}
+
+impl ContentOptions {
+    fn from_config(config: &YekConfig) -> Self {
+        Self {
+            encoding: config.source_encoding(),
+            include_binary: config.include_binary,
+            on_invalid: config.on_invalid_utf8,
+        }
+    }
+}
+
+/// Turn raw bytes into `(content, is_binary)`. Text is transcoded to UTF-8; binary
+/// content is base64-encoded when included and left empty otherwise.
+/// Text that cannot be decoded is handled per `--on-invalid-utf8`: replaced lossily,
+/// skipped with a warning (`None`) or returned as an error.
+fn file_content(
+    bytes: &[u8],
+    rel_path: &str,
+    options: ContentOptions,
+) -> Result<Option<(String, bool)>> {
+    if looks_binary(bytes, options.encoding) {
+        debug!("Binary file: {rel_path}");
+        let content = if options.include_binary {
+            BASE64_STANDARD.encode(bytes)
+        } else {
+            String::new()
+        };
+        return Ok(Some((content, true)));
+    }
+
+    if options.on_invalid == InvalidUtf8::Lossy {
+        return Ok(Some((decode_content_lossy(bytes, options.encoding), false)));
+    }
+    match decode_content(bytes, options.encoding) {
+        Ok(text) => Ok(Some((text, false))),
+        Err(e) if options.on_invalid == InvalidUtf8::Error => Err(anyhow!(
+            "on_invalid_utf8: {rel_path}: {e}; use --on-invalid-utf8 lossy or skip to continue"
+        )),
+        Err(e) => {
+            warn!("skipping {rel_path}: {e}");
+            Ok(None)
+        }
+    }
 }
 
 /// Process a single file, checking ignore patterns and reading its contents.
+/// Ignore rules are evaluated relative to `ignore_root`, which is the file's own
+/// directory for literal paths and the wildcard-free prefix for glob matches.
+/// A `named` file (given literally rather than matched by a glob) is wanted even if an
+/// ignore rule matches it; `--include`/`--exclude` still apply.
 fn process_single_file(
     file_path: &Path,
+    ignore_root: &Path,
+    named: bool,
     config: &YekConfig,
     boost_map: &HashMap<String, i32>,
+    ignored: &AtomicUsize,
+    progress: &ScanProgress,
 ) -> Result<Vec<ProcessedFile>> {
     let base_dir = file_path.parent().unwrap_or(Path::new(""));
     let rel_path = normalize_path(file_path, base_dir);
 
-    // Build the gitignore
-    let mut gitignore_builder = GitignoreBuilder::new(base_dir);
-    for pattern in &config.ignore_patterns {
-        gitignore_builder.add_line(None, pattern)?;
-    }
-
-    // If there is a .gitignore in this folder, add it last so its "!" lines override prior patterns
-    let gitignore_file = base_dir.join(".gitignore");
-    if gitignore_file.exists() {
-        gitignore_builder.add(&gitignore_file);
-    }
-
-    let gitignore = gitignore_builder.build()?;
-    if gitignore.matched(file_path, false).is_ignore() {
+    if (!named && is_ignored_under(ignore_root, file_path, config)?)
+        || !PathFilter::new(ignore_root, config)?.allows(file_path)
+    {
         debug!("Skipping ignored file: {rel_path}");
+        ignored.fetch_add(1, Ordering::Relaxed);
         return Ok(Vec::new());
     }
 
@@ -51,9 +144,10 @@ fn process_single_file(
 
     match fs::read(file_path) {
         Ok(content) => {
-            if inspect(&content) == ContentType::BINARY {
-                debug!("Skipping binary file: {rel_path}");
-            } else {
+            progress.read_file(content.len() as u64);
+            if let Some((text, binary)) =
+                file_content(&content, &rel_path, ContentOptions::from_config(config))?
+            {
                 let rule_priority = get_file_priority(&rel_path, &config.priority_rules);
                 let boost = boost_map.get(&rel_path).copied().unwrap_or(0);
                 let combined_priority = rule_priority + boost;
@@ -62,7 +156,10 @@ fn process_single_file(
                     priority: combined_priority,
                     file_index: 0, // For a single file, the index is always 0
                     rel_path,
-                    content: String::from_utf8_lossy(&content).to_string(),
+                    content: text,
+                    disk_path: file_path.to_path_buf(),
+                    binary,
+                    ..Default::default()
                 });
             }
         }
@@ -74,111 +171,193 @@ fn process_single_file(
     Ok(processed_files)
 }
 
+/// The `--max-total-files` error for an input that found more files than allowed
+fn too_many_files(config: &YekConfig, base_path: &Path) -> anyhow::Error {
+    anyhow!(
+        "max_total_files: found more than {} files under '{}'; narrow the input paths or \
+         raise --max-total-files",
+        config.max_total_files,
+        base_path.display()
+    )
+}
+
 /// Walk files in parallel (if a directory is given), skipping ignored paths,
-/// then read each file's contents in a separate thread.
+/// then read each file's contents on the rayon thread pool.
 /// Return the resulting `ProcessedFile` objects.
 pub fn process_files_parallel(
     base_path: &Path,
     config: &YekConfig,
     boost_map: &HashMap<String, i32>,
+) -> Result<Vec<ProcessedFile>> {
+    process_files_parallel_counting(
+        base_path,
+        config,
+        boost_map,
+        &AtomicUsize::new(0),
+        &ScanProgress::hidden(),
+    )
+}
+
+/// Same as [`process_files_parallel`], adding the number of files skipped by yek's
+/// ignore patterns and `--include`/`--exclude` filters to `ignored`.
+/// Files pruned by the walker's own .gitignore handling are not seen, so not counted.
+/// Files found and read are reported to `progress`.
+pub fn process_files_parallel_counting(
+    base_path: &Path,
+    config: &YekConfig,
+    boost_map: &HashMap<String, i32>,
+    ignored: &AtomicUsize,
+    progress: &ScanProgress,
 ) -> Result<Vec<ProcessedFile>> {
     // Expand globs into a list of paths
     let mut expanded_paths = Vec::new();
     let path_str = base_path.to_string_lossy();
-    for entry in glob(&path_str)? {
+    let options = MatchOptions {
+        require_literal_leading_dot: !config.walks_hidden(),
+        ..MatchOptions::new()
+    };
+    for entry in glob_with(&path_str, options)? {
         match entry {
-            Ok(path) => expanded_paths.push(path),
+            Ok(path) => {
+                // Matched files count toward --max-total-files before any is read;
+                // matched directories are counted as they are walked
+                if path.is_file() {
+                    let found = progress.found_file();
+                    if config.max_total_files > 0 && found > config.max_total_files {
+                        return Err(too_many_files(config, base_path));
+                    }
+                }
+                expanded_paths.push(path);
+            }
             Err(e) => debug!("Glob entry error: {:?}", e),
         }
     }
 
+    // Glob matches are checked against ignore rules from the glob's literal prefix,
+    // so patterns like "target/**" and nested .gitignore files still apply
+    let pattern_root = glob_root(&path_str);
+    let ignore_root_for = |path: &Path| match &pattern_root {
+        Some(root) => root.clone(),
+        None => path.parent().unwrap_or(Path::new("")).to_path_buf(),
+    };
+
     // If it's a single file (no glob expansion or single file result), process it directly
     if expanded_paths.len() == 1 && expanded_paths[0].is_file() {
-        return process_single_file(&expanded_paths[0], config, boost_map);
+        let ignore_root = ignore_root_for(&expanded_paths[0]);
+        return process_single_file(
+            &expanded_paths[0],
+            &ignore_root,
+            pattern_root.is_none(),
+            config,
+            boost_map,
+            ignored,
+            progress,
+        );
     }
 
     // Iterate over expanded paths, handling files and directories
     let mut all_processed_files = Vec::new();
     for path in expanded_paths {
         if path.is_file() {
-            all_processed_files.extend(process_single_file(&path, config, boost_map)?);
+            let ignore_root = ignore_root_for(&path);
+            all_processed_files.extend(process_single_file(
+                &path,
+                &ignore_root,
+                pattern_root.is_none(),
+                config,
+                boost_map,
+                ignored,
+                progress,
+            )?);
         } else if path.is_dir() {
             // For directories, use the original recursive logic
-            all_processed_files.extend(process_files_parallel_internal(&path, config, boost_map)?);
+            all_processed_files.extend(process_files_parallel_internal(
+                &path, config, boost_map, ignored, progress,
+            )?);
         }
     }
 
     Ok(all_processed_files)
 }
 
+/// Read exactly the given paths, for `--files-from`. Ignore rules are not applied;
+/// `file_index` records each file's position in the list so the output keeps that order.
+/// Paths listed more than once are read once, and missing files are skipped with a warning.
+pub fn process_file_list(
+    paths: &[String],
+    config: &YekConfig,
+    progress: &ScanProgress,
+) -> Result<Vec<ProcessedFile>> {
+    let mut seen = HashSet::new();
+    let unique: Vec<&String> = paths.iter().filter(|p| seen.insert(p.as_str())).collect();
+    let options = ContentOptions::from_config(config);
+
+    unique
+        .into_par_iter()
+        .enumerate()
+        .map(|(file_index, path)| {
+            let disk_path = Path::new(path);
+            let rel_path = normalize_path(disk_path, Path::new("."));
+            let bytes = match fs::read(disk_path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    warn!("skipping {rel_path}: {e}");
+                    return Ok(None);
+                }
+            };
+            progress.read_file(bytes.len() as u64);
+            let Some((content, binary)) = file_content(&bytes, &rel_path, options)? else {
+                return Ok(None);
+            };
+            Ok(Some(ProcessedFile {
+                priority: get_file_priority(&rel_path, &config.priority_rules),
+                file_index,
+                rel_path,
+                content,
+                disk_path: disk_path.to_path_buf(),
+                binary,
+                ..Default::default()
+            }))
+        })
+        .filter_map(Result::transpose)
+        .collect()
+}
+
 /// Internal function to handle directory recursion (separated for clarity)
 fn process_files_parallel_internal(
     base_path: &Path,
     config: &YekConfig,
     boost_map: &HashMap<String, i32>,
+    ignored: &AtomicUsize,
+    progress: &ScanProgress,
 ) -> Result<Vec<ProcessedFile>> {
     // It's a directory, so walk it
     let mut walk_builder = ignore::WalkBuilder::new(base_path);
 
-    // Standard filters + no follow symlinks
+    // Standard filters, walking with as many threads as --jobs
     walk_builder
-        .follow_links(false)
-        .standard_filters(true)
+        .threads(config.jobs.unwrap_or(0))
+        .follow_links(config.follow_symlinks)
+        .standard_filters(config.uses_ignore_rules())
+        .hidden(!config.walks_hidden())
+        .filter_entry(|entry| !is_git_dir(entry))
         .require_git(false);
-
-    // Build the gitignore
-    let mut gitignore_builder = GitignoreBuilder::new(base_path);
-    // Add our custom patterns first
-    for pattern in &config.ignore_patterns {
-        gitignore_builder.add_line(None, pattern)?;
-    }
-
-    // If there is a .gitignore in this folder, add it last so its "!" lines override prior patterns
-    let gitignore_file = base_path.join(".gitignore");
-    if gitignore_file.exists() {
-        gitignore_builder.add(&gitignore_file);
+    if !config.no_yekignore && config.uses_ignore_rules() {
+        walk_builder.add_custom_ignore_filename(YEKIGNORE_FILE_NAME);
     }
 
-    let gitignore = Arc::new(gitignore_builder.build()?); // Propagate error here
+    let gitignore = Arc::new(build_gitignore(base_path, config)?); // Propagate error here
+    let path_filter = Arc::new(PathFilter::new(base_path, config)?);
+    let export_ignore = Arc::new(ExportIgnore::new(base_path, config));
+    // A symlinked directory that points back inside the walk is skipped, so each directory
+    // there is entered once, by its real path; the walker itself reports cycles elsewhere
+    let canonical_base = Arc::new(fs::canonicalize(base_path).ok());
+    // Set when the walk stopped at --max-total-files
+    let too_many = &AtomicBool::new(false);
 
-    // This channel will carry (path, rel_path) to the processing thread
+    // This channel will carry (path, rel_path) from the walker threads
     let (processed_files_tx, processed_files_rx) = mpsc::channel::<(std::path::PathBuf, String)>();
 
-    // Processing happens on a dedicated thread, to keep from blocking the main walker
-    let process_thread = std::thread::spawn({
-        let priority_rules = config.priority_rules.clone();
-        let boost_map = boost_map.clone();
-        move || {
-            let mut processed = Vec::new();
-            for (path, rel_path) in processed_files_rx {
-                // Read entire file
-                match fs::read(&path) {
-                    Ok(content) => {
-                        // Check if it's binary quickly
-                        if inspect(&content) == ContentType::BINARY {
-                            debug!("Skipping binary file: {rel_path}");
-                            continue;
-                        }
-                        // Compute priority
-                        let rule_priority = get_file_priority(&rel_path, &priority_rules);
-                        let boost = boost_map.get(&rel_path).copied().unwrap_or(0);
-                        let combined = rule_priority + boost;
-                        processed.push(ProcessedFile {
-                            priority: combined,
-                            file_index: 0, // assigned later
-                            rel_path,
-                            content: String::from_utf8_lossy(&content).to_string(),
-                        });
-                    }
-                    Err(e) => {
-                        debug!("Failed to read {rel_path}: {e}");
-                    }
-                }
-            }
-            processed
-        }
-    });
-
     // Use ignore's parallel walker to skip ignored files
     let base_cloned = base_path.to_owned();
     let walker_tx = processed_files_tx.clone();
@@ -188,14 +367,40 @@ fn process_files_parallel_internal(
         let base_dir = base_cloned.clone();
         let processed_files_tx = walker_tx.clone();
         let gitignore = Arc::clone(&gitignore);
+        let path_filter = Arc::clone(&path_filter);
+        let export_ignore = Arc::clone(&export_ignore);
+        let canonical_base = Arc::clone(&canonical_base);
 
         Box::new(move |entry| {
             let entry = match entry {
                 Ok(e) => e,
-                Err(_) => return ignore::WalkState::Continue,
+                Err(e) => {
+                    debug!("Walk error: {e}");
+                    return ignore::WalkState::Continue;
+                }
+            };
+            let Some(file_type) = entry.file_type() else {
+                return ignore::WalkState::Continue;
             };
-            // Only process files
-            if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+            if file_type.is_dir() {
+                if entry.path_is_symlink() && entry.depth() > 0 {
+                    let target = fs::canonicalize(entry.path()).ok();
+                    let inside = match (target.as_deref(), canonical_base.as_deref()) {
+                        (Some(target), Some(base)) => target.starts_with(base),
+                        _ => false,
+                    };
+                    if inside {
+                        debug!(
+                            "Skipping symlinked directory walked by its real path: {}",
+                            entry.path().display()
+                        );
+                        return ignore::WalkState::Skip;
+                    }
+                }
+                return ignore::WalkState::Continue;
+            }
+            // Process files, and symlinks that are not followed (listed in the tree only)
+            if !file_type.is_file() && !file_type.is_symlink() {
                 return ignore::WalkState::Continue;
             }
 
@@ -203,22 +408,91 @@ fn process_files_parallel_internal(
             let rel_path = normalize_path(&path, &base_dir);
 
             // If gitignore says skip, we do not even read
-            if gitignore.matched(&path, false).is_ignore() {
+            if is_path_ignored(&gitignore, &path) {
                 debug!("Skipping ignored file: {rel_path}");
+                ignored.fetch_add(1, Ordering::Relaxed);
+                return ignore::WalkState::Continue;
+            }
+
+            if !path_filter.allows(&path) {
+                debug!("Skipping filtered file: {rel_path}");
+                ignored.fetch_add(1, Ordering::Relaxed);
+                return ignore::WalkState::Continue;
+            }
+
+            if export_ignore.ignores(&path) {
+                debug!("Skipping export-ignore file: {rel_path}");
+                ignored.fetch_add(1, Ordering::Relaxed);
                 return ignore::WalkState::Continue;
             }
 
             // Otherwise we send to processing thread
+            let found = progress.found_file();
+            if config.max_total_files > 0 && found > config.max_total_files {
+                too_many.store(true, Ordering::Relaxed);
+                return ignore::WalkState::Quit;
+            }
             processed_files_tx.send((path, rel_path)).ok();
             ignore::WalkState::Continue
         })
     });
 
-    // Drop the sender so the thread can end
+    // Drop the sender so the receiver ends once the walk is done
     drop(processed_files_tx);
+    if too_many.load(Ordering::Relaxed) {
+        return Err(too_many_files(config, base_path));
+    }
 
-    // Join the processing thread
-    let mut processed_files = process_thread.join().unwrap();
+    // Walker threads finish in any order; sort so reading and indexing are deterministic
+    let mut candidates: Vec<_> = processed_files_rx.into_iter().collect();
+    candidates.sort_by(|a, b| a.1.cmp(&b.1));
+
+    // Read, decode and prioritize on the rayon pool, keeping the sorted order
+    let content_options = ContentOptions::from_config(config);
+    let mut processed_files: Vec<ProcessedFile> = candidates
+        .into_par_iter()
+        .map(|(path, rel_path)| {
+            if !config.follow_symlinks && path.is_symlink() {
+                let Ok(target) = fs::read_link(&path) else {
+                    return Ok(None);
+                };
+                return Ok(Some(ProcessedFile {
+                    priority: get_file_priority(&rel_path, &config.priority_rules),
+                    symlink_target: Some(to_forward_slashes(&target.to_string_lossy())),
+                    rel_path,
+                    disk_path: path,
+                    ..Default::default()
+                }));
+            }
+            let content = match fs::read(&path) {
+                Ok(content) => {
+                    progress.read_file(content.len() as u64);
+                    content
+                }
+                Err(e) => {
+                    debug!("Failed to read {rel_path}: {e}");
+                    return Ok(None);
+                }
+            };
+            // Check for binary content and transcode text to UTF-8
+            let Some((text, binary)) = file_content(&content, &rel_path, content_options)? else {
+                return Ok(None);
+            };
+            // Compute priority
+            let rule_priority = get_file_priority(&rel_path, &config.priority_rules);
+            let boost = boost_map.get(&rel_path).copied().unwrap_or(0);
+            Ok(Some(ProcessedFile {
+                priority: rule_priority + boost,
+                file_index: 0, // assigned below
+                rel_path,
+                content: text,
+                disk_path: path,
+                binary,
+                ..Default::default()
+            }))
+        })
+        .filter_map(Result::transpose)
+        .collect::<Result<_>>()?;
 
     // Now assign file_index within each priority group
     let mut counters = HashMap::new();
@@ -228,7 +502,7 @@ fn process_files_parallel_internal(
         *ctr += 1;
     }
 
-    if config.debug {
+    if config.log_level() >= tracing::Level::DEBUG {
         debug!(
             "Processed {} files in parallel for base_path: {}",
             processed_files.len(),
@@ -247,12 +521,379 @@ fn process_files_parallel_internal(
     Ok(processed_files)
 }
 
+/// How much of a file is sniffed for binary content
+const BINARY_SNIFF_BYTES: usize = 8192;
+
+/// Binary sniffing by content: NUL bytes near the start of the file mean binary.
+/// UTF-16 text without a BOM is full of NUL bytes, so the check is skipped when the
+/// user forces a UTF-16 encoding.
+fn looks_binary(content: &[u8], forced: Option<&'static encoding_rs::Encoding>) -> bool {
+    if forced.is_some_and(|e| e == encoding_rs::UTF_16LE || e == encoding_rs::UTF_16BE) {
+        return false;
+    }
+    let head = &content[..content.len().min(BINARY_SNIFF_BYTES)];
+    inspect(head) == ContentType::BINARY || (Encoding::for_bom(head).is_none() && head.contains(&0))
+}
+
+/// Per-directory ignore files to honor, in increasing precedence (none with `--only`)
+fn ignore_file_names(config: &YekConfig) -> &'static [&'static str] {
+    if !config.uses_ignore_rules() {
+        &[]
+    } else if config.no_yekignore {
+        &[".gitignore"]
+    } else {
+        &[".gitignore", YEKIGNORE_FILE_NAME]
+    }
+}
+
+/// Build the matcher for our custom ignore patterns plus the `.gitignore` and `.yekignore`
+/// at `base_dir`. With `--only` it matches nothing.
+fn build_gitignore(base_dir: &Path, config: &YekConfig) -> Result<Gitignore> {
+    let mut gitignore_builder = GitignoreBuilder::new(base_dir);
+    // Add our custom patterns first
+    if config.uses_ignore_rules() {
+        for pattern in &config.ignore_patterns {
+            gitignore_builder.add_line(None, pattern)?;
+        }
+    }
+
+    // If there is a .gitignore (or .yekignore) in this folder, add it last so its "!" lines
+    // override prior patterns
+    for name in ignore_file_names(config) {
+        let ignore_file = base_dir.join(name);
+        if ignore_file.exists() {
+            gitignore_builder.add(&ignore_file);
+        }
+    }
+
+    Ok(gitignore_builder.build()?)
+}
+
+/// The extensions in `--ext`/`--no-ext` values: comma-separated, lowercased and without
+/// a leading dot. Empty entries are skipped.
+pub fn parse_extensions(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+/// Whether the file name of `path` ends in one of `extensions`, ignoring case. Multi-part
+/// extensions such as "min.js" match too; a dotfile such as `.toml` has no extension.
+pub fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+    let name = name.to_string_lossy().to_lowercase();
+    extensions.iter().any(|ext| {
+        name.len() > ext.len() + 1
+            && name.ends_with(ext.as_str())
+            && name[..name.len() - ext.len()].ends_with('.')
+    })
+}
+
+/// `--include` / `--exclude` / `--only` / `--filter` globs (gitignore syntax) evaluated
+/// relative to an input root, and the `--ext` / `--no-ext` extension lists. Excludes win
+/// over includes; with no includes, everything not excluded is allowed. The `--output` and `--manifest`
+/// files are always left out, so a run never serializes what a previous run wrote.
+struct PathFilter {
+    includes: Option<Gitignore>,
+    only: Option<Gitignore>,
+    excludes: Gitignore,
+    filter: Gitignore,
+    extensions: Vec<String>,
+    excluded_extensions: Vec<String>,
+    /// Absolute paths of the files yek writes
+    written: Vec<std::path::PathBuf>,
+}
+
+impl PathFilter {
+    fn new(root: &Path, config: &YekConfig) -> Result<Self> {
+        let written = config
+            .written_files()
+            .iter()
+            .map(std::path::absolute)
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let includes = if config.include.is_empty() {
+            None
+        } else {
+            Some(build_glob_matcher(root, &config.include)?)
+        };
+        let only = if config.only.is_empty() {
+            None
+        } else {
+            Some(build_glob_matcher(root, &config.only)?)
+        };
+        Ok(Self {
+            includes,
+            only,
+            excludes: build_glob_matcher(root, &config.exclude)?,
+            filter: build_glob_matcher(root, &config.filter)?,
+            extensions: parse_extensions(&config.ext),
+            excluded_extensions: parse_extensions(&config.no_ext),
+            written,
+        })
+    }
+
+    fn allows(&self, path: &Path) -> bool {
+        if is_path_ignored(&self.excludes, path) || has_extension(path, &self.excluded_extensions) {
+            return false;
+        }
+        if !self.extensions.is_empty() && !has_extension(path, &self.extensions) {
+            return false;
+        }
+        if !filter_allows(&self.filter, path) {
+            return false;
+        }
+        // Never read back our own output
+        if !self.written.is_empty()
+            && std::path::absolute(path).is_ok_and(|path| self.written.contains(&path))
+        {
+            return false;
+        }
+        // A "match" in the include and only matchers means the path was listed
+        [&self.includes, &self.only]
+            .into_iter()
+            .flatten()
+            .all(|listed| is_path_ignored(listed, path))
+    }
+}
+
+/// Compile gitignore-style globs into a matcher rooted at `root`
+pub(crate) fn build_glob_matcher(root: &Path, patterns: &[String]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        builder.add_line(None, pattern)?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Check a file against a matcher. Directory patterns such as `generated/` also
+/// exclude everything beneath them, mirroring how git treats ignored directories.
+fn is_path_ignored(gitignore: &Gitignore, path: &Path) -> bool {
+    path_match(gitignore, path).is_ignore()
+}
+
+/// The last pattern of `gitignore` matching a file or one of its parent directories
+fn path_match<'a>(gitignore: &'a Gitignore, path: &Path) -> Match<&'a ignore::gitignore::Glob> {
+    if path.has_root() && !path.starts_with(gitignore.path()) {
+        return gitignore.matched(path, false);
+    }
+    gitignore.matched_path_or_any_parents(path, false)
+}
+
+/// Whether the `--filter` list built by [`build_glob_matcher`] keeps a file. Its plain
+/// patterns are "ignore" globs to the matcher and its `!` patterns whitelist globs, so
+/// the meanings are swapped: a plain match keeps the file and a `!` match drops it.
+/// A file matching nothing is kept only if the list has no plain patterns.
+fn filter_allows(filter: &Gitignore, path: &Path) -> bool {
+    match path_match(filter, path) {
+        Match::Ignore(_) => true,
+        Match::Whitelist(_) => false,
+        Match::None => filter.num_ignores() == 0,
+    }
+}
+
+/// Decide whether `file_path` is ignored, honoring our custom patterns plus every
+/// `.gitignore` / `.yekignore` between `root` and the file's directory. As in git, rules from
+/// deeper `.gitignore` files take precedence. Files marked `export-ignore` in a
+/// `.gitattributes` are ignored whatever the ignore files say.
+fn is_ignored_under(root: &Path, file_path: &Path, config: &YekConfig) -> Result<bool> {
+    let mut ignored = is_path_ignored(&build_gitignore(root, config)?, file_path);
+
+    let parent = file_path.parent().unwrap_or(Path::new(""));
+    let rel_dir = if root == Path::new(".") {
+        parent
+    } else {
+        match parent.strip_prefix(root) {
+            Ok(rel) => rel,
+            Err(_) => return Ok(ignored),
+        }
+    };
+
+    let mut dir = root.to_path_buf();
+    for component in rel_dir.components() {
+        dir.push(component);
+        for name in ignore_file_names(config) {
+            let ignore_file = dir.join(name);
+            if !ignore_file.is_file() {
+                continue;
+            }
+            let (nested, _) = Gitignore::new(&ignore_file);
+            match nested.matched_path_or_any_parents(file_path, false) {
+                Match::Ignore(_) => ignored = true,
+                Match::Whitelist(_) => ignored = false,
+                Match::None => {}
+            }
+        }
+    }
+
+    Ok(ignored || ExportIgnore::new(root, config).ignores(file_path))
+}
+
+/// Whether a change to `path` can affect the output: it must be one of the `--files-from`
+/// entries, or fall under an input path and pass the same hidden-file, ignore and
+/// `--include`/`--exclude` rules as the walker. Used by `--watch` to skip irrelevant events.
+pub fn is_watched_path(path: &Path, config: &YekConfig) -> Result<bool> {
+    let path = std::path::absolute(path)?;
+
+    if let Some(source) = &config.files_from {
+        let listed = crate::read_file_list(source)?;
+        return Ok(listed
+            .iter()
+            .any(|p| std::path::absolute(p).is_ok_and(|p| p == path)));
+    }
+
+    for input in &config.input_paths {
+        let (root, matches, walked) = match glob_root(input) {
+            Some(root) => {
+                let pattern = std::path::absolute(input)?;
+                let matches = glob::Pattern::new(&pattern.to_string_lossy())?.matches_path(&path);
+                (std::path::absolute(root)?, matches, true)
+            }
+            None => {
+                let input = std::path::absolute(input)?;
+                if input.is_dir() {
+                    let matches = path.starts_with(&input);
+                    (input, matches, true)
+                } else {
+                    let root = input.parent().unwrap_or(Path::new("/")).to_path_buf();
+                    (root, path == input, false)
+                }
+            }
+        };
+        if !matches {
+            continue;
+        }
+
+        // Files named directly skip the ignore rules, as when they are read
+        let listed = if walked {
+            is_walked_file(&root, &path, config)?
+        } else {
+            PathFilter::new(&root, config)?.allows(&path)
+        };
+        if listed {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Whether walking `root` would list `path`: like the walker, hidden files (without
+/// --hidden) and .git contents are skipped, then the ignore rules and the
+/// `--include`/`--exclude` filters apply. The file need not exist.
+pub(crate) fn is_walked_file(root: &Path, path: &Path, config: &YekConfig) -> Result<bool> {
+    let hidden = path.strip_prefix(root).is_ok_and(|rel| {
+        rel.components().any(|c| {
+            let name = c.as_os_str().to_string_lossy();
+            name == ".git" || (!config.walks_hidden() && name.starts_with('.'))
+        })
+    });
+    Ok(!hidden
+        && !is_ignored_under(root, path, config)?
+        && PathFilter::new(root, config)?.allows(path))
+}
+
+/// The leading part of a glob pattern that contains no wildcards,
+/// e.g. `src` for `src/**/*.rs` or `.` for `*.txt`. Returns `None` for literal paths.
+pub(crate) fn glob_root(pattern: &str) -> Option<std::path::PathBuf> {
+    const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+    if !pattern.contains(GLOB_CHARS) {
+        return None;
+    }
+
+    let mut root = std::path::PathBuf::new();
+    for component in Path::new(pattern).components() {
+        if component.as_os_str().to_string_lossy().contains(GLOB_CHARS) {
+            break;
+        }
+        root.push(component);
+    }
+
+    if root.as_os_str().is_empty() {
+        Some(std::path::PathBuf::from("."))
+    } else {
+        Some(root)
+    }
+}
+
+/// A `.git` directory, never walked even with `--hidden`
+fn is_git_dir(entry: &ignore::DirEntry) -> bool {
+    entry.file_name() == ".git" && entry.file_type().is_some_and(|ft| ft.is_dir())
+}
+
+/// Walk a directory and return the relative paths of every non-ignored subdirectory.
+/// Used to show directories in the tree even when they contain no serialized files.
+pub fn collect_directories(base_path: &Path, config: &YekConfig) -> Result<Vec<String>> {
+    if !base_path.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let gitignore = build_gitignore(base_path, config)?;
+    let excludes = build_glob_matcher(base_path, &config.exclude)?;
+    let filter = build_glob_matcher(base_path, &config.filter)?;
+    let export_ignore = ExportIgnore::new(base_path, config);
+    let mut walk_builder = ignore::WalkBuilder::new(base_path);
+    if !config.no_yekignore && config.uses_ignore_rules() {
+        walk_builder.add_custom_ignore_filename(YEKIGNORE_FILE_NAME);
+    }
+    let walker = walk_builder
+        .follow_links(false)
+        .standard_filters(config.uses_ignore_rules())
+        .hidden(!config.walks_hidden())
+        .require_git(false)
+        .filter_entry(move |entry| {
+            if is_git_dir(entry) {
+                return false;
+            }
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+            if gitignore.matched(entry.path(), is_dir).is_ignore()
+                || excludes.matched(entry.path(), is_dir).is_ignore()
+            {
+                return false;
+            }
+            // Patterns like "node_modules/**" only match a directory's contents,
+            // so probe a hypothetical child to see if the whole directory is excluded
+            let probe = entry.path().join("yek-probe");
+            !(is_dir
+                && (gitignore.matched(&probe, false).is_ignore() || export_ignore.ignores(&probe)))
+        })
+        .build();
+
+    let mut dirs = Vec::new();
+    for entry in walker.flatten() {
+        if entry.depth() == 0 || !entry.file_type().is_some_and(|ft| ft.is_dir()) {
+            continue;
+        }
+        // Like the files, a directory is listed if --filter would keep a file directly in it.
+        // Its subdirectories are still walked, since they may match where it does not
+        if !filter_allows(&filter, &entry.path().join("yek-probe")) {
+            continue;
+        }
+        dirs.push(normalize_path(entry.path(), base_path));
+    }
+    Ok(dirs)
+}
+
 /// Create a relative, slash-normalized path
 pub fn normalize_path(path: &Path, base: &Path) -> String {
-    path.strip_prefix(base)
+    to_forward_slashes(
+        &path
+            .strip_prefix(base)
+            .unwrap_or(path)
+            .to_path_buf()
+            .to_slash()
+            .unwrap_or_default(),
+    )
+}
+
+/// A path as emitted in the output: every `\` becomes `/` and a Windows verbatim
+/// prefix (`\\?\`) is dropped, so output is identical across platforms
+pub fn to_forward_slashes(path: &str) -> String {
+    path.strip_prefix(r"\\?\")
         .unwrap_or(path)
-        .to_path_buf()
-        .to_slash()
-        .unwrap_or_default()
-        .to_string()
+        .replace('\\', "/")
 }
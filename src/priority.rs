@@ -1,7 +1,10 @@
 use git2;
 use regex;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
 use tracing::debug;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -24,6 +27,41 @@ pub fn get_file_priority(path: &str, rules: &[PriorityRule]) -> i32 {
     priority
 }
 
+/// An ordering weight from `--priority <glob>:<weight>`
+#[derive(Debug, Clone)]
+pub struct PriorityWeight {
+    pub pattern: glob::Pattern,
+    pub weight: i32,
+}
+
+/// Parse a `<glob>:<weight>` spec. The weight follows the last colon,
+/// so globs may themselves contain colons.
+pub fn parse_priority_weight(spec: &str) -> anyhow::Result<PriorityWeight> {
+    let (glob, weight) = spec.rsplit_once(':').ok_or_else(|| {
+        anyhow::anyhow!(
+            "priority: Invalid rule '{}': expected <glob>:<weight>",
+            spec
+        )
+    })?;
+    let weight = weight
+        .trim()
+        .parse::<i32>()
+        .map_err(|e| anyhow::anyhow!("priority: Invalid weight in '{}': {}", spec, e))?;
+    let pattern = glob::Pattern::new(glob.trim())
+        .map_err(|e| anyhow::anyhow!("priority: Invalid pattern in '{}': {}", spec, e))?;
+    Ok(PriorityWeight { pattern, weight })
+}
+
+/// Ordering weight of a file: the highest weight among matching rules, or 0 if none match
+pub fn get_file_weight(path: &str, weights: &[PriorityWeight]) -> i32 {
+    weights
+        .iter()
+        .filter(|w| w.pattern.matches(path))
+        .map(|w| w.weight)
+        .max()
+        .unwrap_or(0)
+}
+
 /// Rank-based approach to compute how "recent" each file is (0=oldest, 1=newest).
 /// Then scale it to a user-defined or default max boost.
 pub fn compute_recentness_boost(
@@ -152,3 +190,160 @@ pub fn get_recent_commit_times_git2(
 
     Some(commit_times)
 }
+
+/// The most recent commit that touched a file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LastCommit {
+    /// Commit time as a Unix timestamp
+    pub time: u64,
+    /// Author name
+    pub author: String,
+    /// Abbreviated commit hash
+    pub short_id: String,
+}
+
+/// The last commit that touched each of `paths` (relative to the repo workdir,
+/// `/`-separated). Walks history from HEAD, newest first, and stops once every path
+/// tracked in HEAD has been seen. Untracked paths are absent from the result.
+pub fn get_last_commits(repo: &git2::Repository, paths: &[String]) -> HashMap<String, LastCommit> {
+    let mut commits = HashMap::new();
+    let head_tree = match repo.head().and_then(|head| head.peel_to_tree()) {
+        Ok(tree) => tree,
+        Err(e) => {
+            debug!("Unable to read HEAD tree: {:?}", e);
+            return commits;
+        }
+    };
+    let mut pending: HashSet<&str> = paths
+        .iter()
+        .map(String::as_str)
+        .filter(|path| head_tree.get_path(Path::new(path)).is_ok())
+        .collect();
+    if pending.is_empty() {
+        return commits;
+    }
+
+    let mut revwalk = match repo.revwalk() {
+        Ok(revwalk) => revwalk,
+        Err(e) => {
+            debug!("Unable to get revwalk: {:?}", e);
+            return commits;
+        }
+    };
+    if revwalk.push_head().is_err() || revwalk.set_sorting(git2::Sort::TIME).is_err() {
+        return commits;
+    }
+
+    for oid in revwalk.flatten() {
+        let Ok(commit) = repo.find_commit(oid) else {
+            continue;
+        };
+        let Ok(tree) = commit.tree() else {
+            continue;
+        };
+        let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+        let Ok(diff) = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None) else {
+            continue;
+        };
+
+        let last_commit = LastCommit {
+            time: commit.time().seconds() as u64,
+            author: commit.author().name().unwrap_or_default().to_string(),
+            short_id: commit
+                .as_object()
+                .short_id()
+                .ok()
+                .and_then(|id| id.as_str().map(str::to_string))
+                .unwrap_or_default(),
+        };
+        for delta in diff.deltas() {
+            if let Some(path) = delta.new_file().path().and_then(|p| p.to_str()) {
+                if pending.remove(path) {
+                    commits.insert(path.to_string(), last_commit.clone());
+                }
+            }
+        }
+        if pending.is_empty() {
+            break;
+        }
+    }
+
+    commits
+}
+
+/// Paths (relative to the repo workdir, `/`-separated) that differ between `reference`
+/// and the working tree, like `git diff --name-only <ref>`. Deleted files are left out
+/// and renamed files are reported under their new path.
+pub fn get_changed_paths(
+    repo: &git2::Repository,
+    reference: &str,
+) -> anyhow::Result<HashSet<String>> {
+    let tree = repo
+        .revparse_single(reference)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|e| anyhow::anyhow!("since: Unknown git ref '{}': {}", reference, e.message()))?;
+    let mut diff = repo
+        .diff_tree_to_workdir_with_index(Some(&tree), None)
+        .map_err(|e| anyhow::anyhow!("since: Failed to diff against '{}': {}", reference, e))?;
+    let mut find = git2::DiffFindOptions::new();
+    find.renames(true);
+    diff.find_similar(Some(&mut find))
+        .map_err(|e| anyhow::anyhow!("since: Failed to detect renames: {}", e))?;
+
+    Ok(diff
+        .deltas()
+        .filter(|delta| delta.status() != git2::Delta::Deleted)
+        .filter_map(|delta| delta.new_file().path()?.to_str().map(str::to_string))
+        .collect())
+}
+
+/// The `--git-header` line describing `repo`:
+/// `# Repo: <origin-url> @ <branch> (<short-hash>, dirty)`. The URL is left out when
+/// there is no `origin` remote, a detached HEAD shows as `detached`, and a branch with
+/// no commits yet as `no commits`. Untracked files count as dirty; ignored ones do not.
+pub fn repo_header(repo: &git2::Repository) -> String {
+    let branch = repo
+        .find_reference("HEAD")
+        .ok()
+        .and_then(|head| head.symbolic_target().map(str::to_string))
+        .map(|target| {
+            target
+                .strip_prefix("refs/heads/")
+                .unwrap_or(&target)
+                .to_string()
+        })
+        .unwrap_or_else(|| "detached".to_string());
+    let commit = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .and_then(|commit| commit.as_object().short_id())
+        .ok()
+        .and_then(|id| id.as_str().map(str::to_string))
+        .unwrap_or_else(|| "no commits".to_string());
+
+    let mut options = git2::StatusOptions::new();
+    options
+        .include_untracked(true)
+        .include_ignored(false)
+        .exclude_submodules(true);
+    let dirty = match repo.statuses(Some(&mut options)) {
+        Ok(statuses) => !statuses.is_empty(),
+        Err(e) => {
+            debug!("Unable to read repository status: {:?}", e);
+            false
+        }
+    };
+
+    let origin = repo
+        .find_remote("origin")
+        .ok()
+        .and_then(|remote| remote.url().map(|url| format!("{} @ ", url)))
+        .unwrap_or_default();
+    format!(
+        "# Repo: {}{} ({}{})",
+        origin,
+        branch,
+        commit,
+        if dirty { ", dirty" } else { "" }
+    )
+}
@@ -0,0 +1,92 @@
+use std::{
+    io::IsTerminal,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use bytesize::ByteSize;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::config::YekConfig;
+
+/// Progress for the walk and read phases, drawn on stderr so stdout output is untouched.
+/// Hidden with `--quiet` or when stderr is not a terminal; the counters run either way.
+pub struct ScanProgress {
+    bar: Option<ProgressBar>,
+    found: AtomicUsize,
+    read: AtomicUsize,
+    bytes: AtomicU64,
+}
+
+impl ScanProgress {
+    /// A spinner on stderr, unless `--quiet` is set or stderr is not a terminal
+    pub fn new(config: &YekConfig) -> Self {
+        if config.quiet || !std::io::stderr().is_terminal() {
+            return Self::hidden();
+        }
+        let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+        bar.set_style(
+            ProgressStyle::with_template("{spinner} {msg} [{elapsed}]")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        );
+        bar.set_message("Scanning");
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Self {
+            bar: Some(bar),
+            ..Self::hidden()
+        }
+    }
+
+    /// Counts without drawing anything
+    pub fn hidden() -> Self {
+        Self {
+            bar: None,
+            found: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// The walker found a file to read. Returns the number found so far, across inputs.
+    pub fn found_file(&self) -> usize {
+        let found = self.found.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(bar) = &self.bar {
+            bar.set_message(format!("Scanning: {} files found", found));
+        }
+        found
+    }
+
+    /// A file of `bytes` bytes was read
+    pub fn read_file(&self, bytes: u64) {
+        let read = self.read.fetch_add(1, Ordering::Relaxed) + 1;
+        let total = self.bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if let Some(bar) = &self.bar {
+            bar.set_message(format!(
+                "Reading: {}/{} files ({})",
+                read,
+                self.found.load(Ordering::Relaxed).max(read),
+                ByteSize::b(total)
+            ));
+        }
+    }
+
+    /// Whether the indicator is drawn
+    pub fn is_visible(&self) -> bool {
+        self.bar.is_some()
+    }
+
+    /// Files read so far and their total size in bytes
+    pub fn totals(&self) -> (usize, u64) {
+        (
+            self.read.load(Ordering::Relaxed),
+            self.bytes.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Remove the indicator, leaving stderr as it was
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
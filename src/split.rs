@@ -0,0 +1,318 @@
+use crate::{
+    atomic::AtomicFile, config::YekConfig, count_config_tokens, display_path, file_json,
+    file_language, format_file, group_header, output_capacity, output_order,
+    parallel::ProcessedFile, template_mtime, template_tokens, tree::generate_tree_with_options,
+    tree_entries, tree_options, TemplateVars,
+};
+use anyhow::{anyhow, Result};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// One serialized chunk ready to be written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub content: String,
+    /// Number of entries in this chunk (a file split into parts counts once per part)
+    pub entry_count: usize,
+}
+
+/// A single file (or one part of an oversized file) to be placed in a chunk.
+/// `rendered` is the text exactly as it appears in the chunk, so its size is what is written
+struct Entry {
+    rendered: String,
+    size: usize,
+}
+
+/// Split the serialized output into chunks that each fit within `--max-size` / `--tokens`.
+/// Files are kept whole unless a single file is larger than the limit on its own,
+/// in which case it is split on line boundaries into numbered parts.
+pub fn split_into_chunks(files: &[ProcessedFile], config: &YekConfig) -> Result<Vec<Chunk>> {
+    let cap = output_capacity(config)?;
+    let measure = |text: &str| {
+        if config.token_mode {
+            count_config_tokens(text, config)
+        } else {
+            text.len()
+        }
+    };
+
+    let tree_header = if config.tree_header && config.tree_matches_output {
+        let emitted = output_order(files, config);
+        generate_tree_with_options(&tree_entries(emitted, config)?, &tree_options(config))
+    } else if config.tree_header {
+        generate_tree_with_options(&tree_entries(files, config)?, &tree_options(config))
+    } else {
+        String::new()
+    };
+    let tree_header_size = measure(&tree_header);
+    // What joins entries, and what surrounds them: JSON chunks are arrays of the entries
+    let (separator_size, frame_size) = if config.json {
+        (
+            measure(JSON_SEPARATOR),
+            measure(JSON_OPEN) + measure(JSON_CLOSE),
+        )
+    } else {
+        (measure("\n"), 0)
+    };
+    // Budget per entry: every chunk may need to hold the header when it is repeated
+    let entry_cap = if config.split_repeat_tree {
+        cap.saturating_sub(tree_header_size + frame_size)
+    } else {
+        cap.saturating_sub(frame_size)
+    };
+
+    // Same order as the single-output mode
+    let sorted_files = output_order(files, config);
+
+    // FILE_INDEX / FILE_COUNT number files across all chunks
+    let count = sorted_files.len();
+    let mut entries = Vec::new();
+    for (i, file) in sorted_files.iter().enumerate() {
+        let position = (i + 1, count);
+        // A --group-by header stays attached to the first file of its group
+        let header = group_header(file, i.checked_sub(1).map(|p| sorted_files[p]), config)
+            .unwrap_or_default();
+        let rendered = header.clone()
+            + &render_entry(
+                file,
+                &display_path(file, config),
+                &file.content,
+                position,
+                config,
+            )?;
+        let size = measure(&rendered);
+        if size <= entry_cap {
+            entries.push(Entry { rendered, size });
+        } else {
+            let mut parts = split_oversized(file, position, entry_cap, config, &measure)?;
+            if let Some(first) = parts.first_mut().filter(|_| !header.is_empty()) {
+                first.rendered.insert_str(0, &header);
+                first.size = measure(&first.rendered);
+            }
+            entries.extend(parts);
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut current: Vec<Entry> = Vec::new();
+    let mut current_size = tree_header_size + frame_size;
+    for entry in entries {
+        // The separator joining entries counts toward the limit too
+        let separator = if current.is_empty() {
+            0
+        } else {
+            separator_size
+        };
+        // A chunk holding only a repeated header is not worth flushing on its own
+        let has_content =
+            !current.is_empty() || (current_size > frame_size && !config.split_repeat_tree);
+        if has_content && current_size + separator + entry.size > cap {
+            chunks.push(assemble_chunk(
+                std::mem::take(&mut current),
+                &tree_header,
+                chunks.is_empty() || config.split_repeat_tree,
+                config,
+            )?);
+            current_size = frame_size
+                + if config.split_repeat_tree {
+                    tree_header_size
+                } else {
+                    0
+                };
+        }
+        if !current.is_empty() {
+            current_size += separator_size;
+        }
+        current_size += entry.size;
+        current.push(entry);
+    }
+    if !current.is_empty() || chunks.is_empty() {
+        chunks.push(assemble_chunk(
+            current,
+            &tree_header,
+            chunks.is_empty() || config.split_repeat_tree,
+            config,
+        )?);
+    }
+
+    Ok(chunks)
+}
+
+/// Write chunks to `dir` as `output-001.txt`, `output-002.txt`, ... and return their paths
+pub fn write_chunks(dir: &Path, chunks: &[Chunk], extension: &str) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)
+        .map_err(|e| anyhow!("split_output: cannot create '{}': {}", dir.display(), e))?;
+
+    let mut paths = Vec::with_capacity(chunks.len());
+    for (i, chunk) in chunks.iter().enumerate() {
+        let path = dir.join(format!("output-{:03}.{}", i + 1, extension));
+        // Each chunk replaces its previous version only once it is fully written
+        let mut file = AtomicFile::create(&path)?;
+        file.write_all(chunk.content.as_bytes())?;
+        file.commit()?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// How a JSON chunk is put together, matching `serde_json`'s pretty-printed array
+const JSON_OPEN: &str = "[\n";
+const JSON_SEPARATOR: &str = ",\n";
+const JSON_CLOSE: &str = "\n]";
+
+/// Render a file the way it appears in the chunk: the template, or a JSON object indented
+/// as an element of the chunk's array. `position` is the file's 1-based index and the
+/// total file count.
+fn render_entry(
+    file: &ProcessedFile,
+    path: &str,
+    content: &str,
+    (index, count): (usize, usize),
+    config: &YekConfig,
+) -> Result<String> {
+    if config.json {
+        let object = serde_json::to_string_pretty(&file_json(file, path, content, config))
+            .map_err(|e| anyhow!("Failed to serialize JSON: {}", e))?;
+        // Strings are escaped onto one line, so every line is part of the structure
+        Ok(object
+            .lines()
+            .map(|line| format!("  {}", line))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    } else {
+        Ok(format_file(
+            &TemplateVars {
+                path,
+                content,
+                index,
+                count,
+                lang: &file_language(&file.rel_path, config),
+                commit: file.last_commit.as_ref(),
+                size: content.len(),
+                tokens: template_tokens(file, content, config),
+                mtime: template_mtime(file, config),
+            },
+            config,
+        ))
+    }
+}
+
+/// Break a file that exceeds the limit into parts, each rendered as `path (part i/n)`.
+/// A single line longer than the limit still becomes its own (oversized) part.
+///
+/// Each line is measured once and parts are filled from a running total, plus the size of
+/// an empty part with a worst-case label. Placeholders such as FILE_SIZE and the tokenizer
+/// make that an estimate, so each part is then checked once rendered, and lines that do
+/// not fit after all move on to the next part.
+fn split_oversized(
+    file: &ProcessedFile,
+    position: (usize, usize),
+    cap: usize,
+    config: &YekConfig,
+    measure: &dyn Fn(&str) -> usize,
+) -> Result<Vec<Entry>> {
+    // Measure with a worst-case label so the final labels never push a part over the limit
+    let path = display_path(file, config);
+    let probe_path = format!("{} (part 999/999)", path);
+    let overhead = measure(&render_entry(file, &probe_path, "", position, config)?);
+    let line_size = |line: &str| {
+        if config.json {
+            // As it appears inside the JSON string, without the quotes
+            let escaped = serde_json::to_string(line).expect("a string always serializes");
+            measure(&escaped[1..escaped.len() - 1])
+        } else {
+            measure(line)
+        }
+    };
+
+    let mut parts: Vec<Vec<&str>> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut current_size = overhead;
+    for line in file.content.split_inclusive('\n') {
+        let size = line_size(line);
+        if !current.is_empty() && current_size + size > cap {
+            parts.push(std::mem::take(&mut current));
+            current_size = overhead;
+        }
+        current.push(line);
+        current_size += size;
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+
+    let mut i = 0;
+    while i < parts.len() {
+        while parts[i].len() > 1
+            && measure(&render_entry(
+                file,
+                &probe_path,
+                &parts[i].concat(),
+                position,
+                config,
+            )?) > cap
+        {
+            let line = parts[i].pop().expect("part has more than one line");
+            match parts.get_mut(i + 1) {
+                Some(next) => next.insert(0, line),
+                None => parts.push(vec![line]),
+            }
+        }
+        i += 1;
+    }
+    let parts: Vec<String> = parts.into_iter().map(|lines| lines.concat()).collect();
+
+    let total = parts.len();
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let label = format!("{} (part {}/{})", path, i + 1, total);
+            let rendered = render_entry(file, &label, part, position, config)?;
+            let size = measure(&rendered);
+            Ok(Entry { rendered, size })
+        })
+        .collect()
+}
+
+/// Join entries into the final chunk text, optionally prefixed with the tree header
+fn assemble_chunk(
+    entries: Vec<Entry>,
+    tree_header: &str,
+    include_tree: bool,
+    config: &YekConfig,
+) -> Result<Chunk> {
+    let entry_count = entries.len();
+    let body = if config.json {
+        if entries.is_empty() {
+            "[]".to_string()
+        } else {
+            let elements: Vec<String> = entries.into_iter().map(|e| e.rendered).collect();
+            format!(
+                "{}{}{}",
+                JSON_OPEN,
+                elements.join(JSON_SEPARATOR),
+                JSON_CLOSE
+            )
+        }
+    } else {
+        entries
+            .into_iter()
+            .map(|e| e.rendered)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let content = if include_tree {
+        format!("{tree_header}{body}")
+    } else {
+        body
+    };
+    Ok(Chunk {
+        content,
+        entry_count,
+    })
+}
@@ -0,0 +1,379 @@
+use std::path::Path;
+
+/// How comments and string literals look in a language, for `--strip-comments`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommentSyntax {
+    /// Markers that comment out the rest of the line
+    pub line: &'static [&'static str],
+    /// Opening and closing markers of block comments
+    pub block: Option<(&'static str, &'static str)>,
+    /// Characters that delimit string literals, which may contain comment markers
+    pub quotes: &'static [char],
+    /// `'` starts a character literal such as `'x'` or `'\n'`, and is plain text where it
+    /// does not (Rust lifetimes, apostrophes)
+    pub char_literals: bool,
+    /// Python-style `"""` and `'''` strings, which may span lines
+    pub triple_quotes: bool,
+    /// A line comment marker only counts at the start of a line or after whitespace, as
+    /// with `#` in shell, where `$#` and `${#var}` are not comments
+    pub marker_after_space: bool,
+    /// Backslash escapes apply inside `'` strings too (not in shell or YAML)
+    pub single_quote_escapes: bool,
+    /// Raw strings whose quotes are set apart by delimiters: Rust's `r#"…"#` and C++'s
+    /// `R"(…)"`
+    pub raw_strings: bool,
+}
+
+const C_LIKE: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    char_literals: true,
+    triple_quotes: false,
+    marker_after_space: false,
+    single_quote_escapes: true,
+    raw_strings: true,
+};
+
+/// JavaScript and friends: `'` strings rather than characters, and template literals
+const JS_LIKE: CommentSyntax = CommentSyntax {
+    quotes: &['"', '\'', '`'],
+    char_literals: false,
+    raw_strings: false,
+    ..C_LIKE
+};
+
+/// Go: runes in `'`, raw strings in backticks
+const GO: CommentSyntax = CommentSyntax {
+    quotes: &['"', '\'', '`'],
+    raw_strings: false,
+    ..C_LIKE
+};
+
+const CSS: CommentSyntax = CommentSyntax {
+    line: &[],
+    char_literals: false,
+    raw_strings: false,
+    ..C_LIKE
+};
+
+/// Sass and Less: `//` comments, but not in unquoted URLs such as `url(http://x)`
+const SCSS: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    marker_after_space: true,
+    ..CSS
+};
+
+const HASH: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: None,
+    quotes: &['"', '\''],
+    char_literals: false,
+    triple_quotes: false,
+    marker_after_space: true,
+    single_quote_escapes: false,
+    raw_strings: false,
+};
+
+const PYTHON: CommentSyntax = CommentSyntax {
+    triple_quotes: true,
+    single_quote_escapes: true,
+    ..HASH
+};
+
+const RUBY: CommentSyntax = CommentSyntax {
+    single_quote_escapes: true,
+    ..HASH
+};
+
+const SQL: CommentSyntax = CommentSyntax {
+    line: &["--"],
+    block: Some(("/*", "*/")),
+    quotes: &['\''],
+    char_literals: false,
+    triple_quotes: false,
+    marker_after_space: false,
+    single_quote_escapes: false,
+    raw_strings: false,
+};
+
+const LUA: CommentSyntax = CommentSyntax {
+    line: &["--"],
+    block: Some(("--[[", "]]")),
+    quotes: &['"', '\''],
+    single_quote_escapes: true,
+    ..SQL
+};
+
+const HASKELL: CommentSyntax = CommentSyntax {
+    line: &["--"],
+    block: Some(("{-", "-}")),
+    quotes: &['"', '\''],
+    char_literals: true,
+    single_quote_escapes: true,
+    ..SQL
+};
+
+/// Markup: quotes in text are prose, not strings
+const MARKUP: CommentSyntax = CommentSyntax {
+    line: &[],
+    block: Some(("<!--", "-->")),
+    quotes: &[],
+    ..SQL
+};
+
+/// The comment syntax for a file, by extension (or name, for files such as `Makefile`).
+/// `None` for languages `--strip-comments` does not know, which are left as they are.
+pub fn comment_syntax(path: &Path) -> Option<CommentSyntax> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    match name.as_str() {
+        "dockerfile" | "makefile" | "gnumakefile" | "cmakelists.txt" | ".gitignore"
+        | ".dockerignore" => return Some(HASH),
+        "gemfile" | "rakefile" => return Some(RUBY),
+        _ => {}
+    }
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    let syntax = match ext.as_str() {
+        "rs" | "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "m" | "mm" | "java" | "cs"
+        | "kt" | "kts" | "scala" | "swift" | "dart" | "groovy" | "gradle" | "proto" | "zig"
+        | "sol" | "glsl" | "hlsl" => C_LIKE,
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => JS_LIKE,
+        "scss" | "less" => SCSS,
+        "go" => GO,
+        "css" => CSS,
+        "sh" | "bash" | "zsh" | "fish" | "ksh" | "yaml" | "yml" | "toml" | "conf" | "cfg"
+        | "mk" | "cmake" | "r" | "pl" | "pm" | "tf" | "hcl" | "nix" | "ps1" | "ex" | "exs"
+        | "jl" | "nim" | "coffee" => HASH,
+        "py" | "pyi" | "pyw" => PYTHON,
+        "rb" | "rake" | "gemspec" => RUBY,
+        "sql" => SQL,
+        "lua" => LUA,
+        "hs" | "lhs" | "elm" => HASKELL,
+        "html" | "htm" | "xml" | "svg" | "vue" | "svelte" => MARKUP,
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// Builds the stripped text line by line, so lines left empty by a removed comment can
+/// be dropped while lines that were blank to begin with are kept
+struct Output {
+    text: String,
+    line: String,
+    /// Whether a comment was removed from the current line
+    stripped: bool,
+}
+
+impl Output {
+    fn push(&mut self, text: &str) {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.end_line();
+            }
+            self.line.push_str(part);
+        }
+    }
+
+    fn end_line(&mut self) {
+        let (body, cr) = match self.line.strip_suffix('\r') {
+            Some(body) => (body, "\r"),
+            None => (self.line.as_str(), ""),
+        };
+        if self.stripped {
+            let body = body.trim_end();
+            if !body.is_empty() {
+                self.text.push_str(body);
+                self.text.push_str(cr);
+                self.text.push('\n');
+            }
+        } else {
+            self.text.push_str(&self.line);
+            self.text.push('\n');
+        }
+        self.line.clear();
+        self.stripped = false;
+    }
+
+    fn finish(mut self) -> String {
+        if self.stripped {
+            let body = self.line.trim_end();
+            self.text.push_str(body);
+        } else {
+            self.text.push_str(&self.line);
+        }
+        self.text
+    }
+}
+
+/// Remove the comments from `content`. Comment markers inside string and character
+/// literals are left alone, as is a leading `#!` line. Lines that held nothing but a
+/// comment are removed entirely; code before a trailing comment keeps its line.
+pub fn strip_comments(content: &str, syntax: &CommentSyntax) -> String {
+    let mut out = Output {
+        text: String::with_capacity(content.len()),
+        line: String::new(),
+        stripped: false,
+    };
+    let mut i = 0;
+    if syntax.line.contains(&"#") && content.starts_with("#!") {
+        i = content.find('\n').unwrap_or(content.len());
+        out.push(&content[..i]);
+    }
+
+    while i < content.len() {
+        let rest = &content[i..];
+        let c = rest.chars().next().expect("not at the end");
+
+        if c == '\n' {
+            out.end_line();
+            i += 1;
+            continue;
+        }
+
+        if let Some((open, close)) = syntax.block {
+            if let Some(body) = rest.strip_prefix(open) {
+                let end = body
+                    .find(close)
+                    .map_or(rest.len(), |at| open.len() + at + close.len());
+                // Keep the line breaks, so the lines the comment covered can be dropped
+                for _ in rest[..end].matches('\n') {
+                    out.stripped = true;
+                    out.end_line();
+                }
+                out.stripped = true;
+                i += end;
+                continue;
+            }
+        }
+
+        let after_space = out.line.is_empty() || out.line.ends_with(char::is_whitespace);
+        if (after_space || !syntax.marker_after_space)
+            && syntax.line.iter().any(|marker| rest.starts_with(marker))
+        {
+            out.stripped = true;
+            // Stop short of a CRLF line ending, which stays with the line
+            let end = rest.find('\n').unwrap_or(rest.len());
+            i += rest[..end].strip_suffix('\r').map_or(end, str::len);
+            continue;
+        }
+
+        // A raw string's prefix starts a word, unlike the `r` in `bar"…"`
+        let word_start = !out
+            .line
+            .ends_with(|c: char| c.is_alphanumeric() || c == '_');
+        if syntax.raw_strings && word_start {
+            if let Some(len) = raw_string_len(rest) {
+                out.push(&rest[..len]);
+                i += len;
+                continue;
+            }
+        }
+
+        if syntax.triple_quotes && (rest.starts_with("\"\"\"") || rest.starts_with("'''")) {
+            let len = 3 + string_len(&rest[3..], &rest[..3], true, true);
+            out.push(&rest[..len]);
+            i += len;
+            continue;
+        }
+
+        if syntax.quotes.contains(&c) {
+            let len = if c == '\'' && syntax.char_literals {
+                char_literal_len(rest).unwrap_or(1)
+            } else {
+                let escapes = c != '\'' || syntax.single_quote_escapes;
+                // Only template literals and raw strings run on past the end of a line
+                let multiline = c == '`';
+                1 + string_len(&rest[1..], &rest[..1], escapes, multiline)
+            };
+            out.push(&rest[..len]);
+            i += len;
+            continue;
+        }
+
+        out.push(&rest[..c.len_utf8()]);
+        i += c.len_utf8();
+    }
+    out.finish()
+}
+
+/// Length of a string literal's body and closing quote in `rest`. An unterminated
+/// literal ends at the end of the line (or of the text, if it may span lines).
+fn string_len(rest: &str, quote: &str, escapes: bool, multiline: bool) -> usize {
+    let mut chars = rest.char_indices();
+    while let Some((at, c)) = chars.next() {
+        if escapes && c == '\\' {
+            chars.next();
+        } else if rest[at..].starts_with(quote) {
+            return at + quote.len();
+        } else if c == '\n' && !multiline {
+            return at;
+        }
+    }
+    rest.len()
+}
+
+/// Length of a raw string literal at the start of `rest`: Rust's `r"…"` and `r#"…"#` (also
+/// `br` and `cr`), or C++'s `R"delim(…)delim"` (also with an encoding prefix). `None` when
+/// `rest` does not open one; an unterminated literal runs to the end of the text.
+fn raw_string_len(rest: &str) -> Option<usize> {
+    let literal_len = |body_start: usize, close: &str| {
+        rest[body_start..]
+            .find(close)
+            .map_or(rest.len(), |at| body_start + at + close.len())
+    };
+
+    if let Some(prefix) = ["br", "cr", "r"].into_iter().find(|p| rest.starts_with(p)) {
+        let after = &rest[prefix.len()..];
+        let hashes = after.len() - after.trim_start_matches('#').len();
+        if after[hashes..].starts_with('"') {
+            let close = format!("\"{}", "#".repeat(hashes));
+            return Some(literal_len(prefix.len() + hashes + 1, &close));
+        }
+    }
+
+    let prefix = ["u8R", "uR", "UR", "LR", "R"]
+        .into_iter()
+        .find(|p| rest.starts_with(p))?;
+    let after = rest[prefix.len()..].strip_prefix('"')?;
+    let delimiter = &after[..after.find('(')?];
+    if delimiter.len() > 16
+        || delimiter.contains(|c: char| c.is_whitespace() || matches!(c, ')' | '\\' | '"'))
+    {
+        return None;
+    }
+    let close = format!("){}\"", delimiter);
+    Some(literal_len(prefix.len() + 1 + delimiter.len() + 1, &close))
+}
+
+/// Length of a character literal (`'x'`, `'\n'`, `'\u{1F600}'`) at the start of `rest`,
+/// or `None` when the quote does not open one
+fn char_literal_len(rest: &str) -> Option<usize> {
+    let body = &rest[1..];
+    let mut chars = body.char_indices();
+    let (_, first) = chars.next()?;
+    if first == '\\' {
+        // Escapes are short; give up at a line break or after a dozen characters
+        let end = body
+            .char_indices()
+            .skip(2)
+            .take(12)
+            .find_map(|(at, c)| match c {
+                '\'' => Some(Some(at)),
+                '\n' => Some(None),
+                _ => None,
+            })??;
+        return Some(1 + end + 1);
+    }
+    match chars.next() {
+        Some((at, '\'')) if first != '\'' && first != '\n' => Some(1 + at + 1),
+        _ => None,
+    }
+}
+
+/// Remove lines that are empty or only whitespace
+pub fn strip_blank_lines(content: &str) -> String {
+    content
+        .split_inclusive('\n')
+        .filter(|line| !line.trim().is_empty())
+        .collect()
+}
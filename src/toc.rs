@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+/// Heading above the `--toc` list of files
+pub const TOC_HEADING: &str = "Contents";
+
+/// The anchor GitHub generates for a heading: the text lowercased, with everything but
+/// letters, digits, spaces, `-` and `_` removed and spaces turned into `-`
+pub fn heading_anchor(text: &str) -> String {
+    text.trim()
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Anchors handed out so far in a document. As on GitHub, a heading whose anchor was
+/// already used gets `-1`, `-2`, ... appended, in document order.
+#[derive(Debug, Default)]
+pub struct Anchors {
+    seen: HashMap<String, usize>,
+}
+
+impl Anchors {
+    /// The anchor of the next heading with this text
+    pub fn next(&mut self, text: &str) -> String {
+        let base = heading_anchor(text);
+        let mut anchor = base.clone();
+        // A suffixed anchor may itself belong to an earlier heading, so keep counting
+        while self.seen.contains_key(&anchor) {
+            let count = self.seen.get_mut(&base).expect("base anchor was seen");
+            *count += 1;
+            anchor = format!("{}-{}", base, count);
+        }
+        self.seen.insert(anchor.clone(), 0);
+        anchor
+    }
+}
+
+/// Escape the characters that would turn a path into Markdown formatting inside link text
+pub fn escape_link_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '[' | ']' | '*' | '_' | '`' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The text of a Markdown ATX heading line (`## Title` gives `Title`), if it is one
+pub fn heading_text(line: &str) -> Option<&str> {
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    if !(1..=6).contains(&hashes) {
+        return None;
+    }
+    let rest = &line[hashes..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    // An optional closing run of '#' must follow a space
+    let text = rest.trim();
+    let unclosed = text.trim_end_matches('#');
+    if unclosed.is_empty() || unclosed.ends_with([' ', '\t']) {
+        return Some(unclosed.trim_end());
+    }
+    Some(text)
+}
@@ -0,0 +1,86 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Result};
+
+/// A `--transform <glob>:<command>` rule: matching files are piped through `command`
+#[derive(Debug, Clone)]
+pub struct TransformRule {
+    pub pattern: glob::Pattern,
+    pub command: String,
+}
+
+/// Parse a `<glob>:<command>` spec. The glob ends at the first colon, so commands may
+/// themselves contain colons.
+pub fn parse_transform_rule(spec: &str) -> Result<TransformRule> {
+    let (glob, command) = spec.split_once(':').ok_or_else(|| {
+        anyhow!(
+            "transform: Invalid rule '{}': expected <glob>:<command>",
+            spec
+        )
+    })?;
+    let command = command.trim();
+    if command.is_empty() {
+        return Err(anyhow!("transform: Empty command in '{}'", spec));
+    }
+    let pattern = glob::Pattern::new(glob.trim())
+        .map_err(|e| anyhow!("transform: Invalid pattern in '{}': {}", spec, e))?;
+    Ok(TransformRule {
+        pattern,
+        command: command.to_string(),
+    })
+}
+
+/// Run `command` through the shell (`sh -c`, or `cmd /C` on Windows) with `content` on
+/// its stdin, returning its stdout. The file's relative path is passed in the
+/// `YEK_FILE_PATH` environment variable, for tools that pick behavior by file name.
+/// A non-zero exit is an error carrying the command's stderr.
+pub fn run_transform(command: &str, path: &str, content: &str) -> Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut child = shell
+        .arg(command)
+        .env("YEK_FILE_PATH", path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("transform: cannot run '{}': {}", command, e))?;
+
+    // Feed stdin from another thread so a command that writes before reading everything
+    // cannot deadlock against us
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = content.to_string();
+    let writer = std::thread::spawn(move || {
+        // A command that exits without reading its input closes the pipe; that is its choice
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let output = child
+        .wait_with_output()
+        .map_err(|e| anyhow!("transform: cannot run '{}': {}", command, e))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "transform: '{}' failed on {} ({}){}",
+            command,
+            path,
+            output.status,
+            match stderr.trim() {
+                "" => String::new(),
+                message => format!(": {}", message),
+            }
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
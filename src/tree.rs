@@ -1,37 +1,564 @@
-use std::collections::HashMap;
+use crate::xml_escape;
+use bytesize::ByteSize;
+use std::collections::{HashMap, HashSet};
 use std::path::{Component, Path, PathBuf};
 
+/// Character set used to draw the tree
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeStyle {
+    /// Box-drawing characters (`├──`, `└──`, `│`)
+    #[default]
+    Unicode,
+    /// Plain ASCII connectors (`|--`, `` `-- ``, `|`)
+    Ascii,
+    /// Nested Markdown bullet list (`- src/`, `  - main.rs`)
+    Markdown,
+}
+
+/// Order of entries in the tree and, with `--sort`, of files in the output
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeSort {
+    /// Directories first, then files, both alphabetically
+    #[default]
+    Name,
+    /// Largest first (directories by the total of their contents), ties by name
+    Size,
+    /// Directories first, then files grouped by extension, ties by name
+    Ext,
+}
+
+/// Whether directories come before files in the tree. Given as `--tree-dirs-first
+/// true|false` on the command line and as a plain bool in the config file.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(from = "bool", into = "bool")]
+pub enum TreeDirsFirst {
+    /// Directories before files
+    #[default]
+    True,
+    /// Files before directories
+    False,
+}
+
+impl From<bool> for TreeDirsFirst {
+    fn from(dirs_first: bool) -> Self {
+        if dirs_first {
+            TreeDirsFirst::True
+        } else {
+            TreeDirsFirst::False
+        }
+    }
+}
+
+impl From<TreeDirsFirst> for bool {
+    fn from(dirs_first: TreeDirsFirst) -> Self {
+        dirs_first == TreeDirsFirst::True
+    }
+}
+
+/// Whether directories and files are kept apart in the tree (sorting by size ignores this)
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum TreeOrder {
+    /// Directories and files listed separately (see --tree-dirs-first), each alphabetically
+    #[default]
+    Alpha,
+    /// Directories and files mixed in one alphabetical list
+    AlphaInterleaved,
+}
+
+/// How names are compared when sorting the tree
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeSortCase {
+    /// Byte order, so uppercase names come before all lowercase ones
+    #[default]
+    Sensitive,
+    /// Ignore case, like most file explorers; names that differ only in case keep byte order
+    Insensitive,
+}
+
+impl TreeSortCase {
+    /// Compare two names in this case mode
+    pub fn compare(self, a: &str, b: &str) -> std::cmp::Ordering {
+        match self {
+            TreeSortCase::Sensitive => a.cmp(b),
+            TreeSortCase::Insensitive => a
+                .to_lowercase()
+                .cmp(&b.to_lowercase())
+                .then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+/// Custom strings for drawing the tree (`tree_connectors` in a config file). Each one
+/// left unset comes from the tree style and indent.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TreeConnectors {
+    /// Before an entry with more siblings below it (`├── `)
+    pub branch: Option<String>,
+    /// Before the last entry of a directory (`└── `)
+    pub last: Option<String>,
+    /// Continues the line of an ancestor with more entries below (`│   `)
+    pub vertical: Option<String>,
+    /// Stands in for an ancestor that was the last entry (`    `)
+    pub blank: Option<String>,
+}
+
+/// The strings used to draw one level of the tree
+struct Connectors {
+    branch: String,
+    last: String,
+    vertical: String,
+    blank: String,
+    ellipsis: &'static str,
+}
+
+impl Connectors {
+    fn new(options: &TreeOptions) -> Self {
+        let mut connectors = options.style.connectors(options.indent);
+        if let Some(custom) = &options.connectors {
+            for (string, custom) in [
+                (&mut connectors.branch, &custom.branch),
+                (&mut connectors.last, &custom.last),
+                (&mut connectors.vertical, &custom.vertical),
+                (&mut connectors.blank, &custom.blank),
+            ] {
+                if let Some(custom) = custom {
+                    string.clone_from(custom);
+                }
+            }
+        }
+        connectors
+    }
+}
+
+impl TreeStyle {
+    /// Columns per tree level unless `--tree-indent` says otherwise
+    pub fn default_indent(self) -> usize {
+        match self {
+            TreeStyle::Unicode | TreeStyle::Ascii => 4,
+            TreeStyle::Markdown => 2,
+        }
+    }
+
+    /// The connectors of this style, `indent` columns wide (at least 2)
+    fn connectors(self, indent: Option<usize>) -> Connectors {
+        let indent = indent.unwrap_or(self.default_indent()).max(2);
+        // A corner or tee, a horizontal line out to the name, then a space
+        let line = |corner: &str, horizontal: &str| {
+            format!("{}{} ", corner, horizontal.repeat(indent - 2))
+        };
+        let pad = |first: &str| format!("{}{}", first, " ".repeat(indent - 1));
+        match self {
+            TreeStyle::Unicode => Connectors {
+                branch: line("├", "─"),
+                last: line("└", "─"),
+                vertical: pad("│"),
+                blank: pad(" "),
+                ellipsis: "…",
+            },
+            TreeStyle::Ascii => Connectors {
+                branch: line("|", "-"),
+                last: line("`", "-"),
+                vertical: pad("|"),
+                blank: pad(" "),
+                ellipsis: "...",
+            },
+            // Bullets stay as they are; only the nesting is indented
+            TreeStyle::Markdown => Connectors {
+                branch: "- ".to_string(),
+                last: "- ".to_string(),
+                vertical: pad(" "),
+                blank: pad(" "),
+                ellipsis: "…",
+            },
+        }
+    }
+}
+
+/// Where an entry of a [`generate_diff_tree`] tree is present
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeChange {
+    /// In both trees
+    Unchanged,
+    /// Only in the new tree, marked `+`
+    Added,
+    /// Only in the old tree, marked `-`
+    Removed,
+}
+
+impl TreeChange {
+    /// The two-column gutter drawn before the entry's line
+    fn gutter(self) -> &'static str {
+        match self {
+            TreeChange::Unchanged => "  ",
+            TreeChange::Added => "+ ",
+            TreeChange::Removed => "- ",
+        }
+    }
+}
+
+/// Options controlling how the directory tree is rendered.
+#[derive(Debug, Clone, Default)]
+pub struct TreeOptions {
+    /// Annotate each entry with its size (directories show the sum of their descendants)
+    pub show_sizes: bool,
+    /// Annotate each directory with the number of files and subdirectories below it
+    pub show_counts: bool,
+    /// Stop descending below this many directory levels (0 = top-level entries only)
+    pub max_depth: Option<usize>,
+    /// Character set used for the tree connectors
+    pub style: TreeStyle,
+    /// Merge chains of directories that each hold a single directory into one
+    /// node, e.g. `src/main/java/`
+    pub collapse: bool,
+    /// Order of siblings in the tree
+    pub sort: TreeSort,
+    /// List files before directories instead of after them
+    pub files_first: bool,
+    /// Keep directories and files apart, or interleave them
+    pub order: TreeOrder,
+    /// Whether names are compared case-sensitively
+    pub sort_case: TreeSortCase,
+    /// Line printed above the tree instead of "Directory structure:" (empty = no line)
+    pub header_text: Option<String>,
+    /// Line printed below the tree, before the blank line that ends it (empty = no line)
+    pub footer_text: Option<String>,
+    /// Escape `&`, `<` and `>` in entry names, for trees embedded in XML
+    pub escape_xml: bool,
+    /// Appended to directory names instead of "/" (empty = nothing)
+    pub dir_marker: Option<String>,
+    /// Name of a root directory drawn above the top-level entries (none by default).
+    /// In JSON trees it names the root object instead of "."
+    pub root_name: Option<String>,
+    /// Color entry names with ANSI escapes: directories bold blue, symlinks cyan
+    pub color: bool,
+    /// Columns per level (default: [`TreeStyle::default_indent`]; at least 2)
+    pub indent: Option<usize>,
+    /// Custom connector strings, overriding those of `style` and `indent`
+    pub connectors: Option<TreeConnectors>,
+}
+
+/// Directory levels nested in JSON trees. Each level is an object inside a `children`
+/// array, so this keeps documents within the 128 nesting levels that serde_json and many
+/// other parsers accept by default, and keeps building and dropping them off the call stack.
+pub const MAX_JSON_TREE_DEPTH: usize = 60;
+
+/// The label [`generate_tree_with_options`] puts above the tree unless told otherwise
+pub const DEFAULT_TREE_HEADER_TEXT: &str = "Directory structure:";
+
+/// ANSI escapes used with [`TreeOptions::color`]
+const DIR_COLOR: &str = "\x1b[1;34m";
+const LINK_COLOR: &str = "\x1b[36m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+/// `label` wrapped in `color` and a reset
+fn paint(label: &str, color: &str) -> String {
+    format!("{}{}{}", color, label, RESET_COLOR)
+}
+
+/// A single path to place in the tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeEntry {
+    pub path: PathBuf,
+    /// Byte size of the file, if known
+    pub size: Option<u64>,
+    /// Whether the final path component is a file (otherwise a directory)
+    pub is_file: bool,
+    /// Target of a symlink that was not followed, shown as `name -> target`
+    pub link_target: Option<String>,
+    /// Left out of the output by the size budget, shown as `name (dropped)`
+    pub dropped: bool,
+}
+
+impl TreeEntry {
+    /// A file entry with an optional byte size
+    pub fn file(path: impl Into<PathBuf>, size: Option<u64>) -> Self {
+        TreeEntry {
+            path: path.into(),
+            size,
+            is_file: true,
+            link_target: None,
+            dropped: false,
+        }
+    }
+
+    /// A directory entry, rendered even if it has no children
+    pub fn dir(path: impl Into<PathBuf>) -> Self {
+        TreeEntry {
+            path: path.into(),
+            size: None,
+            is_file: false,
+            link_target: None,
+            dropped: false,
+        }
+    }
+
+    /// A symlink that was not followed, rendered as `name -> target`
+    pub fn link(path: impl Into<PathBuf>, target: impl Into<String>) -> Self {
+        TreeEntry {
+            path: path.into(),
+            size: None,
+            is_file: true,
+            link_target: Some(target.into()),
+            dropped: false,
+        }
+    }
+
+    /// The same entry, marked as left out by the size budget
+    pub fn dropped(self) -> Self {
+        TreeEntry {
+            dropped: true,
+            ..self
+        }
+    }
+}
+
 /// Generate a directory tree from a list of file paths
 pub fn generate_tree(paths: &[PathBuf]) -> String {
-    if paths.is_empty() {
+    let entries: Vec<TreeEntry> = paths.iter().map(|p| TreeEntry::file(p, None)).collect();
+    generate_tree_with_options(&entries, &TreeOptions::default())
+}
+
+/// Generate a directory tree from a list of entries (files with optional sizes, or directories).
+pub fn generate_tree_with_options(entries: &[TreeEntry], options: &TreeOptions) -> String {
+    if entries.is_empty() {
         return String::new();
     }
 
-    // Pre-allocate string with estimated capacity
-    let total_path_len: usize = paths.iter().map(|p| p.to_string_lossy().len()).sum();
-    let mut output = String::with_capacity(total_path_len + paths.len() * 8);
-
     // Build a tree structure from the paths
-    let mut tree = TreeNode::new();
+    let mut builder = TreeBuilder::new();
+    for entry in entries {
+        builder.add_entry(entry);
+    }
+    tree_document(&builder.build(), options)
+}
 
-    // Add all paths to the tree
-    for path in paths {
-        add_path_to_tree(&mut tree, path);
+/// Generate a tree of everything in `old` and `new`, with a gutter before each line:
+/// `-` for entries only in `old`, `+` for entries only in `new`, blank for the rest.
+/// A directory on one side only is marked along with everything in it.
+pub fn generate_diff_tree(old: &[TreeEntry], new: &[TreeEntry], options: &TreeOptions) -> String {
+    if old.is_empty() && new.is_empty() {
+        return String::new();
     }
+    tree_document(&build_diff_tree(old, new), options)
+}
+
+/// Build the tree of `old` and `new` combined, each node marked with its [`TreeChange`].
+/// Files keep their size from `new` when they are on both sides.
+pub fn build_diff_tree(old: &[TreeEntry], new: &[TreeEntry]) -> TreeNode {
+    // Every path (and every directory leading to one) on each side
+    let prefixes = |entries: &[TreeEntry]| {
+        let mut prefixes = HashSet::new();
+        for entry in entries {
+            let components = clean_path_components(&entry.path);
+            for end in 1..=components.len() {
+                prefixes.insert(components[..end].to_vec());
+            }
+        }
+        prefixes
+    };
+    let (in_old, in_new) = (prefixes(old), prefixes(new));
+
+    let mut builder = TreeBuilder::new();
+    for entry in new.iter().chain(old) {
+        builder.add_entry(entry);
+    }
+    let mut root = builder.build();
+    root.change = Some(TreeChange::Unchanged);
 
-    // Generate the tree output
-    output.push_str("Directory structure:\n");
-    render_tree(&tree, &mut output, "", true);
+    let mut pending: Vec<(&mut TreeNode, Vec<String>)> = root
+        .children
+        .values_mut()
+        .map(|child| {
+            let path = vec![child.name.clone()];
+            (child, path)
+        })
+        .collect();
+    while let Some((node, path)) = pending.pop() {
+        node.change = Some(match (in_old.contains(&path), in_new.contains(&path)) {
+            (true, false) => TreeChange::Removed,
+            (false, true) => TreeChange::Added,
+            _ => TreeChange::Unchanged,
+        });
+        for child in node.children.values_mut() {
+            let mut child_path = path.clone();
+            child_path.push(child.name.clone());
+            pending.push((child, child_path));
+        }
+    }
+    root
+}
+
+/// The rendered tree with its header line, root line and footer line, followed by a
+/// blank line
+fn tree_document(tree: &TreeNode, options: &TreeOptions) -> String {
+    let mut output = String::new();
+    let header = options
+        .header_text
+        .as_deref()
+        .unwrap_or(DEFAULT_TREE_HEADER_TEXT);
+    let mut root = match &options.root_name {
+        Some(name) => {
+            let marker = options.dir_marker.as_deref().unwrap_or("/");
+            let label = format!("{}{}", name, marker);
+            let mut line = if options.color {
+                paint(&label, DIR_COLOR)
+            } else {
+                label
+            };
+            if let Some(annotation) = tree.annotation(options) {
+                line.push_str(&format!(" ({})", annotation));
+            }
+            line
+        }
+        None => String::new(),
+    };
+    if !root.is_empty() {
+        if let Some(change) = tree.change {
+            root.insert_str(0, change.gutter());
+        }
+    }
+    let mut body = tree.render(options);
+    if options.escape_xml {
+        root = xml_escape(&root).into_owned();
+        body = xml_escape(&body).into_owned();
+    }
+    let footer = options.footer_text.as_deref().unwrap_or("");
+    for text in [header, &root, &body, footer] {
+        if !text.is_empty() {
+            output.push_str(text);
+            if !text.ends_with('\n') {
+                output.push('\n');
+            }
+        }
+    }
     output.push('\n'); // Add blank line after tree
 
     output
 }
 
+/// Paths of the files in the tree built from `entries`, in the order the tree lists them:
+/// depth first, with siblings ordered by `options` (`max_depth` is ignored). Each path is
+/// its cleaned components joined with '/'.
+pub fn tree_file_order(entries: &[TreeEntry], options: &TreeOptions) -> Vec<String> {
+    let mut builder = TreeBuilder::new();
+    for entry in entries {
+        builder.add_entry(entry);
+    }
+    builder
+        .build()
+        .file_paths(options)
+        .iter()
+        .map(|path| {
+            path.iter()
+                .map(|name| name.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .collect()
+}
+
+/// Build the directory tree as nested JSON objects with `name`, `is_file` and
+/// `children` fields. Sizes are included when `options.show_sizes` is set.
+pub fn generate_tree_json(entries: &[TreeEntry], options: &TreeOptions) -> serde_json::Value {
+    let mut builder = TreeBuilder::with_root_name(options.root_name.as_deref().unwrap_or("."));
+    for entry in entries {
+        builder.add_entry(entry);
+    }
+    builder.build().to_json(options)
+}
+
+/// Build a tree from `(path, is_file)` pairs.
+///
+/// Intermediate components always become directories; the final component is a
+/// file or directory according to the flag.
+pub fn build_tree<I, P>(paths: I) -> TreeNode
+where
+    I: IntoIterator<Item = (P, bool)>,
+    P: AsRef<Path>,
+{
+    let mut builder = TreeBuilder::new();
+    for (path, is_file) in paths {
+        builder.add_path(path.as_ref(), is_file);
+    }
+    builder.build()
+}
+
+/// Incrementally assembles a [`TreeNode`] hierarchy from paths.
 #[derive(Debug)]
-struct TreeNode {
+pub struct TreeBuilder {
+    root: TreeNode,
+}
+
+impl Default for TreeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TreeBuilder {
+    /// Start an empty tree with an unnamed root
+    pub fn new() -> Self {
+        TreeBuilder {
+            root: TreeNode::new(),
+        }
+    }
+
+    /// Start an empty tree whose root node carries `name`
+    pub fn with_root_name(name: impl Into<String>) -> Self {
+        TreeBuilder {
+            root: TreeNode::new_with_name(name.into(), false),
+        }
+    }
+
+    /// Add a path, treating its final component as a file or directory
+    pub fn add_path(&mut self, path: &Path, is_file: bool) -> &mut Self {
+        add_path_to_tree_with_type(&mut self.root, path, is_file, None);
+        self
+    }
+
+    /// Add an entry, keeping its size when it is a file
+    pub fn add_entry(&mut self, entry: &TreeEntry) -> &mut Self {
+        add_entry_to_tree(&mut self.root, entry);
+        self
+    }
+
+    /// Finish building: directory sizes and counts are aggregated from their descendants
+    pub fn build(mut self) -> TreeNode {
+        aggregate_totals(&mut self.root);
+        self.root
+    }
+}
+
+/// A file or directory in the tree. The root node is an unnamed directory
+/// unless built with [`TreeBuilder::with_root_name`].
+#[derive(Debug, Clone)]
+pub struct TreeNode {
     name: String,
     children: HashMap<String, TreeNode>,
     is_file: bool,
+    size: Option<u64>,
+    /// Files (including unfollowed symlinks) and directories below a directory
+    file_count: usize,
+    dir_count: usize,
+    link_target: Option<String>,
+    dropped: bool,
+    /// Set on every node of a tree built by [`build_diff_tree`]
+    change: Option<TreeChange>,
 }
 
 impl TreeNode {
@@ -40,6 +567,12 @@ impl TreeNode {
             name: String::new(),
             children: HashMap::new(),
             is_file: false,
+            size: None,
+            file_count: 0,
+            dir_count: 0,
+            link_target: None,
+            dropped: false,
+            change: None,
         }
     }
 
@@ -48,6 +581,285 @@ impl TreeNode {
             name,
             children: HashMap::new(),
             is_file,
+            size: None,
+            file_count: 0,
+            dir_count: 0,
+            link_target: None,
+            dropped: false,
+            change: None,
+        }
+    }
+
+    /// The last path component this node represents
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether this node is a file (otherwise a directory)
+    pub fn is_file(&self) -> bool {
+        self.is_file
+    }
+
+    /// File size, or the total of all sized descendants for a directory
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Number of files below this directory, at any depth (0 for a file)
+    pub fn file_count(&self) -> usize {
+        self.file_count
+    }
+
+    /// Number of directories below this directory, at any depth (0 for a file)
+    pub fn dir_count(&self) -> usize {
+        self.dir_count
+    }
+
+    /// Where this node points, if it is a symlink that was not followed
+    pub fn link_target(&self) -> Option<&str> {
+        self.link_target.as_deref()
+    }
+
+    /// Whether this file was left out of the output by the size budget
+    pub fn is_dropped(&self) -> bool {
+        self.dropped
+    }
+
+    /// Whether this node is only in the old or new tree of a [`build_diff_tree`] tree
+    /// (`None` in ordinary trees)
+    pub fn change(&self) -> Option<TreeChange> {
+        self.change
+    }
+
+    /// Look up a direct child by name
+    pub fn child(&self, name: &str) -> Option<&TreeNode> {
+        self.children.get(name)
+    }
+
+    /// Direct children in display order: directories first, then files, both alphabetically
+    pub fn children(&self) -> Vec<&TreeNode> {
+        sorted_children(self, &TreeOptions::default())
+    }
+
+    /// Number of entries (files and directories) below this node
+    pub fn descendant_count(&self) -> usize {
+        let mut count = 0;
+        let mut pending = vec![self];
+        while let Some(node) = pending.pop() {
+            count += node.children.len();
+            pending.extend(node.children.values());
+        }
+        count
+    }
+
+    /// Paths of the files below this node, relative to it, in the depth-first order
+    /// [`TreeNode::render`] lists them with the same `options` (`max_depth` is ignored).
+    /// Symlinks that were not followed count as files.
+    pub fn file_paths(&self, options: &TreeOptions) -> Vec<PathBuf> {
+        // Nodes still to visit, each with its path; children are pushed in reverse so they
+        // come off the stack in display order
+        let mut paths = Vec::new();
+        let mut pending: Vec<(&TreeNode, PathBuf)> = sorted_children(self, options)
+            .into_iter()
+            .rev()
+            .map(|child| (child, PathBuf::from(&child.name)))
+            .collect();
+        while let Some((node, path)) = pending.pop() {
+            if node.is_file {
+                paths.push(path);
+                continue;
+            }
+            for child in sorted_children(node, options).into_iter().rev() {
+                pending.push((child, path.join(&child.name)));
+            }
+        }
+        paths
+    }
+
+    /// Render this node's children as tree lines (without the "Directory structure:" label)
+    pub fn render(&self, options: &TreeOptions) -> String {
+        let mut output = String::with_capacity(self.descendant_count() * 32);
+        render_tree(self, &mut output, options);
+        output
+    }
+
+    /// Convert this node and its descendants into nested JSON objects. Directories
+    /// [`MAX_JSON_TREE_DEPTH`] levels down list no children; instead `truncated` counts
+    /// the entries left out below them.
+    pub fn to_json(&self, options: &TreeOptions) -> serde_json::Value {
+        // Built bottom up from an explicit stack, like `render_tree`: each entry is a node,
+        // its children in display order and the JSON of the children done so far
+        let mut stack = vec![(self, sorted_children(self, options), Vec::new())];
+        loop {
+            let (node, children, done) = stack.last_mut().expect("root stays on the stack");
+            if let Some(&child) = children.get(done.len()) {
+                let children = if stack.len() >= MAX_JSON_TREE_DEPTH {
+                    Vec::new()
+                } else {
+                    sorted_children(child, options)
+                };
+                stack.push((child, children, Vec::new()));
+                continue;
+            }
+            let mut value = node.json_value(std::mem::take(done), options);
+            if children.is_empty() && !node.children.is_empty() {
+                value["truncated"] = serde_json::json!(node.descendant_count());
+            }
+            stack.pop();
+            match stack.last_mut() {
+                Some((_, _, siblings)) => siblings.push(value),
+                None => return value,
+            }
+        }
+    }
+
+    /// This node as a JSON object, given its children's objects
+    fn json_value(
+        &self,
+        children: Vec<serde_json::Value>,
+        options: &TreeOptions,
+    ) -> serde_json::Value {
+        let mut value = serde_json::json!({
+            "name": self.name,
+            "is_file": self.is_file,
+            "children": children,
+        });
+        if options.show_sizes {
+            if let Some(size) = self.size {
+                value["size"] = serde_json::json!(size);
+            }
+        }
+        if options.show_counts && !self.is_file {
+            value["files"] = serde_json::json!(self.file_count);
+            value["dirs"] = serde_json::json!(self.dir_count);
+        }
+        if let Some(target) = &self.link_target {
+            value["link_target"] = serde_json::json!(target);
+        }
+        if self.dropped {
+            value["dropped"] = serde_json::json!(true);
+        }
+        match self.change {
+            Some(TreeChange::Added) => value["change"] = serde_json::json!("added"),
+            Some(TreeChange::Removed) => value["change"] = serde_json::json!("removed"),
+            _ => {}
+        }
+        value
+    }
+
+    /// Name as displayed in the tree, followed by `dir_marker` for directories
+    fn label(&self, dir_marker: &str) -> String {
+        if let Some(target) = &self.link_target {
+            format!("{} -> {}", self.name, target)
+        } else if self.dropped {
+            format!("{} (dropped)", self.name)
+        } else if self.is_file {
+            self.name.clone()
+        } else {
+            format!("{}{}", self.name, dir_marker)
+        }
+    }
+
+    /// What follows the label in parentheses: the counts of a directory with
+    /// `show_counts`, then the size with `show_sizes`, e.g. "3 files, 1 dir, 4.2 kB"
+    fn annotation(&self, options: &TreeOptions) -> Option<String> {
+        let mut parts = Vec::new();
+        if options.show_counts && !self.is_file {
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            parts.push(format!(
+                "{} file{}",
+                self.file_count,
+                plural(self.file_count)
+            ));
+            if self.dir_count > 0 {
+                parts.push(format!("{} dir{}", self.dir_count, plural(self.dir_count)));
+            }
+        }
+        if options.show_sizes {
+            if let Some(size) = self.size {
+                parts.push(format_size(size));
+            }
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+
+    /// The label to render and the node whose children follow it. With `--tree-collapse`,
+    /// a directory whose only child is a directory is merged with it, repeatedly.
+    /// Merged names are joined with '/', and the directory marker follows the last one.
+    fn display(&self, options: &TreeOptions) -> (String, &TreeNode) {
+        let mut merged = String::new();
+        let mut node = self;
+        while options.collapse && !node.is_file && node.children.len() == 1 {
+            let only = node.children.values().next().expect("one child");
+            if only.is_file {
+                break;
+            }
+            merged.push_str(&node.name);
+            merged.push('/');
+            node = only;
+        }
+        let marker = options.dir_marker.as_deref().unwrap_or("/");
+        (merged + &node.label(marker), node)
+    }
+}
+
+impl Drop for TreeNode {
+    /// Dropping nested maps would recurse once per level, so descendants are detached
+    /// and dropped one at a time instead
+    fn drop(&mut self) {
+        let mut pending: Vec<TreeNode> = self.children.drain().map(|(_, child)| child).collect();
+        while let Some(mut node) = pending.pop() {
+            pending.extend(node.children.drain().map(|(_, child)| child));
+        }
+    }
+}
+
+/// Format a byte count using the same SI units `--max-size` accepts (e.g. "1.2 kB").
+pub fn format_size(bytes: u64) -> String {
+    ByteSize::b(bytes).display().si().to_string()
+}
+
+/// Set each directory's size to the sum of its descendants' sizes, and count the files and
+/// directories below it. Directories without any sized descendants keep a `None` size.
+///
+/// Each directory's children are detached while they are summed, and put back once their
+/// own totals are final, so no recursion is needed however deep the tree is.
+fn aggregate_totals(root: &mut TreeNode) {
+    // Directories being summed, innermost last, with their children still to visit
+    let mut stack: Vec<(TreeNode, Vec<TreeNode>)> = Vec::new();
+    let children = std::mem::take(&mut root.children).into_values().collect();
+    stack.push((std::mem::replace(root, TreeNode::new()), children));
+
+    while let Some((node, pending)) = stack.last_mut() {
+        if let Some(mut child) = pending.pop() {
+            if child.is_file {
+                node.children.insert(child.name.clone(), child);
+            } else {
+                let grandchildren = std::mem::take(&mut child.children).into_values().collect();
+                stack.push((child, grandchildren));
+            }
+            continue;
+        }
+
+        let (mut node, _) = stack.pop().expect("stack is not empty");
+        node.size = node
+            .children
+            .values()
+            .filter_map(|child| child.size)
+            .reduce(|a, b| a + b);
+        for child in node.children.values() {
+            if child.is_file {
+                node.file_count += 1;
+            } else {
+                node.file_count += child.file_count;
+                node.dir_count += 1 + child.dir_count;
+            }
+        }
+        match stack.last_mut() {
+            Some((parent, _)) => {
+                parent.children.insert(node.name.clone(), node);
+            }
+            None => *root = node,
         }
     }
 }
@@ -80,14 +892,14 @@ pub fn clean_path_components(path: &Path) -> Vec<String> {
 ///
 /// # Arguments
 /// * `root` - The root tree node to add the path to
-/// * `path` - The path to add to the tree
-/// * `final_is_file` - Whether to treat the final component as a file (default: true)
-///
-/// # Future Enhancement
-/// For explicit directory support, this function could be extended to accept
-/// an additional parameter or use a separate function that marks directories explicitly.
-fn add_path_to_tree(root: &mut TreeNode, path: &Path) {
-    add_path_to_tree_with_type(root, path, true)
+/// * `entry` - The entry to add; `entry.is_file` marks explicit directories
+fn add_entry_to_tree(root: &mut TreeNode, entry: &TreeEntry) {
+    if let Some(node) = add_path_to_tree_with_type(root, &entry.path, entry.is_file, entry.size) {
+        if node.is_file {
+            node.link_target = entry.link_target.clone();
+            node.dropped = entry.dropped;
+        }
+    }
 }
 
 /// Internal function to add a path to the tree with explicit control over final component type.
@@ -96,10 +908,18 @@ fn add_path_to_tree(root: &mut TreeNode, path: &Path) {
 /// * `root` - The root tree node to add the path to
 /// * `path` - The path to add to the tree
 /// * `final_is_file` - Whether to treat the final component as a file
-fn add_path_to_tree_with_type(root: &mut TreeNode, path: &Path, final_is_file: bool) {
+/// * `size` - Optional byte size recorded on the final component when it is a file
+///
+/// Returns the node for the final component (`None` for an empty path).
+fn add_path_to_tree_with_type<'a>(
+    root: &'a mut TreeNode,
+    path: &Path,
+    final_is_file: bool,
+    size: Option<u64>,
+) -> Option<&'a mut TreeNode> {
     let components = clean_path_components(path);
     if components.is_empty() {
-        return;
+        return None;
     }
 
     let mut current = root;
@@ -122,6 +942,7 @@ fn add_path_to_tree_with_type(root: &mut TreeNode, path: &Path, final_is_file: b
                         // Keep as directory if it has children, otherwise make it a file
                         if existing_entry.children.is_empty() {
                             existing_entry.is_file = true;
+                            existing_entry.size = size;
                         }
                         // If it has children, directory wins and we ignore the file
                     }
@@ -129,10 +950,11 @@ fn add_path_to_tree_with_type(root: &mut TreeNode, path: &Path, final_is_file: b
                 }
                 None => {
                     // Create new entry
-                    current.children.insert(
-                        name.clone(),
-                        TreeNode::new_with_name(name.clone(), final_is_file),
-                    );
+                    let mut node = TreeNode::new_with_name(name.clone(), final_is_file);
+                    if final_is_file {
+                        node.size = size;
+                    }
+                    current.children.insert(name.clone(), node);
                 }
             }
         } else {
@@ -149,62 +971,158 @@ fn add_path_to_tree_with_type(root: &mut TreeNode, path: &Path, final_is_file: b
             current = entry;
         }
     }
+
+    current.children.get_mut(components.last()?)
+}
+
+/// One directory being rendered by [`render_tree`]
+struct Level<'a> {
+    /// Children in display order
+    children: Vec<&'a TreeNode>,
+    /// Index of the next child to render
+    next: usize,
+    /// Width that size and count annotations are aligned to
+    label_width: usize,
+    /// Length of the prefix drawn before each child
+    prefix_len: usize,
 }
 
-fn render_child(
-    child: &TreeNode,
-    output: &mut String,
-    current_prefix: &str,
-    is_last: bool,
-    is_root: bool,
-) {
-    // Add current prefix (empty for root)
-    if !is_root {
-        output.push_str(current_prefix);
+impl<'a> Level<'a> {
+    fn new(node: &'a TreeNode, options: &TreeOptions, prefix_len: usize) -> Self {
+        let children = sorted_children(node, options);
+        let label_width = if options.show_sizes || options.show_counts {
+            children
+                .iter()
+                .map(|c| c.display(options).0.chars().count())
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        Level {
+            children,
+            next: 0,
+            label_width,
+            prefix_len,
+        }
     }
+}
 
-    // Add tree symbols
-    let child_prefix = if is_last { "└── " } else { "├── " };
-    output.push_str(child_prefix);
-    output.push_str(&child.name);
+/// Render the children of `root`, depth first. The directories being walked are kept on
+/// an explicit stack rather than recursed into, so arbitrarily deep trees cannot overflow
+/// the call stack.
+fn render_tree(root: &TreeNode, output: &mut String, options: &TreeOptions) {
+    let connectors = Connectors::new(options);
+    // One continuation ("│   " or "    ") per ancestor of the entries being drawn
+    let mut prefix = String::new();
+    let mut stack = vec![Level::new(root, options, 0)];
 
-    // Add '/' for directories
-    if !child.is_file {
-        output.push('/');
-    }
-    output.push('\n');
+    while let Some(level) = stack.last_mut() {
+        let Some(&child) = level.children.get(level.next) else {
+            stack.pop();
+            if let Some(parent) = stack.last() {
+                prefix.truncate(parent.prefix_len);
+            }
+            continue;
+        };
+        level.next += 1;
+        let is_last = level.next == level.children.len();
+        let label_width = level.label_width;
+        let depth = stack.len() - 1;
 
-    // Calculate next prefix for children
-    let next_prefix = if is_root {
-        // For root children, use simple prefix
-        if is_last { "    " } else { "│   " }.to_string()
-    } else {
-        // For non-root children, extend current prefix
-        let mut next = String::with_capacity(current_prefix.len() + 4);
-        next.push_str(current_prefix);
-        next.push_str(if is_last { "    " } else { "│   " });
-        next
-    };
+        if let Some(change) = child.change {
+            output.push_str(change.gutter());
+        }
+        output.push_str(&prefix);
+        output.push_str(if is_last {
+            &connectors.last
+        } else {
+            &connectors.branch
+        });
 
-    // Recursively render this child's children
-    render_tree(child, output, &next_prefix, false);
+        // Directories get their marker ('/' by default)
+        let (label, child) = child.display(options);
+        let color = if !options.color {
+            None
+        } else if child.link_target.is_some() {
+            Some(LINK_COLOR)
+        } else if !child.is_file {
+            Some(DIR_COLOR)
+        } else {
+            None
+        };
+        match color {
+            Some(color) => output.push_str(&paint(&label, color)),
+            None => output.push_str(&label),
+        }
+
+        // Size and count annotation, padded so they line up across siblings
+        if let Some(annotation) = child.annotation(options) {
+            let padding = label_width.saturating_sub(label.chars().count());
+            output.push_str(&" ".repeat(padding));
+            output.push_str(&format!(" ({})", annotation));
+        }
+        output.push('\n');
+
+        if child.children.is_empty() {
+            continue;
+        }
+        let continuation = if is_last {
+            &connectors.blank
+        } else {
+            &connectors.vertical
+        };
+
+        // Past the depth limit, summarize the hidden entries instead of descending
+        if options.max_depth.is_some_and(|max| depth >= max) {
+            if let Some(change) = child.change {
+                output.push_str(change.gutter());
+            }
+            output.push_str(&prefix);
+            output.push_str(continuation);
+            output.push_str(&format!(
+                "{}{} ({} more)\n",
+                connectors.last,
+                connectors.ellipsis,
+                child.descendant_count()
+            ));
+            continue;
+        }
+
+        prefix.push_str(continuation);
+        stack.push(Level::new(child, options, prefix.len()));
+    }
 }
 
-fn render_tree(node: &TreeNode, output: &mut String, prefix: &str, is_root: bool) {
-    // Sort children: directories first, then files, both alphabetically
+/// Sort children according to the options; ties are always broken by name
+fn sorted_children<'a>(node: &'a TreeNode, options: &TreeOptions) -> Vec<&'a TreeNode> {
     let mut children: Vec<_> = node.children.values().collect();
     children.sort_by(|a, b| {
-        // Directories before files
-        match (a.is_file, b.is_file) {
-            (false, true) => std::cmp::Ordering::Less,
-            (true, false) => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
-        }
+        // Directories before files (or after, with files_first), unless interleaved
+        // or sorting by size
+        let kind = if options.sort == TreeSort::Size || options.order == TreeOrder::AlphaInterleaved
+        {
+            std::cmp::Ordering::Equal
+        } else if options.files_first {
+            b.is_file.cmp(&a.is_file)
+        } else {
+            a.is_file.cmp(&b.is_file)
+        };
+        let key = match options.sort {
+            TreeSort::Name => std::cmp::Ordering::Equal,
+            TreeSort::Size => b.size.unwrap_or(0).cmp(&a.size.unwrap_or(0)),
+            TreeSort::Ext => extension(&a.name).cmp(extension(&b.name)),
+        };
+        kind.then(key)
+            .then_with(|| options.sort_case.compare(&a.name, &b.name))
     });
+    children
+}
 
-    // Render each child using the helper function
-    for (i, child) in children.iter().enumerate() {
-        let is_last = i == children.len() - 1;
-        render_child(child, output, prefix, is_last, is_root);
-    }
+/// A file name's extension, or "" when it has none
+pub(crate) fn extension(name: &str) -> &str {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
 }
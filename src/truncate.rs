@@ -0,0 +1,96 @@
+use anyhow::{anyhow, Result};
+
+/// A `--truncate <glob>:<lines>` rule: matching files keep only their first `lines` lines
+#[derive(Debug, Clone)]
+pub struct TruncateRule {
+    pub pattern: glob::Pattern,
+    pub lines: usize,
+}
+
+/// Parse a `<glob>:<lines>` spec. The line count follows the last colon,
+/// so globs may themselves contain colons.
+pub fn parse_truncate_rule(spec: &str) -> Result<TruncateRule> {
+    let (glob, lines) = spec
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("truncate: Invalid rule '{}': expected <glob>:<lines>", spec))?;
+    let lines = lines
+        .trim()
+        .parse::<usize>()
+        .map_err(|e| anyhow!("truncate: Invalid line count in '{}': {}", spec, e))?;
+    let pattern = glob::Pattern::new(glob.trim())
+        .map_err(|e| anyhow!("truncate: Invalid pattern in '{}': {}", spec, e))?;
+    Ok(TruncateRule { pattern, lines })
+}
+
+/// Line limit for a file: the smallest among matching rules, or `None` if none match
+pub fn truncate_limit(path: &str, rules: &[TruncateRule]) -> Option<usize> {
+    rules
+        .iter()
+        .filter(|rule| rule.pattern.matches(path))
+        .map(|rule| rule.lines)
+        .min()
+}
+
+/// Keep the first `lines` lines of `content`, followed by a marker counting the rest.
+/// Content that already fits is returned unchanged.
+pub fn truncate_lines(content: &str, lines: usize) -> String {
+    let total = content.lines().count();
+    if total <= lines {
+        return content.to_string();
+    }
+
+    let mut kept: String = content.split_inclusive('\n').take(lines).collect();
+    kept.push_str(&format!("... (truncated, {} more lines)\n", total - lines));
+    kept
+}
+
+/// Appended to lines cut short by `--max-line-length`
+pub const LINE_TRUNCATED_MARKER: &str = "…(truncated)";
+
+/// Limit every line of `content` to `max` characters. Longer lines are cut and marked with
+/// [`LINE_TRUNCATED_MARKER`], or with `wrap` split over as many lines as they need.
+/// Line endings are kept, and content where every line fits is returned unchanged.
+pub fn limit_line_length(content: &str, max: usize, wrap: bool) -> String {
+    if max == 0 || content.lines().all(|line| line.chars().count() <= max) {
+        return content.to_string();
+    }
+
+    let mut limited = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[text.len()..];
+        if text.chars().count() <= max {
+            limited.push_str(line);
+            continue;
+        }
+        if wrap {
+            let chars: Vec<char> = text.chars().collect();
+            for (i, piece) in chars.chunks(max).enumerate() {
+                if i > 0 {
+                    limited.push('\n');
+                }
+                limited.extend(piece);
+            }
+        } else {
+            limited.extend(text.chars().take(max));
+            limited.push_str(LINE_TRUNCATED_MARKER);
+        }
+        limited.push_str(ending);
+    }
+    limited
+}
+
+/// Keep the first `head` and last `tail` lines of `content`, with a marker for the
+/// lines elided in between. Content with at most `head + tail` lines is returned whole.
+pub fn head_tail_lines(content: &str, head: usize, tail: usize) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    if lines.len() <= head + tail {
+        return content.to_string();
+    }
+
+    let omitted = lines.len() - head - tail;
+    let mut kept: String = lines[..head].concat();
+    kept.push_str(&format!("... ({} lines omitted)\n", omitted));
+    kept.push_str(&lines[lines.len() - tail..].concat());
+    kept
+}
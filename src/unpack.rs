@@ -0,0 +1,177 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+
+use crate::{config::closing_separator, defaults::DEFAULT_SEPARATOR};
+
+/// `yek unpack`: recreate the files stored in a previously generated output
+#[derive(Parser, Debug)]
+#[command(name = "yek unpack")]
+pub struct UnpackArgs {
+    /// Output file to read, or "-" for stdin
+    pub file: String,
+
+    /// Directory to recreate the files in
+    #[arg(long = "into", default_value = ".")]
+    pub into: String,
+
+    /// Marker the output was generated with (see --separator)
+    #[arg(long = "separator", default_value = DEFAULT_SEPARATOR)]
+    pub separator: String,
+}
+
+/// A file recovered from an output
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnpackedFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Split an output generated with the default template back into files.
+///
+/// Each file starts at a `<separator> <path>` line; anything before the first one, such as
+/// the tree header, is skipped. Output from `--separator-style fence` is detected by its
+/// closing `<mirrored separator> <path>` lines and parsed exactly. Without them a file runs
+/// until the next marker, so a file containing a marker line of its own is cut short there,
+/// and the last file keeps anything that follows it, such as a `--stats` footer.
+pub fn parse_output(text: &str, separator: &str) -> Result<Vec<UnpackedFile>> {
+    let open = format!("{} ", separator);
+    let close = format!("{} ", closing_separator(separator));
+
+    // Lines with the byte offset they start at, without their line break
+    let mut offset = 0;
+    let lines: Vec<(usize, &str)> = text
+        .split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line.strip_suffix('\n').unwrap_or(line))
+        })
+        .collect();
+
+    let fenced = lines
+        .iter()
+        .position(|(_, line)| line.starts_with(&open))
+        .is_some_and(|first| {
+            let path = &lines[first].1[open.len()..];
+            lines[first + 1..]
+                .iter()
+                .any(|(_, line)| line.strip_prefix(&close) == Some(path))
+        });
+
+    // Content starts on the line after a marker
+    let after = |start: usize, line: &str| (start + line.len() + 1).min(text.len());
+
+    let mut files = Vec::new();
+    // Path and content start of the file being read
+    let mut current: Option<(&str, usize)> = None;
+    for &(start, line) in &lines {
+        match current {
+            Some((path, from)) if fenced => {
+                if line.strip_prefix(&close) == Some(path) {
+                    files.push(unpacked(path, &text[from..start]));
+                    current = None;
+                }
+            }
+            Some((path, from)) => {
+                if let Some(next) = line.strip_prefix(&open) {
+                    files.push(unpacked(path, &text[from..start]));
+                    current = Some((next, after(start, line)));
+                }
+            }
+            None => {
+                if let Some(path) = line.strip_prefix(&open) {
+                    current = Some((path, after(start, line)));
+                }
+            }
+        }
+    }
+
+    match current {
+        Some((path, _)) if fenced => Err(anyhow!(
+            "unpack: '{}' has no closing '{}{}' line",
+            path,
+            close,
+            path
+        )),
+        Some((path, from)) => {
+            files.push(UnpackedFile {
+                path: path.to_string(),
+                content: text[from..].to_string(),
+            });
+            Ok(files)
+        }
+        None => Ok(files),
+    }
+}
+
+/// A file whose block ends right before a marker line. The newline before that line
+/// was added by the output (as the template's or the separator between files), so it is dropped.
+fn unpacked(path: &str, block: &str) -> UnpackedFile {
+    UnpackedFile {
+        path: path.to_string(),
+        content: block.strip_suffix('\n').unwrap_or(block).to_string(),
+    }
+}
+
+/// Where a recovered file goes under `root`. Absolute paths and `..` components are
+/// rejected so an output cannot write outside the target directory.
+pub fn unpack_target(root: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+    let safe = !path.is_empty()
+        && relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !safe {
+        return Err(anyhow!(
+            "unpack: refusing to write '{}' outside the target directory",
+            path
+        ));
+    }
+    Ok(root.join(relative))
+}
+
+/// Write `files` under `root`, creating directories as needed. Returns the paths written.
+pub fn write_unpacked(root: &Path, files: &[UnpackedFile]) -> Result<Vec<PathBuf>> {
+    // Check every path before writing anything
+    let targets = files
+        .iter()
+        .map(|file| unpack_target(root, &file.path))
+        .collect::<Result<Vec<_>>>()?;
+
+    for (target, file) in targets.iter().zip(files) {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| anyhow!("unpack: cannot create '{}': {}", parent.display(), e))?;
+        }
+        fs::write(target, &file.content)
+            .map_err(|e| anyhow!("unpack: cannot write '{}': {}", target.display(), e))?;
+    }
+    Ok(targets)
+}
+
+/// Run `yek unpack` with the given arguments
+pub fn unpack(args: &UnpackArgs) -> Result<Vec<PathBuf>> {
+    let text = if args.file == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        fs::read_to_string(&args.file)
+            .map_err(|e| anyhow!("unpack: cannot read '{}': {}", args.file, e))?
+    };
+    let files = parse_output(&text, &args.separator)?;
+    if files.is_empty() {
+        return Err(anyhow!(
+            "unpack: no '{} <path>' lines found in '{}'",
+            args.separator,
+            args.file
+        ));
+    }
+    write_unpacked(Path::new(&args.into), &files)
+}
@@ -0,0 +1,135 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tracing::{debug, error, info};
+
+use crate::{
+    atomic::temp_path,
+    config::YekConfig,
+    parallel::{glob_root, is_watched_path},
+    read_file_list,
+};
+
+/// What to hand the file watcher for the configured inputs: directories and glob roots
+/// recursively, and the parent directory of single files, since editors often save by
+/// replacing the file, which a watch on the file itself would lose.
+pub fn watch_targets(config: &YekConfig) -> Result<Vec<(PathBuf, RecursiveMode)>> {
+    let inputs = match &config.files_from {
+        Some(source) => read_file_list(source)?,
+        None => config.input_paths.clone(),
+    };
+
+    let mut targets: Vec<(PathBuf, RecursiveMode)> = Vec::new();
+    for input in &inputs {
+        let path = glob_root(input).unwrap_or_else(|| PathBuf::from(input));
+        let path = std::path::absolute(path)?;
+        let target = if path.is_dir() {
+            (path, RecursiveMode::Recursive)
+        } else {
+            let parent = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+            (parent, RecursiveMode::NonRecursive)
+        };
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    Ok(targets)
+}
+
+/// Whether an event should trigger regeneration: anything but a plain read, on a path that
+/// would be serialized. Writes into the output directory or to the `--output` and
+/// `--manifest` files (and their temporary files) never count, so regenerating cannot
+/// trigger itself.
+pub fn is_relevant_event(event: &Event, config: &YekConfig) -> bool {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+    let output_dir = config
+        .output_dir
+        .as_ref()
+        .filter(|_| !config.stream)
+        .and_then(|dir| std::path::absolute(dir).ok());
+    let written: Vec<PathBuf> = config
+        .written_files()
+        .iter()
+        .filter_map(|file| std::path::absolute(file).ok())
+        .collect();
+
+    event.paths.iter().any(|path| {
+        if output_dir.as_ref().is_some_and(|dir| path.starts_with(dir)) {
+            return false;
+        }
+        if written
+            .iter()
+            .any(|file| path == file || *path == temp_path(file))
+        {
+            return false;
+        }
+        is_watched_path(path, config).unwrap_or_else(|e| {
+            debug!("Cannot check {}: {e}", path.display());
+            false
+        })
+    })
+}
+
+/// Call `regenerate` whenever a watched file changes, until the process is interrupted.
+/// Bursts of changes are coalesced: regeneration waits until no relevant event has arrived
+/// for `--watch-debounce` milliseconds. Errors from `regenerate` are reported and watching
+/// goes on, so a bad intermediate state does not end the session.
+pub fn watch<F>(config: &mut YekConfig, mut regenerate: F) -> Result<()>
+where
+    F: FnMut(&mut YekConfig) -> Result<()>,
+{
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| anyhow!("watch: cannot start file watcher: {}", e))?;
+    for (path, mode) in watch_targets(config)? {
+        watcher
+            .watch(&path, mode)
+            .map_err(|e| anyhow!("watch: cannot watch '{}': {}", path.display(), e))?;
+    }
+
+    let debounce = Duration::from_millis(config.watch_debounce);
+    let relevant = |event: notify::Result<Event>, config: &YekConfig| match event {
+        Ok(event) => is_relevant_event(&event, config),
+        Err(e) => {
+            debug!("Watch error: {e}");
+            false
+        }
+    };
+
+    info!("Watching for changes (Ctrl+C to stop)");
+    loop {
+        let event = rx
+            .recv()
+            .map_err(|_| anyhow!("watch: file watcher stopped"))?;
+        if !relevant(event, config) {
+            continue;
+        }
+
+        // Wait for things to settle; only relevant events push the deadline back
+        let mut deadline = Instant::now() + debounce;
+        loop {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(event) => {
+                    if relevant(event, config) {
+                        deadline = Instant::now() + debounce;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(anyhow!("watch: file watcher stopped"))
+                }
+            }
+        }
+
+        if let Err(e) = regenerate(config) {
+            error!("{}", e);
+        }
+    }
+}
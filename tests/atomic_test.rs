@@ -0,0 +1,107 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::{fs, io::Write};
+use tempfile::tempdir;
+use yek::atomic::{temp_path, AtomicFile};
+
+#[test]
+fn test_atomic_file_appears_on_commit() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("out.txt");
+    fs::write(&path, "previous").unwrap();
+
+    let mut file = AtomicFile::create(&path).unwrap();
+    file.write_all(b"new output").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "previous");
+    assert!(temp_path(&path).exists());
+
+    file.commit().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new output");
+    assert!(!temp_path(&path).exists());
+}
+
+#[test]
+fn test_atomic_file_dropped_keeps_previous() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("out.txt");
+    fs::write(&path, "previous").unwrap();
+
+    {
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"half an out").unwrap();
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), "previous");
+    assert!(!temp_path(&path).exists());
+}
+
+#[test]
+fn test_temp_path_is_hidden_sibling() {
+    let temp = temp_path(std::path::Path::new("dir/out.txt"));
+    assert_eq!(temp.parent().unwrap(), std::path::Path::new("dir"));
+    let name = temp.file_name().unwrap().to_string_lossy().to_string();
+    assert!(name.starts_with(".out.txt.yek-tmp-"), "{}", name);
+}
+
+#[test]
+fn test_output_flag() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+
+    Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--output", "prompt.txt", "."])
+        .assert()
+        .success()
+        .stdout("prompt.txt\n");
+    let first = fs::read_to_string(dir.path().join("prompt.txt")).unwrap();
+    assert!(first.contains(">>>> main.rs\nfn main() {}"), "{}", first);
+
+    // The previous output is not read back in
+    Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["-o", "prompt.txt", "."])
+        .assert()
+        .success();
+    let second = fs::read_to_string(dir.path().join("prompt.txt")).unwrap();
+    assert_eq!(second, first);
+    assert!(!second.contains(">>>> prompt.txt"));
+}
+
+#[test]
+fn test_output_flag_with_compression() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+
+    Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--output", "prompt.txt", "--compress", "gzip", "."])
+        .assert()
+        .success()
+        .stdout("prompt.txt.gz\n");
+    let bytes = fs::read(dir.path().join("prompt.txt.gz")).unwrap();
+    assert_eq!(&bytes[..2], &[0x1f, 0x8b]);
+    assert!(!dir.path().join("prompt.txt").exists());
+}
+
+#[test]
+fn test_output_flag_conflicts() {
+    let dir = tempdir().unwrap();
+    for (args, message) in [
+        (
+            vec!["--output", "out.txt", "--split-output", "chunks"],
+            "output: cannot be combined with split_output",
+        ),
+        (vec!["--output", "."], "output: '.' is not a file path"),
+    ] {
+        Command::cargo_bin("yek")
+            .unwrap()
+            .current_dir(dir.path())
+            .args(&args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(message));
+    }
+}
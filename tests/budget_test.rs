@@ -0,0 +1,58 @@
+use yek::budget::{budget_group, parse_budget_rule, reservations};
+
+#[test]
+fn test_parse_budget_rule() {
+    let rule = parse_budget_rule("tests/**:30").unwrap();
+    assert!(rule.pattern.matches("tests/unit/a.rs"));
+    assert_eq!(rule.percent, 30);
+
+    // A trailing % is optional and the percentage follows the last colon
+    let rule = parse_budget_rule("docs/a:b.md: 25%").unwrap();
+    assert!(rule.pattern.matches("docs/a:b.md"));
+    assert_eq!(rule.percent, 25);
+}
+
+#[test]
+fn test_parse_budget_rule_errors() {
+    assert_eq!(
+        parse_budget_rule("src/**").unwrap_err().to_string(),
+        "budget: Invalid rule 'src/**': expected <glob>:<percent>"
+    );
+    assert!(parse_budget_rule("src/**:most")
+        .unwrap_err()
+        .to_string()
+        .starts_with("budget: Invalid percentage in 'src/**:most'"));
+    assert_eq!(
+        parse_budget_rule("src/**:0").unwrap_err().to_string(),
+        "budget: Percentage in 'src/**:0' must be between 1 and 100"
+    );
+    assert_eq!(
+        parse_budget_rule("src/**:150").unwrap_err().to_string(),
+        "budget: Percentage in 'src/**:150' must be between 1 and 100"
+    );
+    assert!(parse_budget_rule("[:3")
+        .unwrap_err()
+        .to_string()
+        .starts_with("budget: Invalid pattern in '[:3'"));
+}
+
+#[test]
+fn test_budget_group_uses_first_match() {
+    let rules = vec![
+        parse_budget_rule("src/tests/**:10").unwrap(),
+        parse_budget_rule("src/**:50").unwrap(),
+    ];
+    assert_eq!(budget_group("src/tests/a.rs", &rules), Some(0));
+    assert_eq!(budget_group("src/lib.rs", &rules), Some(1));
+    assert_eq!(budget_group("README.md", &rules), None);
+}
+
+#[test]
+fn test_reservations_round_down() {
+    let rules = vec![
+        parse_budget_rule("src/**:70").unwrap(),
+        parse_budget_rule("tests/**:30").unwrap(),
+    ];
+    assert_eq!(reservations(1000, &rules), vec![700, 300]);
+    assert_eq!(reservations(15, &rules), vec![10, 4]);
+}
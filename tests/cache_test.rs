@@ -0,0 +1,193 @@
+use assert_cmd::Command;
+use std::{cell::Cell, fs, path::Path};
+use tempfile::tempdir;
+use yek::cache::{clear_token_cache, save_token_cache, token_cache, TokenCache, CACHE_FILE};
+use yek::config::YekConfig;
+use yek::models::Tokenizer;
+use yek::serialize_repo;
+
+/// Count through `cache`, recording whether the counter actually ran
+fn count(cache: &TokenCache, path: &Path, text: &str, calls: &Cell<usize>) -> usize {
+    cache.tokens(path, text, Tokenizer::Cl100k, |text| {
+        calls.set(calls.get() + 1);
+        text.len()
+    })
+}
+
+#[test]
+fn test_unchanged_file_is_counted_once() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("a.rs");
+    fs::write(&file, "fn a() {}").unwrap();
+    let cache = TokenCache::load(&dir.path().join("cache"));
+    let calls = Cell::new(0);
+
+    assert_eq!(count(&cache, &file, "fn a() {}", &calls), 9);
+    assert_eq!(count(&cache, &file, "fn a() {}", &calls), 9);
+    assert_eq!(calls.get(), 1);
+    assert_eq!(cache.len(), 1);
+
+    // Different text rendered from the same file is counted on its own
+    assert_eq!(count(&cache, &file, ">>>> a.rs\nfn a() {}", &calls), 19);
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn test_changed_file_is_counted_again() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("a.rs");
+    fs::write(&file, "one").unwrap();
+    let cache = TokenCache::load(&dir.path().join("cache"));
+    let calls = Cell::new(0);
+
+    count(&cache, &file, "one", &calls);
+    fs::write(&file, "three").unwrap();
+    count(&cache, &file, "one", &calls);
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn test_tokenizers_are_cached_separately() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("a.rs");
+    fs::write(&file, "text").unwrap();
+    let cache = TokenCache::load(&dir.path().join("cache"));
+
+    assert_eq!(cache.tokens(&file, "text", Tokenizer::Cl100k, |_| 1), 1);
+    assert_eq!(cache.tokens(&file, "text", Tokenizer::O200k, |_| 2), 2);
+    assert_eq!(cache.tokens(&file, "text", Tokenizer::Cl100k, |_| 3), 1);
+}
+
+#[test]
+fn test_missing_file_is_never_cached() {
+    let dir = tempdir().unwrap();
+    let cache = TokenCache::load(dir.path());
+    let calls = Cell::new(0);
+    let missing = dir.path().join("missing.rs");
+
+    count(&cache, &missing, "text", &calls);
+    count(&cache, &missing, "text", &calls);
+    assert_eq!(calls.get(), 2);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_cache_survives_save_and_load() {
+    let dir = tempdir().unwrap();
+    let cache_dir = dir.path().join("cache");
+    let kept = dir.path().join("kept.rs");
+    let removed = dir.path().join("removed.rs");
+    fs::write(&kept, "kept").unwrap();
+    fs::write(&removed, "removed").unwrap();
+
+    let cache = TokenCache::load(&cache_dir);
+    let calls = Cell::new(0);
+    count(&cache, &kept, "kept", &calls);
+    count(&cache, &removed, "removed", &calls);
+    fs::remove_file(&removed).unwrap();
+    cache.save().unwrap();
+    assert!(cache_dir.join(CACHE_FILE).exists());
+
+    // Files that are gone are left out of the saved cache
+    let reloaded = TokenCache::load(&cache_dir);
+    assert_eq!(reloaded.len(), 1);
+    count(&reloaded, &kept, "kept", &calls);
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn test_unreadable_cache_starts_empty() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join(CACHE_FILE), "not json").unwrap();
+    assert!(TokenCache::load(dir.path()).is_empty());
+}
+
+#[test]
+fn test_clear_token_cache() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("a.rs");
+    fs::write(&file, "a").unwrap();
+    let cache = TokenCache::load(dir.path());
+    cache.tokens(&file, "a", Tokenizer::Cl100k, |_| 1);
+    cache.save().unwrap();
+
+    assert!(clear_token_cache(dir.path()).unwrap());
+    assert!(!dir.path().join(CACHE_FILE).exists());
+    assert!(!clear_token_cache(dir.path()).unwrap());
+}
+
+#[test]
+fn test_cache_is_off_without_a_directory_or_with_no_cache() {
+    let dir = tempdir().unwrap();
+    assert!(token_cache(&YekConfig::default()).is_none());
+
+    let mut config = YekConfig {
+        cache_dir: Some(dir.path().to_string_lossy().to_string()),
+        ..Default::default()
+    };
+    assert!(token_cache(&config).is_some());
+    config.no_cache = true;
+    assert!(token_cache(&config).is_none());
+    assert!(config.token_cache_dir().is_none());
+}
+
+#[test]
+fn test_cached_counts_give_the_same_output() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("src");
+    fs::create_dir_all(&source).unwrap();
+    for i in 0..5 {
+        fs::write(
+            source.join(format!("file{i}.rs")),
+            format!("fn f{i}() {{}}\n").repeat(20),
+        )
+        .unwrap();
+    }
+    let uncached = YekConfig {
+        input_paths: vec![source.to_string_lossy().to_string()],
+        tokens: "250".to_string(),
+        token_mode: true,
+        ..Default::default()
+    };
+    let cached = YekConfig {
+        cache_dir: Some(dir.path().join("cache").to_string_lossy().to_string()),
+        ..uncached.clone()
+    };
+
+    let (expected, _) = serialize_repo(&uncached).unwrap();
+    let (first, _) = serialize_repo(&cached).unwrap();
+    save_token_cache(&cached).unwrap();
+    let (second, _) = serialize_repo(&cached).unwrap();
+    assert_eq!(first, expected);
+    assert_eq!(second, expected);
+    assert!(!token_cache(&cached).unwrap().is_empty());
+    assert!(dir.path().join("cache").join(CACHE_FILE).exists());
+}
+
+#[test]
+fn test_cache_flags() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+    let cache_dir = dir.path().join("cache");
+    let cache_file = cache_dir.join(CACHE_FILE);
+    let run = |extra: &[&str]| {
+        Command::cargo_bin("yek")
+            .unwrap()
+            .current_dir(dir.path())
+            .arg("--cache-dir")
+            .arg(&cache_dir)
+            .args(["--count-only", "main.rs"])
+            .args(extra)
+            .assert()
+            .success();
+    };
+
+    run(&["--no-cache"]);
+    assert!(!cache_file.exists());
+
+    run(&[]);
+    assert!(cache_file.exists());
+
+    run(&["--clear-cache", "--no-cache"]);
+    assert!(!cache_file.exists());
+}
@@ -0,0 +1,51 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+use yek::clipboard::clipboard_summary;
+use yek::config::YekConfig;
+
+#[test]
+fn test_clipboard_summary() {
+    assert_eq!(
+        clipboard_summary(42, 18_300),
+        "Copied 42 files (17.9 KiB) to clipboard"
+    );
+    assert_eq!(clipboard_summary(1, 5), "Copied 1 file (5 B) to clipboard");
+}
+
+#[test]
+fn test_clipboard_with_split_output_is_invalid() {
+    let cfg = YekConfig {
+        clipboard: true,
+        split_output: Some("chunks".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "clipboard: cannot be combined with split_output"
+    );
+}
+
+/// Without a display server there is no clipboard, so yek must fail loudly
+/// instead of silently dropping the output.
+#[cfg(target_os = "linux")]
+#[test]
+fn test_clipboard_headless_fails_clearly() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("main.rs"), "fn main() {}").unwrap();
+
+    Command::cargo_bin("yek")
+        .unwrap()
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .arg("--clipboard")
+        .arg("--tree-header")
+        .arg(temp.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "clipboard: no system clipboard available",
+        ))
+        .stdout(predicate::str::contains("fn main() {}").not());
+}
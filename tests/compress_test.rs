@@ -0,0 +1,60 @@
+use std::io::{Read, Write};
+use yek::compress::{compressed_path, CompressedWriter, Compression};
+
+fn compress(text: &str, compression: Option<Compression>) -> Vec<u8> {
+    let mut writer = CompressedWriter::new(Vec::new(), compression).unwrap();
+    // Several writes, as the output is streamed file by file
+    for line in text.split_inclusive('\n') {
+        writer.write_all(line.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+#[test]
+fn test_compressed_path_appends_extension_once() {
+    assert_eq!(
+        compressed_path("out/yek-output-1.txt", Compression::Gzip),
+        "out/yek-output-1.txt.gz"
+    );
+    assert_eq!(
+        compressed_path("out/yek-output-1.txt", Compression::Zstd),
+        "out/yek-output-1.txt.zst"
+    );
+    assert_eq!(
+        compressed_path("out.txt.gz", Compression::Gzip),
+        "out.txt.gz"
+    );
+    assert_eq!(
+        compressed_path("out.txt.gz", Compression::Zstd),
+        "out.txt.gz.zst"
+    );
+}
+
+#[test]
+fn test_gzip_round_trip() {
+    let text = ">>>> main.rs\nfn main() {}\n".repeat(100);
+    let compressed = compress(&text, Some(Compression::Gzip));
+    assert!(compressed.len() < text.len());
+
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(compressed.as_slice())
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, text);
+}
+
+#[test]
+fn test_zstd_round_trip() {
+    let text = ">>>> main.rs\nfn main() {}\n".repeat(100);
+    let compressed = compress(&text, Some(Compression::Zstd));
+    assert!(compressed.len() < text.len());
+
+    let decoded = zstd::decode_all(compressed.as_slice()).unwrap();
+    assert_eq!(String::from_utf8(decoded).unwrap(), text);
+}
+
+#[test]
+fn test_no_compression_passes_through() {
+    let text = ">>>> main.rs\nfn main() {}\n";
+    assert_eq!(compress(text, None), text.as_bytes());
+}
@@ -5,9 +5,14 @@ use std::path::Path;
 use tempfile::TempDir;
 use yek::defaults::{BINARY_FILE_EXTENSIONS, DEFAULT_IGNORE_PATTERNS, DEFAULT_OUTPUT_TEMPLATE};
 
-use yek::config::YekConfig;
+use yek::config::{
+    closing_separator, command_line_settings, env_ignore_patterns, BudgetFill, OutputFormat,
+    OutputOrder, SeparatorStyle, YekConfig,
+};
 use yek::is_text_file;
+use yek::models::Tokenizer;
 use yek::priority::PriorityRule;
+use yek::tree::{TreeConnectors, TreeSort};
 
 #[test]
 fn test_validate_config_valid() {
@@ -108,9 +113,7 @@ fn test_validate_config_json_with_tree_only() {
     config.tree_only = true;
 
     let result = config.validate();
-    assert!(result.is_err());
-    let err = result.unwrap_err().to_string();
-    assert!(err.contains("JSON output not supported in tree-only mode"));
+    assert!(result.is_ok(), "JSON tree output should be allowed");
 }
 
 #[test]
@@ -138,6 +141,235 @@ fn test_validate_invalid_output_template() {
     );
 }
 
+#[test]
+fn test_validate_output_template_escapes() {
+    let cfg = YekConfig {
+        output_template: r">>>> FILE_PATH\xFILE_CONTENT".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        r"output_template: Unknown escape sequence '\x' (use \\ for a backslash)"
+    );
+
+    let cfg = YekConfig {
+        output_template: r"FILE_PATH\t\\\r\n\0FILE_CONTENT".to_string(),
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_closing_separator() {
+    assert_eq!(closing_separator(">>>>"), "<<<<");
+    assert_eq!(closing_separator("-->"), "<--");
+    assert_eq!(closing_separator("[[file"), "elif]]");
+    assert_eq!(closing_separator("===="), "====");
+}
+
+#[test]
+fn test_separator_shapes_default_template() {
+    let cfg = YekConfig {
+        separator_style: SeparatorStyle::Fence,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.effective_output_template(),
+        ">>>> FILE_PATH\nFILE_CONTENT\n<<<< FILE_PATH"
+    );
+
+    let cfg = YekConfig {
+        separator: "#=>".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.effective_output_template(),
+        "#=> FILE_PATH\nFILE_CONTENT"
+    );
+
+    // Backslashes in the separator survive unescaping
+    let cfg = YekConfig {
+        separator: r"\\".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.effective_output_template(),
+        "\\\\\\\\ FILE_PATH\nFILE_CONTENT"
+    );
+}
+
+#[test]
+fn test_show_mtime_extends_preset_templates() {
+    let mtime = |format| YekConfig {
+        show_mtime: true,
+        format,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        mtime(OutputFormat::Text).effective_output_template(),
+        ">>>> FILE_PATH (modified FILE_MTIME)\nFILE_CONTENT"
+    );
+    assert!(mtime(OutputFormat::Markdown)
+        .effective_output_template()
+        .starts_with("## FILE_PATH\n\nModified FILE_MTIME\n\n```"));
+    assert!(mtime(OutputFormat::Xml)
+        .effective_output_template()
+        .starts_with("<file path=\"FILE_PATH\" mtime=\"FILE_MTIME\">"));
+
+    // A custom template is used as given
+    let cfg = YekConfig {
+        show_mtime: true,
+        output_template: "FILE_PATH\nFILE_CONTENT".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(cfg.effective_output_template(), "FILE_PATH\nFILE_CONTENT");
+}
+
+#[test]
+fn test_header_and_content_templates() {
+    let cfg = YekConfig {
+        header_template: Some("==> FILE_PATH (FILE_SIZE) <==".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.effective_output_template(),
+        "==> FILE_PATH (FILE_SIZE) <==\nFILE_CONTENT"
+    );
+    let cfg = YekConfig {
+        content_template: Some("FILE_CONTENT\\n---".to_string()),
+        ..cfg
+    };
+    assert_eq!(
+        cfg.effective_output_template(),
+        "==> FILE_PATH (FILE_SIZE) <==\nFILE_CONTENT\\n---"
+    );
+
+    // A content template alone keeps the header of the preset
+    let cfg = YekConfig {
+        content_template: Some("<<FILE_CONTENT>>".to_string()),
+        format: OutputFormat::Markdown,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.effective_output_template(),
+        "## FILE_PATH\n\n```FILE_LANG\n<<FILE_CONTENT>>\n```\n"
+    );
+    let cfg = YekConfig {
+        content_template: Some("<<FILE_CONTENT>>".to_string()),
+        separator: "====".to_string(),
+        show_mtime: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.effective_output_template(),
+        "==== FILE_PATH (modified FILE_MTIME)\n<<FILE_CONTENT>>"
+    );
+}
+
+#[test]
+fn test_validate_header_and_content_templates() {
+    let err = |cfg: YekConfig| cfg.validate().unwrap_err().to_string();
+    let header = |template: &str| YekConfig {
+        header_template: Some(template.to_string()),
+        ..YekConfig::default()
+    };
+    assert!(header("# FILE_INDEX/FILE_COUNT FILE_PATH\\n")
+        .validate()
+        .is_ok());
+    assert_eq!(
+        err(header("FILE_PATH FILE_CONTENT")),
+        "header_template: must contain FILE_PATH and not FILE_CONTENT \
+         (use --content-template for the body)"
+    );
+    assert_eq!(
+        err(header("FILE_PATH \\q")),
+        "header_template: Unknown escape sequence '\\q' (use \\\\ for a backslash)"
+    );
+    assert_eq!(
+        err(YekConfig {
+            format: OutputFormat::Markdown,
+            ..header("FILE_PATH")
+        }),
+        "header_template: only applies to --format text"
+    );
+    assert_eq!(
+        err(YekConfig {
+            separator: "====".to_string(),
+            ..header("FILE_PATH")
+        }),
+        "header_template: cannot be combined with --separator or --separator-style"
+    );
+    assert_eq!(
+        err(YekConfig {
+            output_template: "FILE_PATH FILE_CONTENT".to_string(),
+            ..header("FILE_PATH")
+        }),
+        "header_template: cannot be combined with --output-template"
+    );
+
+    let content = YekConfig {
+        content_template: Some("FILE_PATH".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(err(content), "content_template: must contain FILE_CONTENT");
+}
+
+#[test]
+fn test_validate_mtime_format() {
+    let cfg = YekConfig {
+        mtime_format: "%Y-%m-%d %H:%M".to_string(),
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+    let cfg = YekConfig {
+        mtime_format: "%Q".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "mtime_format: Invalid format '%Q'"
+    );
+}
+
+#[test]
+fn test_validate_separator_conflicts() {
+    let cfg = YekConfig {
+        separator_style: SeparatorStyle::Fence,
+        output_template: "FILE_PATH FILE_CONTENT".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "separator: cannot be combined with a custom --output-template"
+    );
+
+    let cfg = YekConfig {
+        separator: "@@".to_string(),
+        format: OutputFormat::Markdown,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "separator: only applies to --format text"
+    );
+
+    let cfg = YekConfig {
+        separator: " ".to_string(),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "separator: must be non-empty and fit on one line"
+    );
+
+    let cfg = YekConfig {
+        separator: "@@".to_string(),
+        separator_style: SeparatorStyle::Fence,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
 #[test]
 fn test_validate_max_size_zero() {
     let cfg = YekConfig {
@@ -596,3 +828,945 @@ fn test_is_text_file_with_binary_content() {
         "Expected a binary file to be detected as binary"
     );
 }
+
+#[test]
+fn test_validate_unknown_model() {
+    let cfg = YekConfig {
+        model: Some("gpt-99".to_string()),
+        ..YekConfig::default()
+    };
+    let err = cfg.validate().unwrap_err().to_string();
+    assert!(err.starts_with("model: Unknown model 'gpt-99'"));
+    assert!(err.contains("gpt-4o"));
+    assert!(err.contains("claude-3-5-sonnet"));
+    assert!(err.contains("gemini-1.5-pro"));
+}
+
+#[test]
+fn test_model_preset_sets_token_budget() {
+    let mut cfg = YekConfig {
+        model: Some("claude-3-5-sonnet".to_string()),
+        ..YekConfig::default()
+    };
+    cfg.apply_model_preset();
+    assert_eq!(cfg.tokens, "200000");
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_model_preset_is_case_insensitive() {
+    let mut cfg = YekConfig {
+        model: Some("GPT-4o".to_string()),
+        ..YekConfig::default()
+    };
+    cfg.apply_model_preset();
+    assert_eq!(cfg.tokens, "128000");
+    assert_eq!(cfg.tokenizer(), Tokenizer::O200k);
+}
+
+#[test]
+fn test_explicit_tokens_override_model_preset() {
+    let mut cfg = YekConfig {
+        model: Some("gpt-4o".to_string()),
+        tokens: "50k".to_string(),
+        ..YekConfig::default()
+    };
+    cfg.apply_model_preset();
+    assert_eq!(cfg.tokens, "50k");
+    // The tokenizer still follows the model
+    assert_eq!(cfg.tokenizer(), Tokenizer::O200k);
+}
+
+#[test]
+fn test_default_tokenizer_without_model() {
+    let cfg = YekConfig::default();
+    assert_eq!(cfg.tokenizer(), Tokenizer::Cl100k);
+}
+
+#[test]
+fn test_validate_invalid_priority_weight() {
+    let cfg = YekConfig {
+        priority: vec!["README.md".to_string()],
+        ..YekConfig::default()
+    };
+    let err = cfg.validate().unwrap_err().to_string();
+    assert_eq!(
+        err,
+        "priority: Invalid rule 'README.md': expected <glob>:<weight>"
+    );
+}
+
+#[test]
+fn test_validate_invalid_include_pattern() {
+    let cfg = YekConfig {
+        include: vec!["src/[".to_string()],
+        ..YekConfig::default()
+    };
+    let err = cfg.validate().unwrap_err().to_string();
+    assert!(err.starts_with("include: Invalid pattern 'src/['"));
+
+    let cfg = YekConfig {
+        filter: vec!["src/**".to_string(), "!src/[".to_string()],
+        ..YekConfig::default()
+    };
+    let err = cfg.validate().unwrap_err().to_string();
+    assert!(
+        err.starts_with("filter: Invalid pattern '!src/['"),
+        "{}",
+        err
+    );
+
+    let cfg = YekConfig {
+        only: vec!["docs/[".to_string()],
+        ..YekConfig::default()
+    };
+    let err = cfg.validate().unwrap_err().to_string();
+    assert!(err.starts_with("only: Invalid pattern 'docs/['"));
+}
+
+#[test]
+fn test_validate_fill_priority_with_budget() {
+    let cfg = YekConfig {
+        fill: BudgetFill::Priority,
+        budget: vec!["docs/**:20".to_string()],
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "fill: priority cannot be combined with --budget"
+    );
+    let cfg = YekConfig {
+        budget: Vec::new(),
+        ..cfg
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_validate_extensions() {
+    let cfg = YekConfig {
+        ext: vec!["rs,.toml".to_string()],
+        no_ext: vec!["min.js".to_string()],
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+
+    let cfg = YekConfig {
+        ext: vec!["rs,src/*.rs".to_string()],
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "ext: Invalid extension 'src/*.rs'"
+    );
+    let cfg = YekConfig {
+        no_ext: vec!["*".to_string()],
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "no_ext: Invalid extension '*'"
+    );
+}
+
+#[test]
+fn test_validate_max_files_zero() {
+    let cfg = YekConfig {
+        max_files: Some(0),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "max_files: cannot be 0"
+    );
+}
+
+#[test]
+fn test_confirm_over_size() {
+    let cfg = YekConfig {
+        confirm_over: Some("5MB".to_string()),
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+    assert_eq!(cfg.confirm_over_bytes(), Some(5_000_000));
+    assert_eq!(YekConfig::default().confirm_over_bytes(), None);
+
+    let cfg = YekConfig {
+        confirm_over: Some("lots".to_string()),
+        ..YekConfig::default()
+    };
+    let err = cfg.validate().unwrap_err().to_string();
+    assert!(
+        err.starts_with("confirm_over: Invalid size format"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_validate_invalid_max_file_size() {
+    let cfg = YekConfig {
+        max_file_size: Some("huge".to_string()),
+        ..YekConfig::default()
+    };
+    let err = cfg.validate().unwrap_err().to_string();
+    assert!(
+        err.starts_with("max_file_size: Invalid size format"),
+        "{}",
+        err
+    );
+
+    let cfg = YekConfig {
+        max_file_size: Some("1KB".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(cfg.max_file_size_bytes(), Some(1000));
+}
+
+#[test]
+fn test_validate_outlier_settings() {
+    let cfg = YekConfig {
+        outlier_percentile: 0,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "outlier_percentile: must be between 1 and 100"
+    );
+
+    let cfg = YekConfig {
+        outlier_percentile: 101,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_err());
+
+    let cfg = YekConfig {
+        outlier_factor: 0.0,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "outlier_factor: must be greater than 0"
+    );
+
+    let cfg = YekConfig {
+        drop_outliers: true,
+        outlier_percentile: 90,
+        outlier_factor: 1.5,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_validate_generated_line_length() {
+    let cfg = YekConfig {
+        skip_generated: true,
+        generated_line_length: 0,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "generated_line_length: cannot be 0"
+    );
+}
+
+#[test]
+fn test_validate_order_with_sort() {
+    let cfg = YekConfig {
+        sort: Some(TreeSort::Size),
+        order: OutputOrder::Tree,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "order: cannot be combined with --sort"
+    );
+
+    // The default order leaves --sort in charge
+    let cfg = YekConfig {
+        sort: Some(TreeSort::Size),
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_validate_max_line_length() {
+    let cfg = YekConfig {
+        max_line_length: Some(0),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "max_line_length: cannot be 0"
+    );
+
+    let cfg = YekConfig {
+        wrap: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "wrap: requires --max-line-length"
+    );
+}
+
+#[test]
+fn test_validate_toc() {
+    let cfg = YekConfig {
+        toc: true,
+        format: OutputFormat::Markdown,
+        tree_header: true,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+
+    for (cfg, message) in [
+        (
+            YekConfig {
+                toc: true,
+                ..YekConfig::default()
+            },
+            "toc: requires --format markdown",
+        ),
+        (
+            YekConfig {
+                toc: true,
+                format: OutputFormat::Markdown,
+                output_template: "# FILE_PATH\nFILE_CONTENT".to_string(),
+                ..YekConfig::default()
+            },
+            "toc: cannot be combined with --output-template",
+        ),
+        (
+            YekConfig {
+                toc: true,
+                format: OutputFormat::Markdown,
+                split_output: Some("chunks".to_string()),
+                ..YekConfig::default()
+            },
+            "toc: cannot be combined with split_output",
+        ),
+    ] {
+        assert_eq!(cfg.validate().unwrap_err().to_string(), message);
+    }
+}
+
+#[test]
+fn test_validate_root_name() {
+    for (name, message) in [
+        ("", "root_name: cannot be empty"),
+        ("a\nb", "root_name: must fit on one line"),
+    ] {
+        let cfg = YekConfig {
+            root_name: Some(name.to_string()),
+            ..YekConfig::default()
+        };
+        assert_eq!(cfg.validate().unwrap_err().to_string(), message);
+    }
+    let cfg = YekConfig {
+        root_name: Some("myproject".to_string()),
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_validate_tree_indent_and_connectors() {
+    let cfg = YekConfig {
+        tree_indent: Some(1),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "tree_indent: must be at least 2"
+    );
+    let cfg = YekConfig {
+        tree_connectors: Some(TreeConnectors {
+            vertical: Some("|\n".to_string()),
+            ..Default::default()
+        }),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "tree_connectors: must fit on one line"
+    );
+}
+
+#[test]
+fn test_apply_config_file_tree_connectors() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("yek.yaml");
+    fs::write(
+        &path,
+        "tree_connectors:\n  branch: '+-- '\n  last: '\\-- '\n",
+    )
+    .unwrap();
+
+    let mut config = YekConfig::default();
+    config.apply_config_file(&path, &[]).unwrap();
+    assert_eq!(
+        config.tree_connectors,
+        Some(TreeConnectors {
+            branch: Some("+-- ".to_string()),
+            last: Some("\\-- ".to_string()),
+            ..Default::default()
+        })
+    );
+
+    // Misspelled connectors are reported rather than ignored
+    fs::write(&path, "tree_connectors:\n  brnach: '+-- '\n").unwrap();
+    let err = YekConfig::default()
+        .apply_config_file(&path, &[])
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("unknown field `brnach`"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_validate_headers_only() {
+    let cfg = YekConfig {
+        headers_only: true,
+        tree_header: true,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+
+    for (cfg, message) in [
+        (
+            YekConfig {
+                headers_only: true,
+                tree_only: true,
+                ..YekConfig::default()
+            },
+            "headers_only: cannot be combined with --tree-only",
+        ),
+        (
+            YekConfig {
+                headers_only: true,
+                json: true,
+                ..YekConfig::default()
+            },
+            "headers_only: cannot be combined with JSON output",
+        ),
+        (
+            YekConfig {
+                headers_only: true,
+                split_output: Some("chunks".to_string()),
+                ..YekConfig::default()
+            },
+            "headers_only: cannot be combined with split_output",
+        ),
+    ] {
+        assert_eq!(cfg.validate().unwrap_err().to_string(), message);
+    }
+}
+
+#[test]
+fn test_validate_git_header() {
+    let cfg = YekConfig {
+        git_header: true,
+        tree_only: true,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+
+    for (cfg, message) in [
+        (
+            YekConfig {
+                git_header: true,
+                format: OutputFormat::Xml,
+                ..YekConfig::default()
+            },
+            "git_header: cannot be combined with JSON or XML output",
+        ),
+        (
+            YekConfig {
+                git_header: true,
+                json_lines: true,
+                ..YekConfig::default()
+            },
+            "git_header: cannot be combined with JSON or XML output",
+        ),
+        (
+            YekConfig {
+                git_header: true,
+                split_output: Some("chunks".to_string()),
+                ..YekConfig::default()
+            },
+            "git_header: cannot be combined with split_output",
+        ),
+    ] {
+        assert_eq!(cfg.validate().unwrap_err().to_string(), message);
+    }
+}
+
+#[test]
+fn test_validate_diff_tree() {
+    let cfg = YekConfig {
+        diff_tree: Some("main".to_string()),
+        tree_sizes: true,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+
+    for (cfg, message) in [
+        (
+            YekConfig {
+                diff_tree: Some("main".to_string()),
+                tree_only: true,
+                ..YekConfig::default()
+            },
+            "diff_tree: cannot be combined with --tree-only, --count-only, --dry-run or \
+             --lang-stats-only",
+        ),
+        (
+            YekConfig {
+                diff_tree: Some("main".to_string()),
+                json: true,
+                ..YekConfig::default()
+            },
+            "diff_tree: cannot be combined with JSON output, split_output or --watch",
+        ),
+        (
+            YekConfig {
+                diff_tree: Some("main".to_string()),
+                input_paths: vec!["src".to_string(), "tests".to_string()],
+                ..YekConfig::default()
+            },
+            "diff_tree: requires a single input directory",
+        ),
+    ] {
+        assert_eq!(cfg.validate().unwrap_err().to_string(), message);
+    }
+}
+
+#[test]
+fn test_validate_redact_patterns() {
+    let cfg = YekConfig {
+        redact: vec!["token=\\w+".to_string(), "(unclosed".to_string()],
+        ..YekConfig::default()
+    };
+    let err = cfg.validate().unwrap_err().to_string();
+    assert!(
+        err.starts_with("redact: Invalid pattern '(unclosed':"),
+        "{}",
+        err
+    );
+
+    let cfg = YekConfig {
+        redact: vec!["token=\\w+".to_string()],
+        redact_default: true,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+    assert_eq!(cfg.redact_patterns().unwrap().len(), 7);
+}
+
+#[test]
+fn test_validate_json_format_conflicts() {
+    let cfg = YekConfig {
+        format: OutputFormat::Json,
+        json: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "format: json cannot be combined with --json"
+    );
+
+    let cfg = YekConfig {
+        format: OutputFormat::Json,
+        split_output: Some("chunks".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "format: json cannot be combined with split_output"
+    );
+
+    // Unlike --json, the document can carry the tree
+    let cfg = YekConfig {
+        format: OutputFormat::Json,
+        tree_header: true,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_validate_xml_format_conflicts() {
+    let cfg = YekConfig {
+        format: OutputFormat::Xml,
+        json: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "format: xml cannot be combined with --json or --json-lines"
+    );
+
+    let cfg = YekConfig {
+        format: OutputFormat::Xml,
+        split_output: Some("chunks".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "format: xml cannot be combined with split_output"
+    );
+
+    let cfg = YekConfig {
+        format: OutputFormat::Xml,
+        tree_header: true,
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_validate_json_lines_conflicts() {
+    let cfg = YekConfig {
+        json_lines: true,
+        json: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "json_lines: cannot be combined with --json or --format json"
+    );
+
+    let cfg = YekConfig {
+        json_lines: true,
+        split_output: Some("chunks".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "json_lines: cannot be combined with split_output"
+    );
+}
+
+#[test]
+fn test_validate_tree_mark_dropped_with_matches_output() {
+    let cfg = YekConfig {
+        tree_mark_dropped: true,
+        tree_matches_output: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "tree_mark_dropped: cannot be combined with --tree-matches-output"
+    );
+}
+
+#[test]
+fn test_validate_interactive_with_watch() {
+    let cfg = YekConfig {
+        interactive: true,
+        watch: true,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "interactive: cannot be combined with --watch"
+    );
+}
+
+#[test]
+fn test_validate_watch_with_stdin_file_list() {
+    let cfg = YekConfig {
+        watch: true,
+        files_from: Some("-".to_string()),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "watch: cannot re-read --files-from from stdin; pass a file instead"
+    );
+
+    let cfg = YekConfig {
+        watch: true,
+        files_from: Some("files.txt".to_string()),
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn test_validate_budget_rules() {
+    let cfg = YekConfig {
+        budget: vec!["src/**:70%".to_string(), "tests/**:30".to_string()],
+        ..YekConfig::default()
+    };
+    assert!(cfg.validate().is_ok());
+    assert_eq!(cfg.budget_rules().len(), 2);
+
+    let cfg = YekConfig {
+        budget: vec!["src/**:70".to_string(), "tests/**:40".to_string()],
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "budget: Reservations add up to 110%, more than 100%"
+    );
+
+    let cfg = YekConfig {
+        budget: vec!["src/**".to_string()],
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "budget: Invalid rule 'src/**': expected <glob>:<percent>"
+    );
+}
+
+#[test]
+fn test_log_level_follows_quiet_and_verbose() {
+    use tracing::Level;
+    let level = |quiet: bool, verbose: u8, debug: bool| {
+        YekConfig {
+            quiet,
+            verbose,
+            debug,
+            ..YekConfig::default()
+        }
+        .log_level()
+    };
+    assert_eq!(level(false, 0, false), Level::INFO);
+    assert_eq!(level(true, 0, false), Level::ERROR);
+    assert_eq!(level(false, 1, false), Level::DEBUG);
+    assert_eq!(level(false, 0, true), Level::DEBUG);
+    assert_eq!(level(false, 2, false), Level::TRACE);
+    assert_eq!(level(false, 5, true), Level::TRACE);
+}
+
+#[test]
+fn test_validate_quiet_with_verbose() {
+    let cfg = YekConfig {
+        quiet: true,
+        verbose: 1,
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "verbose: cannot be combined with --quiet"
+    );
+}
+
+#[test]
+fn test_validate_jobs_zero() {
+    let cfg = YekConfig {
+        jobs: Some(0),
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "jobs: must be at least 1"
+    );
+}
+
+#[test]
+fn test_apply_config_file_sets_defaults() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("yek.toml");
+    fs::write(
+        &path,
+        r#"
+max_size = "2MB"
+output_template = "@@ FILE_PATH\nFILE_CONTENT"
+ignore_patterns = ["*.log"]
+tree_header = true
+
+[[priority_rules]]
+pattern = "^src/"
+score = 50
+"#,
+    )
+    .unwrap();
+
+    let mut config = YekConfig::default();
+    config.apply_config_file(&path, &[]).unwrap();
+    assert_eq!(config.max_size, "2MB");
+    assert_eq!(config.output_template, "@@ FILE_PATH\nFILE_CONTENT");
+    assert_eq!(config.ignore_patterns, vec!["*.log".to_string()]);
+    assert!(config.tree_header);
+    assert_eq!(
+        config.priority_rules,
+        vec![PriorityRule {
+            pattern: "^src/".to_string(),
+            score: 50
+        }]
+    );
+}
+
+#[test]
+fn test_apply_config_file_cli_wins() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("yek.yaml");
+    fs::write(&path, "max_size: 2MB\ntokens: \"4k\"\n").unwrap();
+
+    // A flag on the command line is kept, even when it repeats the default
+    let mut config = YekConfig::default();
+    let args = ["yek", "--max-size", "10MB"].map(String::from);
+    config.apply_config_file(&path, &args).unwrap();
+    assert_eq!(config.max_size, "10MB");
+    assert_eq!(config.tokens, "4k");
+
+    // Positional paths after `--` are not flags
+    let mut config = YekConfig::default();
+    let args = ["yek", "--tokens=8k", "--", "--max-size"].map(String::from);
+    config.apply_config_file(&path, &args).unwrap();
+    assert_eq!(config.max_size, "2MB");
+    assert_eq!(config.tokens, "");
+}
+
+#[test]
+fn test_apply_config_file_extends_lists() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("yek.toml");
+    fs::write(
+        &path,
+        "ignore_patterns = [\"*.log\"]\nexclude = [\"docs/**\"]\n",
+    )
+    .unwrap();
+
+    // Patterns from the command line are added after the file's
+    let mut config = YekConfig {
+        ignore_patterns: vec!["*.tmp".to_string()],
+        ..Default::default()
+    };
+    let args = ["yek", "--ignore-patterns", "*.tmp"].map(String::from);
+    config.apply_config_file(&path, &args).unwrap();
+    assert_eq!(config.ignore_patterns, vec!["*.log", "*.tmp"]);
+    assert_eq!(config.exclude, vec!["docs/**"]);
+}
+
+#[test]
+fn test_command_line_settings() {
+    let args = [
+        "yek",
+        "--max-size",
+        "1MB",
+        "--tree-sizes",
+        "-qj4",
+        "--format=json",
+        "src",
+    ]
+    .map(String::from);
+    let settings = command_line_settings(&args);
+    let mut names: Vec<_> = settings.iter().map(String::as_str).collect();
+    names.sort();
+    assert_eq!(names, ["format", "jobs", "max_size", "quiet", "tree_sizes"]);
+}
+
+#[test]
+fn test_apply_config_file_unknown_key() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("yek.toml");
+    fs::write(&path, "max_sise = \"1MB\"\n").unwrap();
+
+    let err = YekConfig::default()
+        .apply_config_file(&path, &[])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("config: Unknown key 'max_sise' in '{}'", path.display())
+    );
+}
+
+#[test]
+fn test_apply_config_file_invalid_value() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("yek.json");
+    fs::write(&path, r#"{"tree_header": "yes"}"#).unwrap();
+
+    let err = YekConfig::default()
+        .apply_config_file(&path, &[])
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("config: Invalid value for 'tree_header'"));
+}
+
+#[test]
+fn test_apply_config_file_accepts_every_setting() {
+    // Every serialized setting (apart from computed ones) must be accepted in a config file
+    let defaults = serde_json::to_value(YekConfig::default()).unwrap();
+    let computed = [
+        "version",
+        "config",
+        "no_config",
+        "stream",
+        "token_mode",
+        "tree_color",
+        "output_file_full_path",
+    ];
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("yek.json");
+    for (key, value) in defaults.as_object().unwrap() {
+        if computed.contains(&key.as_str()) || value.is_null() {
+            continue;
+        }
+        fs::write(&path, serde_json::json!({ key: value }).to_string()).unwrap();
+        YekConfig::default()
+            .apply_config_file(&path, &[])
+            .unwrap_or_else(|e| panic!("{key}: {e}"));
+    }
+}
+
+#[test]
+fn test_env_ignore_patterns() {
+    assert_eq!(
+        env_ignore_patterns("*.log: target/**::docs/*.md:"),
+        vec!["*.log", "target/**", "docs/*.md"]
+    );
+    assert!(env_ignore_patterns("").is_empty());
+}
+
+#[test]
+fn test_find_config_file() {
+    let dir = TempDir::new().unwrap();
+    let config = YekConfig {
+        input_paths: vec![dir.path().to_string_lossy().to_string()],
+        ..YekConfig::default()
+    };
+
+    // Explicit paths win, --no-config disables loading altogether
+    let explicit = YekConfig {
+        config: Some("custom.toml".to_string()),
+        ..config.clone()
+    };
+    assert_eq!(
+        explicit.find_config_file(),
+        Some(Path::new("custom.toml").to_path_buf())
+    );
+    let disabled = YekConfig {
+        no_config: true,
+        ..explicit
+    };
+    assert_eq!(disabled.find_config_file(), None);
+
+    // Discovered at the scan root when the current directory has none
+    fs::write(dir.path().join("yek.toml"), "").unwrap();
+    if !Path::new("yek.toml").exists() && !Path::new("yek.yaml").exists() {
+        assert_eq!(config.find_config_file(), Some(dir.path().join("yek.toml")));
+    }
+}
+
+#[test]
+fn test_validate_invalid_truncate_rule() {
+    let cfg = YekConfig {
+        truncate: vec!["*.lock".to_string()],
+        ..YekConfig::default()
+    };
+    assert_eq!(
+        cfg.validate().unwrap_err().to_string(),
+        "truncate: Invalid rule '*.lock': expected <glob>:<lines>"
+    );
+}
@@ -0,0 +1,82 @@
+use yek::encoding::{decode_content, decode_content_lossy, encoding_for_label};
+
+fn utf16le(text: &str, bom: bool) -> Vec<u8> {
+    let mut bytes = if bom { vec![0xFF, 0xFE] } else { Vec::new() };
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn test_decode_plain_utf8() {
+    assert_eq!(decode_content("héllo".as_bytes(), None).unwrap(), "héllo");
+}
+
+#[test]
+fn test_decode_strips_utf8_bom() {
+    let bytes = [&[0xEF, 0xBB, 0xBF][..], "fn main() {}".as_bytes()].concat();
+    assert_eq!(decode_content(&bytes, None).unwrap(), "fn main() {}");
+}
+
+#[test]
+fn test_decode_utf16_with_bom() {
+    let bytes = utf16le("hello wörld", true);
+    assert_eq!(decode_content(&bytes, None).unwrap(), "hello wörld");
+
+    let mut be = vec![0xFE, 0xFF];
+    for unit in "héllo".encode_utf16() {
+        be.extend_from_slice(&unit.to_be_bytes());
+    }
+    assert_eq!(decode_content(&be, None).unwrap(), "héllo");
+}
+
+#[test]
+fn test_decode_detects_latin1() {
+    // "café à la crème" in ISO-8859-1 / windows-1252
+    let bytes = b"caf\xe9 \xe0 la cr\xe8me, d\xe9j\xe0 vu, tr\xe8s \xe9l\xe9gant";
+    assert_eq!(
+        decode_content(bytes, None).unwrap(),
+        "café à la crème, déjà vu, très élégant"
+    );
+}
+
+#[test]
+fn test_decode_forced_encoding() {
+    let latin1 = encoding_for_label("latin1").unwrap();
+    assert_eq!(decode_content(b"na\xefve", Some(latin1)).unwrap(), "naïve");
+
+    let utf16 = encoding_for_label("utf-16le").unwrap();
+    assert_eq!(
+        decode_content(&utf16le("no bom", false), Some(utf16)).unwrap(),
+        "no bom"
+    );
+    // A matching BOM is still stripped
+    assert_eq!(
+        decode_content(&utf16le("bom", true), Some(utf16)).unwrap(),
+        "bom"
+    );
+}
+
+#[test]
+fn test_decode_invalid_bytes_is_error() {
+    let utf8 = encoding_for_label("utf-8").unwrap();
+    let err = decode_content(b"bad \xff\xfe bytes", Some(utf8)).unwrap_err();
+    assert_eq!(err.to_string(), "could not decode as UTF-8");
+}
+
+#[test]
+fn test_decode_lossy_replaces_invalid_bytes() {
+    let utf8 = encoding_for_label("utf-8").unwrap();
+    assert_eq!(
+        decode_content_lossy(b"bad \xff\xfe bytes", Some(utf8)),
+        "bad \u{FFFD}\u{FFFD} bytes"
+    );
+    assert_eq!(decode_content_lossy("fine".as_bytes(), None), "fine");
+}
+
+#[test]
+fn test_unknown_encoding_label() {
+    let err = encoding_for_label("klingon").unwrap_err();
+    assert_eq!(err.to_string(), "encoding: Unknown encoding 'klingon'");
+}
@@ -0,0 +1,133 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::{fs, path::Path};
+use tempfile::tempdir;
+use yek::config::YekConfig;
+use yek::gitattributes::{parse_export_ignore, ExportIgnore};
+use yek::serialize_repo;
+
+fn ignored(text: &str, path: &str) -> bool {
+    let root = Path::new("/repo");
+    parse_export_ignore(root, text)
+        .unwrap()
+        .matched_path_or_any_parents(root.join(path), false)
+        .is_ignore()
+}
+
+#[test]
+fn test_parse_export_ignore() {
+    let text = "\
+# Keep these out of release archives
+/tests export-ignore
+*.snap   export-ignore diff
+docs/internal/** export-ignore
+*.rs text eol=lf
+";
+    assert!(ignored(text, "tests/e2e.rs"));
+    assert!(ignored(text, "src/ui/view.snap"));
+    assert!(ignored(text, "docs/internal/plan.md"));
+    assert!(!ignored(text, "src/tests/mod.rs"));
+    assert!(!ignored(text, "src/main.rs"));
+    assert!(!ignored(text, "docs/guide.md"));
+}
+
+#[test]
+fn test_parse_export_ignore_unset_and_skipped_lines() {
+    let text = "\
+[attr]internal export-ignore
+*.md export-ignore
+README.md -export-ignore
+CHANGELOG.md !export-ignore
+!LICENSE export-ignore
+notes.txt export-ignore -export-ignore
+";
+    assert!(ignored(text, "docs/guide.md"));
+    assert!(!ignored(text, "README.md"));
+    assert!(!ignored(text, "CHANGELOG.md"));
+    assert!(!ignored(text, "LICENSE"));
+    assert!(!ignored(text, "notes.txt"));
+
+    // Files that never mention the attribute have nothing to match
+    assert!(parse_export_ignore(Path::new("/repo"), "*.rs diff=rust\n").is_none());
+    assert!(parse_export_ignore(Path::new("/repo"), "").is_none());
+}
+
+#[test]
+fn test_nested_gitattributes_take_precedence() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("docs/public")).unwrap();
+    fs::write(root.join(".gitattributes"), "docs export-ignore\n").unwrap();
+    fs::write(
+        root.join("docs/public/.gitattributes"),
+        "*.md -export-ignore\n",
+    )
+    .unwrap();
+
+    let export_ignore = ExportIgnore::new(root, &YekConfig::default());
+    assert!(export_ignore.ignores(&root.join("docs/draft.md")));
+    assert!(!export_ignore.ignores(&root.join("docs/public/guide.md")));
+    assert!(export_ignore.ignores(&root.join("docs/public/image.svg")));
+    assert!(!export_ignore.ignores(&root.join("src/main.rs")));
+
+    let config = YekConfig {
+        no_export_ignore: true,
+        ..Default::default()
+    };
+    assert!(!ExportIgnore::new(root, &config).ignores(&root.join("docs/draft.md")));
+}
+
+#[test]
+fn test_export_ignore_composes_with_ignore_files() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("tests")).unwrap();
+    fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+    fs::write(root.join("tests/it.rs"), "#[test] fn it() {}\n").unwrap();
+    fs::write(root.join("bench.rs"), "// bench\n").unwrap();
+    fs::write(root.join("notes.md"), "notes\n").unwrap();
+    fs::write(root.join(".gitattributes"), "/tests export-ignore\n").unwrap();
+    fs::write(root.join(".yekignore"), "bench.rs\n").unwrap();
+    // An ignore file cannot bring back an export-ignore file
+    fs::write(root.join(".gitignore"), "notes.md\n!tests/it.rs\n").unwrap();
+
+    let config = YekConfig {
+        input_paths: vec![root.to_string_lossy().to_string()],
+        ..Default::default()
+    };
+    let (_, files) = serialize_repo(&config).unwrap();
+    let paths: Vec<&str> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    assert_eq!(paths, ["main.rs"]);
+
+    let config = YekConfig {
+        no_export_ignore: true,
+        ..config
+    };
+    let (_, files) = serialize_repo(&config).unwrap();
+    let paths: Vec<&str> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    assert_eq!(paths, ["main.rs", "tests/it.rs"]);
+}
+
+#[test]
+fn test_no_export_ignore_flag() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.path().join("fixture.json"), "{}\n").unwrap();
+    fs::write(dir.path().join(".gitattributes"), "*.json export-ignore\n").unwrap();
+
+    Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--tree-header", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fixture.json").not());
+
+    Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--no-export-ignore", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(">>>> fixture.json"));
+}
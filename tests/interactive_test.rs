@@ -0,0 +1,170 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use ratatui::crossterm::event::KeyCode;
+use std::fs;
+use tempfile::tempdir;
+use yek::config::YekConfig;
+use yek::interactive::{picker_files, Outcome, Picker, PickerFile, Row};
+use yek::serialize_repo;
+
+fn file(path: &str, size: u64) -> PickerFile {
+    PickerFile {
+        path: path.to_string(),
+        size,
+        tokens: size as usize / 4,
+        selected: true,
+    }
+}
+
+fn picker() -> Picker {
+    Picker::new(vec![
+        file("src/main.rs", 400),
+        file("README.md", 100),
+        file("src/util/io.rs", 200),
+        file("src/lib.rs", 300),
+    ])
+}
+
+#[test]
+fn test_picker_lays_out_a_tree() {
+    let picker = picker();
+    let labels: Vec<String> = picker.rows().iter().map(|r| picker.label(r)).collect();
+    assert_eq!(
+        labels,
+        [
+            "[x] README.md",
+            "[x] src/",
+            "  [x] lib.rs",
+            "  [x] main.rs",
+            "  [x] util/",
+            "    [x] io.rs",
+        ]
+    );
+    assert_eq!(
+        picker.rows()[4],
+        Row::Dir {
+            path: "src/util".to_string(),
+            depth: 1
+        }
+    );
+}
+
+#[test]
+fn test_picker_toggles_files_and_directories() {
+    let mut picker = picker();
+    // Uncheck src/lib.rs: src/ becomes partially selected
+    picker.handle_key(KeyCode::Down);
+    picker.handle_key(KeyCode::Down);
+    picker.handle_key(KeyCode::Char(' '));
+    assert_eq!(picker.label(&picker.rows()[1]), "[-] src/");
+    assert_eq!(
+        picker.selected_paths(),
+        ["README.md", "src/main.rs", "src/util/io.rs"]
+    );
+
+    // Toggling a partially selected directory selects all of it, then clears it
+    picker.handle_key(KeyCode::Up);
+    picker.handle_key(KeyCode::Char(' '));
+    assert_eq!(picker.label(&picker.rows()[1]), "[x] src/");
+    picker.handle_key(KeyCode::Char(' '));
+    assert_eq!(picker.label(&picker.rows()[1]), "[ ] src/");
+    assert_eq!(picker.selected_paths(), ["README.md"]);
+
+    picker.handle_key(KeyCode::Char('a'));
+    assert_eq!(picker.selected_paths().len(), 4);
+    picker.handle_key(KeyCode::Char('n'));
+    assert!(picker.selected_paths().is_empty());
+}
+
+#[test]
+fn test_picker_running_total() {
+    let mut picker = picker();
+    assert_eq!(picker.summary(), "4/4 files, 1000 B, ~250 tokens");
+    picker.handle_key(KeyCode::Char(' '));
+    assert_eq!(picker.summary(), "3/4 files, 900 B, ~225 tokens");
+}
+
+#[test]
+fn test_picker_cursor_and_outcomes() {
+    let mut picker = picker();
+    assert_eq!(picker.handle_key(KeyCode::Up), None);
+    assert_eq!(picker.cursor(), 0);
+    picker.handle_key(KeyCode::End);
+    assert_eq!(picker.cursor(), 5);
+    picker.handle_key(KeyCode::Char('j'));
+    assert_eq!(picker.cursor(), 5);
+    picker.handle_key(KeyCode::Home);
+    assert_eq!(picker.cursor(), 0);
+
+    assert_eq!(picker.handle_key(KeyCode::Enter), Some(Outcome::Confirm));
+    assert_eq!(picker.handle_key(KeyCode::Esc), Some(Outcome::Cancel));
+    assert_eq!(picker.handle_key(KeyCode::Char('q')), Some(Outcome::Cancel));
+}
+
+#[test]
+fn test_picker_starts_from_what_would_be_serialized() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+    fs::write(root.join("skip.log"), "noise").unwrap();
+    fs::write(root.join("blob.dat"), [0u8, 159, 146, 150, 0, 1]).unwrap();
+    let config = YekConfig {
+        ignore_patterns: vec!["*.log".to_string()],
+        ..YekConfig::extend_config_with_defaults(
+            vec![root.to_string_lossy().to_string()],
+            root.join("out").to_string_lossy().to_string(),
+        )
+    };
+
+    let files = picker_files(&config).unwrap();
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, [root.join("main.rs").to_string_lossy()]);
+    assert!(files[0].selected);
+    assert_eq!(files[0].size, 12);
+}
+
+#[test]
+fn test_picked_files_match_files_from() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    fs::create_dir(root.join("src")).unwrap();
+    fs::write(root.join("src/lib.rs"), "// lib\n").unwrap();
+    fs::write(root.join("main.rs"), "// main\n").unwrap();
+    let list = vec![
+        root.join("src/lib.rs").to_string_lossy().to_string(),
+        root.join("main.rs").to_string_lossy().to_string(),
+    ];
+    fs::write(root.join("files.txt"), list.join("\n")).unwrap();
+
+    let base = YekConfig {
+        tree_header: true,
+        ..YekConfig::default()
+    };
+    let picked = YekConfig {
+        picked_files: Some(list),
+        ..base.clone()
+    };
+    let from_file = YekConfig {
+        files_from: Some(root.join("files.txt").to_string_lossy().to_string()),
+        ..base
+    };
+    assert_eq!(
+        serialize_repo(&picked).unwrap().0,
+        serialize_repo(&from_file).unwrap().0
+    );
+}
+
+#[test]
+fn test_interactive_requires_a_terminal() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("main.rs"), "fn main() {}").unwrap();
+    Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(temp.path())
+        .args(["--interactive", "."])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "interactive: needs a terminal on stdin and stderr",
+        ));
+}
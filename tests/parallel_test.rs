@@ -6,8 +6,10 @@ use std::io::Write;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use tempfile::tempdir;
-use yek::config::YekConfig;
-use yek::parallel::process_files_parallel;
+use yek::config::{InvalidUtf8, YekConfig};
+use yek::parallel::{
+    has_extension, normalize_path, parse_extensions, process_files_parallel, to_forward_slashes,
+};
 
 #[test]
 fn test_normalize_path_unix_style() {
@@ -49,6 +51,20 @@ fn test_normalize_path_windows_style() {
     assert_eq!(normalized, expected_normalized);
 }
 
+#[test]
+fn test_to_forward_slashes_mixed_separators() {
+    assert_eq!(to_forward_slashes(r"src\util/mod.rs"), "src/util/mod.rs");
+    assert_eq!(
+        to_forward_slashes(r"\\?\C:\repo\src\lib.rs"),
+        "C:/repo/src/lib.rs"
+    );
+    assert_eq!(to_forward_slashes("src/lib.rs"), "src/lib.rs");
+    assert_eq!(
+        normalize_path(Path::new(r"repo/src\util/mod.rs"), Path::new("repo")),
+        "src/util/mod.rs"
+    );
+}
+
 #[test]
 fn test_process_files_parallel_empty() {
     let temp_dir = tempdir().expect("failed to create temp dir");
@@ -238,3 +254,484 @@ mod tests {
         Ok(())
     }
 }
+
+fn filter_fixture() -> tempfile::TempDir {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("src/gen")).unwrap();
+    fs::create_dir_all(temp_dir.path().join("docs")).unwrap();
+    fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(temp_dir.path().join("src/gen/api.rs"), "// generated").unwrap();
+    fs::write(temp_dir.path().join("docs/guide.md"), "# Guide").unwrap();
+    fs::write(temp_dir.path().join("build.rs"), "fn main() {}").unwrap();
+    temp_dir
+}
+
+fn processed_paths(base: &Path, config: &YekConfig) -> Vec<String> {
+    let mut paths: Vec<String> = process_files_parallel(base, config, &HashMap::new())
+        .expect("process_files_parallel failed")
+        .into_iter()
+        .map(|f| f.rel_path)
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn test_include_globs_limit_files() {
+    let temp_dir = filter_fixture();
+    let config = YekConfig {
+        include: vec!["*.rs".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["build.rs", "src/gen/api.rs", "src/main.rs"]
+    );
+}
+
+#[test]
+fn test_exclude_globs_remove_files() {
+    let temp_dir = filter_fixture();
+    let config = YekConfig {
+        exclude: vec!["src/gen/".to_string(), "*.md".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["build.rs", "src/main.rs"]
+    );
+}
+
+#[test]
+fn test_exclude_wins_over_include() {
+    let temp_dir = filter_fixture();
+    let config = YekConfig {
+        include: vec!["src/**".to_string()],
+        exclude: vec!["gen/".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["src/main.rs"]
+    );
+}
+
+#[test]
+fn test_filter_patterns_apply_in_order() {
+    let temp_dir = filter_fixture();
+    let filtered = |filter: &[&str]| {
+        let config = YekConfig {
+            filter: filter.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        };
+        processed_paths(temp_dir.path(), &config)
+    };
+
+    assert_eq!(filtered(&["src/**", "!src/gen/**"]), vec!["src/main.rs"]);
+    // The last matching pattern decides, so a later pattern can bring files back
+    assert_eq!(
+        filtered(&["src/**", "!src/gen/**", "*.rs"]),
+        vec!["build.rs", "src/gen/api.rs", "src/main.rs"]
+    );
+    // Only negations: everything else stays, and directory patterns cover their contents
+    assert_eq!(
+        filtered(&["!gen/", "!*.md"]),
+        vec!["build.rs", "src/main.rs"]
+    );
+}
+
+#[test]
+fn test_include_exclude_apply_to_single_files() {
+    let temp_dir = filter_fixture();
+    let config = YekConfig {
+        exclude: vec!["build.rs".to_string()],
+        ..Default::default()
+    };
+    assert!(processed_paths(&temp_dir.path().join("build.rs"), &config).is_empty());
+
+    let config = YekConfig {
+        include: vec!["*.md".to_string()],
+        ..Default::default()
+    };
+    assert!(processed_paths(&temp_dir.path().join("build.rs"), &config).is_empty());
+}
+
+#[test]
+fn test_parse_extensions() {
+    assert_eq!(
+        parse_extensions(&["rs, .TOML".to_string(), "min.js,,".to_string()]),
+        vec!["rs", "toml", "min.js"]
+    );
+    assert!(parse_extensions(&[",".to_string()]).is_empty());
+}
+
+#[test]
+fn test_has_extension() {
+    let exts = parse_extensions(&["rs,min.js".to_string()]);
+    assert!(has_extension(Path::new("src/main.rs"), &exts));
+    assert!(has_extension(Path::new("src/MAIN.RS"), &exts));
+    assert!(has_extension(Path::new("dist/app.min.js"), &exts));
+    assert!(!has_extension(Path::new("dist/app.js"), &exts));
+    assert!(!has_extension(Path::new("src/main.rsx"), &exts));
+    assert!(!has_extension(Path::new("dist/appmin.js"), &exts));
+    // A dotfile's name is not an extension
+    assert!(!has_extension(Path::new(".rs"), &exts));
+    assert!(!has_extension(Path::new("src/rs"), &exts));
+}
+
+#[test]
+fn test_ext_filters_files() {
+    let temp_dir = filter_fixture();
+    fs::write(temp_dir.path().join("Cargo.lock"), "# lock").unwrap();
+    let config = YekConfig {
+        ext: vec!["md,lock".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["Cargo.lock", "docs/guide.md"]
+    );
+
+    let config = YekConfig {
+        no_ext: vec!["lock".to_string(), "rs".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["docs/guide.md"]
+    );
+}
+
+#[test]
+fn test_ext_composes_with_include_and_no_ext() {
+    let temp_dir = filter_fixture();
+    // Both --include and --ext must match
+    let config = YekConfig {
+        include: vec!["src/**".to_string()],
+        ext: vec!["rs".to_string(), "md".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["src/gen/api.rs", "src/main.rs"]
+    );
+
+    // --no-ext wins over --ext, and applies to single files too
+    let config = YekConfig {
+        ext: vec!["rs".to_string()],
+        no_ext: vec!["rs".to_string()],
+        ..Default::default()
+    };
+    assert!(processed_paths(temp_dir.path(), &config).is_empty());
+    let config = YekConfig {
+        ext: vec!["md".to_string()],
+        ..Default::default()
+    };
+    assert!(processed_paths(&temp_dir.path().join("build.rs"), &config).is_empty());
+}
+
+#[test]
+fn test_yekignore_at_root() {
+    let temp_dir = filter_fixture();
+    fs::write(temp_dir.path().join(".yekignore"), "docs/\n").unwrap();
+    let config = YekConfig::default();
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["build.rs", "src/gen/api.rs", "src/main.rs"]
+    );
+}
+
+#[test]
+fn test_nested_yekignore_scopes_to_subtree() {
+    let temp_dir = filter_fixture();
+    // "*.rs" in src/gen only affects that directory
+    fs::write(temp_dir.path().join("src/gen/.yekignore"), "*.rs\n").unwrap();
+    let config = YekConfig::default();
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["build.rs", "docs/guide.md", "src/main.rs"]
+    );
+}
+
+#[test]
+fn test_yekignore_composes_with_gitignore() {
+    let temp_dir = filter_fixture();
+    fs::write(temp_dir.path().join(".gitignore"), "docs/\n").unwrap();
+    fs::write(temp_dir.path().join(".yekignore"), "build.rs\n").unwrap();
+    let config = YekConfig::default();
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["src/gen/api.rs", "src/main.rs"]
+    );
+}
+
+#[test]
+fn test_no_yekignore_disables_it() {
+    let temp_dir = filter_fixture();
+    fs::write(temp_dir.path().join(".yekignore"), "docs/\nsrc/\n").unwrap();
+    let config = YekConfig {
+        no_yekignore: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["build.rs", "docs/guide.md", "src/gen/api.rs", "src/main.rs"]
+    );
+}
+
+#[test]
+fn test_yekignore_applies_to_glob_inputs() {
+    let temp_dir = filter_fixture();
+    fs::write(temp_dir.path().join("src/.yekignore"), "gen/\n").unwrap();
+    let pattern = temp_dir.path().join("src/**/*.rs");
+    let config = YekConfig::default();
+    assert_eq!(processed_paths(&pattern, &config), vec!["main.rs"]);
+}
+
+#[test]
+fn test_named_file_bypasses_ignore_rules() {
+    let temp_dir = filter_fixture();
+    fs::write(temp_dir.path().join(".gitignore"), "build.rs\n").unwrap();
+    fs::write(temp_dir.path().join("docs/.yekignore"), "*.md\n").unwrap();
+    let config = YekConfig {
+        ignore_patterns: vec!["src/main.rs".to_string()],
+        ..Default::default()
+    };
+
+    // Walked, all three are ignored
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec!["src/gen/api.rs"]
+    );
+    // Named, each one is read
+    for path in ["build.rs", "docs/guide.md", "src/main.rs"] {
+        assert_eq!(
+            processed_paths(&temp_dir.path().join(path), &config).len(),
+            1,
+            "{}",
+            path
+        );
+    }
+    // A glob is not a name, so its matches still honor the rules
+    assert!(processed_paths(&temp_dir.path().join("docs/*.md"), &config).is_empty());
+}
+
+fn hidden_fixture() -> tempfile::TempDir {
+    let temp_dir = filter_fixture();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join(".github/workflows")).unwrap();
+    fs::create_dir_all(root.join(".git/objects")).unwrap();
+    fs::write(root.join(".github/workflows/ci.yml"), "on: push").unwrap();
+    fs::write(root.join(".env.example"), "KEY=").unwrap();
+    fs::write(root.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+    fs::write(root.join(".git/objects/ab"), "blob").unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_hidden_files_skipped_by_default() {
+    let temp_dir = hidden_fixture();
+    assert_eq!(
+        processed_paths(temp_dir.path(), &YekConfig::default()),
+        vec!["build.rs", "docs/guide.md", "src/gen/api.rs", "src/main.rs"]
+    );
+}
+
+#[test]
+fn test_hidden_flag_includes_dotfiles_but_not_git() {
+    let temp_dir = hidden_fixture();
+    let config = YekConfig {
+        hidden: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(temp_dir.path(), &config),
+        vec![
+            ".env.example",
+            ".github/workflows/ci.yml",
+            "build.rs",
+            "docs/guide.md",
+            "src/gen/api.rs",
+            "src/main.rs"
+        ]
+    );
+}
+
+#[test]
+fn test_hidden_flag_applies_to_glob_inputs() {
+    let temp_dir = hidden_fixture();
+    let pattern = temp_dir.path().join("*");
+    let names = |config: &YekConfig| -> Vec<String> {
+        processed_paths(&pattern, config)
+            .into_iter()
+            .filter(|p| p.starts_with('.'))
+            .collect()
+    };
+    assert!(names(&YekConfig::default()).is_empty());
+    let config = YekConfig {
+        hidden: true,
+        ..Default::default()
+    };
+    assert_eq!(names(&config), vec![".env.example"]);
+}
+
+#[test]
+fn test_only_allowlist_skips_every_ignore_rule() {
+    let temp_dir = hidden_fixture();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+    fs::create_dir_all(root.join("dist")).unwrap();
+    fs::write(root.join("node_modules/pkg/index.js"), "module.exports = 1").unwrap();
+    fs::write(root.join("dist/app.js"), "app()").unwrap();
+    fs::write(root.join("Cargo.lock"), "# lock").unwrap();
+    fs::write(root.join(".gitignore"), "dist/\n").unwrap();
+    fs::write(root.join(".yekignore"), "build.rs\n").unwrap();
+    fs::write(root.join(".gitattributes"), "docs/** export-ignore\n").unwrap();
+    let ignore_patterns = vec!["node_modules/".to_string(), "*.lock".to_string()];
+
+    let config = YekConfig {
+        ignore_patterns: ignore_patterns.clone(),
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(root, &config),
+        vec!["src/gen/api.rs", "src/main.rs"]
+    );
+
+    // Exactly the allowlisted files, whatever the ignore patterns, ignore files,
+    // .gitattributes and hidden-file rule say; .git stays out
+    let config = YekConfig {
+        ignore_patterns,
+        only: vec![
+            "node_modules/pkg/*.js".to_string(),
+            "dist/".to_string(),
+            ".github/**".to_string(),
+            "build.rs".to_string(),
+            "docs/*.md".to_string(),
+            ".git/**".to_string(),
+        ],
+        ..Default::default()
+    };
+    assert_eq!(
+        processed_paths(root, &config),
+        vec![
+            ".github/workflows/ci.yml",
+            "build.rs",
+            "dist/app.js",
+            "docs/guide.md",
+            "node_modules/pkg/index.js"
+        ]
+    );
+
+    // --exclude still narrows it down
+    let config = YekConfig {
+        exclude: vec!["*.js".to_string()],
+        ..config
+    };
+    assert_eq!(
+        processed_paths(root, &config),
+        vec![".github/workflows/ci.yml", "build.rs", "docs/guide.md"]
+    );
+}
+
+#[test]
+fn test_non_utf8_files_are_transcoded() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let mut utf16 = vec![0xFF, 0xFE];
+    for unit in "wide text".encode_utf16() {
+        utf16.extend_from_slice(&unit.to_le_bytes());
+    }
+    fs::write(temp_dir.path().join("wide.txt"), utf16).unwrap();
+    fs::write(
+        temp_dir.path().join("latin.txt"),
+        b"caf\xe9 cr\xe8me br\xfbl\xe9e, tr\xe8s d\xe9licieux",
+    )
+    .unwrap();
+
+    let files = process_files_parallel(temp_dir.path(), &YekConfig::default(), &HashMap::new())
+        .expect("process_files_parallel failed");
+    let content_of = |name: &str| {
+        files
+            .iter()
+            .find(|f| f.rel_path == name)
+            .map(|f| f.content.clone())
+            .unwrap()
+    };
+    assert_eq!(content_of("wide.txt"), "wide text");
+    assert_eq!(content_of("latin.txt"), "café crème brûlée, très délicieux");
+}
+
+#[test]
+fn test_undecodable_files_are_skipped() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    fs::write(temp_dir.path().join("bad.txt"), b"bad \xff\xfe\xfd bytes").unwrap();
+    fs::write(temp_dir.path().join("good.txt"), "good").unwrap();
+
+    let config = YekConfig {
+        encoding: Some("utf-8".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(processed_paths(temp_dir.path(), &config), vec!["good.txt"]);
+}
+
+#[test]
+fn test_on_invalid_utf8_lossy_and_error() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    fs::write(temp_dir.path().join("bad.txt"), b"bad \xff bytes").unwrap();
+    fs::write(temp_dir.path().join("good.txt"), "good").unwrap();
+    let boost_map = HashMap::new();
+
+    let config = YekConfig {
+        encoding: Some("utf-8".to_string()),
+        on_invalid_utf8: InvalidUtf8::Lossy,
+        ..Default::default()
+    };
+    let files = process_files_parallel(temp_dir.path(), &config, &boost_map).unwrap();
+    let bad = files.iter().find(|f| f.rel_path == "bad.txt").unwrap();
+    assert_eq!(bad.content, "bad \u{FFFD} bytes");
+
+    let config = YekConfig {
+        on_invalid_utf8: InvalidUtf8::Error,
+        ..config
+    };
+    let err = process_files_parallel(temp_dir.path(), &config, &boost_map).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "on_invalid_utf8: bad.txt: could not decode as UTF-8; \
+         use --on-invalid-utf8 lossy or skip to continue"
+    );
+}
+
+#[test]
+fn test_max_total_files_aborts_walk() -> Result<()> {
+    let temp_dir = tempdir()?;
+    for i in 0..5 {
+        fs::write(temp_dir.path().join(format!("f{i}.rs")), "fn f() {}")?;
+    }
+    let boost_map = HashMap::new();
+
+    let config = YekConfig {
+        max_total_files: 3,
+        ..YekConfig::default()
+    };
+    let err = process_files_parallel(temp_dir.path(), &config, &boost_map).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("max_total_files: found more than 3 files under"),
+        "{}",
+        err
+    );
+
+    // Exactly at the limit is fine, and 0 turns the guard off
+    for max_total_files in [5, 0] {
+        let config = YekConfig {
+            max_total_files,
+            ..YekConfig::default()
+        };
+        assert_eq!(
+            process_files_parallel(temp_dir.path(), &config, &boost_map)?.len(),
+            5
+        );
+    }
+    Ok(())
+}
@@ -4,7 +4,9 @@ mod priority_tests {
     use std::fs;
     use tempfile::tempdir;
     use yek::priority::{
-        compute_recentness_boost, get_file_priority, get_recent_commit_times_git2, PriorityRule,
+        compute_recentness_boost, get_changed_paths, get_file_priority, get_file_weight,
+        get_last_commits, get_recent_commit_times_git2, parse_priority_weight, repo_header,
+        PriorityRule,
     };
 
     #[test]
@@ -346,4 +348,176 @@ mod priority_tests {
         // Files with same timestamp should get same boost
         assert_eq!(boosts["file1.rs"], boosts["file2.rs"]);
     }
+
+    #[test]
+    fn test_parse_priority_weight() {
+        let weight = parse_priority_weight("README*:100").unwrap();
+        assert!(weight.pattern.matches("README.md"));
+        assert_eq!(weight.weight, 100);
+
+        // The weight follows the last colon
+        let weight = parse_priority_weight("c:/src/*.rs:-5").unwrap();
+        assert!(weight.pattern.matches("c:/src/main.rs"));
+        assert_eq!(weight.weight, -5);
+    }
+
+    #[test]
+    fn test_parse_priority_weight_errors() {
+        let err = parse_priority_weight("README.md").unwrap_err().to_string();
+        assert!(err.contains("expected <glob>:<weight>"));
+
+        let err = parse_priority_weight("README.md:high")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("priority: Invalid weight"));
+
+        let err = parse_priority_weight("[:10").unwrap_err().to_string();
+        assert!(err.starts_with("priority: Invalid pattern"));
+    }
+
+    #[test]
+    fn test_get_file_weight_uses_highest_match() {
+        let weights = vec![
+            parse_priority_weight("*.toml:10").unwrap(),
+            parse_priority_weight("Cargo.*:50").unwrap(),
+        ];
+        assert_eq!(get_file_weight("Cargo.toml", &weights), 50);
+        assert_eq!(get_file_weight("rustfmt.toml", &weights), 10);
+        assert_eq!(get_file_weight("src/main.rs", &weights), 0);
+    }
+
+    /// Run git in `repo`, pinning the commit date so commits are strictly ordered
+    fn git_at(repo: &std::path::Path, args: &[&str], date: &str) {
+        let status = std::process::Command::new("git")
+            .args([
+                "-c",
+                "user.name=Test User",
+                "-c",
+                "user.email=test@example.com",
+            ])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert!(status.status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_get_last_commits_tracks_latest_change() {
+        let dir = tempdir().unwrap();
+        let repo_path = dir.path();
+        git_at(repo_path, &["init", "-q"], "2024-01-01T00:00:00Z");
+
+        fs::write(repo_path.join("a.rs"), "a").unwrap();
+        fs::write(repo_path.join("b.rs"), "b").unwrap();
+        git_at(repo_path, &["add", "."], "2024-01-01T00:00:00Z");
+        git_at(repo_path, &["commit", "-qm", "one"], "2024-01-01T00:00:00Z");
+
+        fs::write(repo_path.join("a.rs"), "a2").unwrap();
+        git_at(
+            repo_path,
+            &["commit", "-qam", "two"],
+            "2024-02-01T00:00:00Z",
+        );
+        fs::write(repo_path.join("untracked.rs"), "u").unwrap();
+
+        let repo = git2::Repository::open(repo_path).unwrap();
+        let paths = ["a.rs", "b.rs", "untracked.rs"].map(String::from);
+        let commits = get_last_commits(&repo, &paths);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits["a.rs"].time, 1706745600);
+        assert_eq!(commits["b.rs"].time, 1704067200);
+        assert_eq!(commits["a.rs"].author, "Test User");
+        assert_eq!(commits["a.rs"].short_id.len(), 7);
+        assert_ne!(commits["a.rs"].short_id, commits["b.rs"].short_id);
+        assert!(!commits.contains_key("untracked.rs"));
+    }
+
+    #[test]
+    fn test_get_last_commits_empty_repo() {
+        let dir = tempdir().unwrap();
+        git_at(dir.path(), &["init", "-q"], "2024-01-01T00:00:00Z");
+        let repo = git2::Repository::open(dir.path()).unwrap();
+        assert!(get_last_commits(&repo, &["a.rs".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_get_changed_paths_since_ref() {
+        let dir = tempdir().unwrap();
+        let repo_path = dir.path();
+        let date = "2024-01-01T00:00:00Z";
+        git_at(repo_path, &["init", "-q"], date);
+        fs::write(repo_path.join("keep.rs"), "fn keep() {}").unwrap();
+        fs::write(repo_path.join("edit.rs"), "fn edit() {}").unwrap();
+        fs::write(repo_path.join("gone.rs"), "fn gone() {}").unwrap();
+        fs::write(
+            repo_path.join("old_name.rs"),
+            "fn renamed() {\n    println!(\"same content\");\n}\n",
+        )
+        .unwrap();
+        git_at(repo_path, &["add", "."], date);
+        git_at(repo_path, &["commit", "-qm", "base"], date);
+
+        fs::write(repo_path.join("edit.rs"), "fn edit() { todo!() }").unwrap();
+        fs::write(repo_path.join("added.rs"), "fn added() {}").unwrap();
+        git_at(repo_path, &["add", "added.rs"], date);
+        git_at(repo_path, &["rm", "-q", "gone.rs"], date);
+        git_at(repo_path, &["mv", "old_name.rs", "new_name.rs"], date);
+
+        let repo = git2::Repository::open(repo_path).unwrap();
+        let mut changed: Vec<_> = get_changed_paths(&repo, "HEAD")
+            .unwrap()
+            .into_iter()
+            .collect();
+        changed.sort();
+        assert_eq!(changed, vec!["added.rs", "edit.rs", "new_name.rs"]);
+    }
+
+    #[test]
+    fn test_get_changed_paths_unknown_ref() {
+        let dir = tempdir().unwrap();
+        git_at(dir.path(), &["init", "-q"], "2024-01-01T00:00:00Z");
+        let repo = git2::Repository::open(dir.path()).unwrap();
+        let err = get_changed_paths(&repo, "no-such-branch").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("since: Unknown git ref 'no-such-branch'"));
+    }
+
+    #[test]
+    fn test_repo_header_branch_commit_and_dirty() {
+        let dir = tempdir().unwrap();
+        let repo_path = dir.path();
+        let date = "2024-01-01T00:00:00Z";
+        git_at(repo_path, &["init", "-q", "-b", "main"], date);
+        let repo = git2::Repository::open(repo_path).unwrap();
+        assert_eq!(repo_header(&repo), "# Repo: main (no commits)");
+
+        fs::write(repo_path.join("a.rs"), "a").unwrap();
+        git_at(repo_path, &["add", "."], date);
+        git_at(repo_path, &["commit", "-qm", "one"], date);
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let short = head.as_object().short_id().unwrap();
+        let short = short.as_str().unwrap();
+        assert_eq!(repo_header(&repo), format!("# Repo: main ({})", short));
+
+        git_at(
+            repo_path,
+            &["remote", "add", "origin", "https://example.com/r.git"],
+            date,
+        );
+        fs::write(repo_path.join("new.rs"), "n").unwrap();
+        assert_eq!(
+            repo_header(&repo),
+            format!(
+                "# Repo: https://example.com/r.git @ main ({}, dirty)",
+                short
+            )
+        );
+
+        git_at(repo_path, &["checkout", "-q", "--detach"], date);
+        assert!(repo_header(&repo).contains("@ detached ("));
+    }
 }
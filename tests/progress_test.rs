@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::atomic::AtomicUsize;
+use tempfile::tempdir;
+use yek::config::YekConfig;
+use yek::parallel::process_files_parallel_counting;
+use yek::progress::ScanProgress;
+
+#[test]
+fn test_progress_hidden_with_quiet() {
+    let config = YekConfig {
+        quiet: true,
+        ..YekConfig::default()
+    };
+    assert!(!ScanProgress::new(&config).is_visible());
+}
+
+#[test]
+fn test_progress_hidden_when_stderr_is_not_a_terminal() {
+    // Test harness output is captured, so stderr is never a terminal here
+    use std::io::IsTerminal;
+    if !std::io::stderr().is_terminal() {
+        assert!(!ScanProgress::new(&YekConfig::default()).is_visible());
+    }
+}
+
+#[test]
+fn test_progress_counts_files_and_bytes() {
+    let progress = ScanProgress::hidden();
+    progress.found_file();
+    progress.found_file();
+    progress.read_file(10);
+    progress.read_file(32);
+    assert_eq!(progress.totals(), (2, 42));
+    progress.finish();
+}
+
+#[test]
+fn test_progress_tracks_walk_and_read() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("a.rs"), "fn a() {}").unwrap();
+    fs::write(temp.path().join("b.rs"), "fn b() {}\n").unwrap();
+
+    let progress = ScanProgress::hidden();
+    let files = process_files_parallel_counting(
+        temp.path(),
+        &YekConfig::default(),
+        &HashMap::new(),
+        &AtomicUsize::new(0),
+        &progress,
+    )
+    .unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(progress.totals(), (2, 19));
+}
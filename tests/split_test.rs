@@ -0,0 +1,261 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+use yek::{
+    config::YekConfig,
+    parallel::ProcessedFile,
+    split::{split_into_chunks, write_chunks},
+};
+
+fn file(rel_path: &str, content: &str) -> ProcessedFile {
+    ProcessedFile {
+        priority: 0,
+        file_index: 0,
+        rel_path: rel_path.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_split_keeps_files_whole() {
+    let config = YekConfig {
+        max_size: "40B".to_string(),
+        ..Default::default()
+    };
+    // Each rendered entry is ">>>> x.txt\n" + 20 bytes = 31 bytes
+    let files = vec![
+        file("a.txt", &"a".repeat(20)),
+        file("b.txt", &"b".repeat(20)),
+        file("c.txt", &"c".repeat(20)),
+    ];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    assert_eq!(chunks.len(), 3);
+    for (chunk, name) in chunks.iter().zip(["a.txt", "b.txt", "c.txt"]) {
+        assert_eq!(chunk.entry_count, 1);
+        assert!(chunk.content.contains(name));
+        assert!(chunk.content.len() <= 40);
+    }
+}
+
+#[test]
+fn test_split_packs_multiple_files_per_chunk() {
+    let config = YekConfig {
+        max_size: "1KB".to_string(),
+        ..Default::default()
+    };
+    let files = vec![file("a.txt", "alpha"), file("b.txt", "beta")];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].entry_count, 2);
+    assert_eq!(chunks[0].content, ">>>> a.txt\nalpha\n>>>> b.txt\nbeta");
+}
+
+#[test]
+fn test_split_oversized_file_into_parts() {
+    let config = YekConfig {
+        max_size: "60B".to_string(),
+        ..Default::default()
+    };
+    let content = (0..10).map(|i| format!("line {i}\n")).collect::<String>();
+    let files = vec![file("big.txt", &content)];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    assert!(chunks.len() > 1);
+    let total = chunks.len();
+    for (i, chunk) in chunks.iter().enumerate() {
+        assert!(chunk.content.len() <= 60, "chunk too large: {:?}", chunk);
+        assert!(chunk
+            .content
+            .contains(&format!("big.txt (part {}/{})", i + 1, total)));
+    }
+
+    // Every line survives exactly once
+    let joined = chunks
+        .iter()
+        .map(|c| c.content.as_str())
+        .collect::<String>();
+    for i in 0..10 {
+        assert_eq!(joined.matches(&format!("line {i}\n")).count(), 1);
+    }
+}
+
+#[test]
+fn test_split_tree_header_first_chunk_only() {
+    let config = YekConfig {
+        max_size: "80B".to_string(),
+        tree_header: true,
+        ..Default::default()
+    };
+    let files = vec![
+        file("a.txt", &"a".repeat(20)),
+        file("b.txt", &"b".repeat(20)),
+    ];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    assert!(chunks.len() >= 2);
+    assert!(chunks[0].content.starts_with("Directory structure:"));
+    assert!(chunks[1..]
+        .iter()
+        .all(|c| !c.content.contains("Directory structure:")));
+}
+
+#[test]
+fn test_split_repeat_tree_header() {
+    let config = YekConfig {
+        max_size: "100B".to_string(),
+        tree_header: true,
+        split_repeat_tree: true,
+        ..Default::default()
+    };
+    let files = vec![
+        file("a.txt", &"a".repeat(20)),
+        file("b.txt", &"b".repeat(20)),
+    ];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    assert_eq!(chunks.len(), 2);
+    for chunk in &chunks {
+        assert!(chunk.content.starts_with("Directory structure:"));
+        assert!(chunk.content.contains("├── a.txt"));
+        assert!(chunk.content.len() <= 100);
+    }
+}
+
+#[test]
+fn test_split_json_chunks_are_arrays() {
+    // Each object is 62 bytes on its own, so two of them only fit the limit
+    // if the array's brackets, commas and indentation are left uncounted
+    let config = YekConfig {
+        max_size: "130B".to_string(),
+        json: true,
+        ..Default::default()
+    };
+    let files = vec![
+        file("a.txt", &"a".repeat(20)),
+        file("b.txt", &"b".repeat(20)),
+    ];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    assert_eq!(chunks.len(), 2);
+    for chunk in &chunks {
+        let value: serde_json::Value = serde_json::from_str(&chunk.content).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 1);
+        assert!(chunk.content.len() <= 130);
+    }
+}
+
+#[test]
+fn test_split_json_oversized_file_parts_fit() {
+    let config = YekConfig {
+        max_size: "120B".to_string(),
+        json: true,
+        ..Default::default()
+    };
+    let content: String = (0..20).map(|i| format!("line \"{}\"\n", i)).collect();
+    let files = vec![file("big.txt", &content)];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    assert!(chunks.len() > 1);
+    let mut joined = String::new();
+    for chunk in &chunks {
+        assert!(chunk.content.len() <= 120);
+        let value: serde_json::Value = serde_json::from_str(&chunk.content).unwrap();
+        for entry in value.as_array().unwrap() {
+            joined.push_str(entry["content"].as_str().unwrap());
+        }
+    }
+    assert_eq!(joined, content);
+}
+
+#[test]
+fn test_split_token_mode() {
+    let config = YekConfig {
+        token_mode: true,
+        tokens: "20".to_string(),
+        ..Default::default()
+    };
+    let files = vec![
+        file("a.txt", "one two three four five six"),
+        file("b.txt", "seven eight nine ten eleven twelve"),
+    ];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    assert_eq!(chunks.len(), 2);
+    for chunk in &chunks {
+        assert!(yek::count_tokens(&chunk.content) <= 20);
+    }
+}
+
+#[test]
+fn test_write_chunks_numbers_files() {
+    let temp = TempDir::new().unwrap();
+    let out_dir = temp.path().join("chunks");
+    let config = YekConfig {
+        max_size: "40B".to_string(),
+        ..Default::default()
+    };
+    let files = vec![
+        file("a.txt", &"a".repeat(20)),
+        file("b.txt", &"b".repeat(20)),
+    ];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    let paths = write_chunks(&out_dir, &chunks, "txt").unwrap();
+    assert_eq!(
+        paths,
+        vec![
+            out_dir.join("output-001.txt"),
+            out_dir.join("output-002.txt")
+        ]
+    );
+    assert!(fs::read_to_string(&paths[1]).unwrap().contains("b.txt"));
+}
+
+#[test]
+fn test_split_output_cli() {
+    let temp = TempDir::new().unwrap();
+    let src = temp.path().join("src");
+    fs::create_dir(&src).unwrap();
+    fs::write(src.join("a.rs"), "a".repeat(600)).unwrap();
+    fs::write(src.join("b.rs"), "b".repeat(600)).unwrap();
+    let out_dir = temp.path().join("out");
+
+    Command::cargo_bin("yek")
+        .unwrap()
+        .arg("--max-size")
+        .arg("1KB")
+        .arg("--split-output")
+        .arg(&out_dir)
+        .arg(&src)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote 2 chunk(s)"))
+        .stdout(predicate::str::contains("output-001.txt"))
+        .stdout(predicate::str::contains("output-002.txt"));
+
+    assert!(out_dir.join("output-001.txt").exists());
+    assert!(out_dir.join("output-002.txt").exists());
+    assert!(!out_dir.join("output-003.txt").exists());
+}
+
+#[test]
+fn test_split_file_index_spans_chunks() {
+    let config = YekConfig {
+        max_size: "40B".to_string(),
+        output_template: "FILE_INDEX/FILE_COUNT FILE_PATH\nFILE_CONTENT".to_string(),
+        ..Default::default()
+    };
+    let files = vec![
+        file("a.txt", &"a".repeat(20)),
+        file("b.txt", &"b".repeat(20)),
+    ];
+
+    let chunks = split_into_chunks(&files, &config).unwrap();
+    assert_eq!(chunks.len(), 2);
+    assert!(chunks[0].content.starts_with("1/2 a.txt"));
+    assert!(chunks[1].content.starts_with("2/2 b.txt"));
+}
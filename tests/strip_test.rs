@@ -0,0 +1,175 @@
+use std::path::Path;
+use yek::strip::{comment_syntax, strip_blank_lines, strip_comments};
+
+/// Strip `content` as if it were in a file named `name`
+fn strip(name: &str, content: &str) -> String {
+    let syntax = comment_syntax(Path::new(name)).expect("known language");
+    strip_comments(content, &syntax)
+}
+
+#[test]
+fn test_comment_syntax_by_extension() {
+    for name in [
+        "a.rs", "a.c", "a.js", "a.ts", "a.go", "a.java", "a.py", "a.sh", "a.yml",
+    ] {
+        assert!(comment_syntax(Path::new(name)).is_some(), "{}", name);
+    }
+    assert!(comment_syntax(Path::new("Dockerfile")).is_some());
+    assert!(comment_syntax(Path::new("README.md")).is_none());
+    assert!(comment_syntax(Path::new("data.json")).is_none());
+    assert!(comment_syntax(Path::new("LICENSE")).is_none());
+    assert_eq!(
+        comment_syntax(Path::new("lib.RS")),
+        comment_syntax(Path::new("lib.rs"))
+    );
+}
+
+#[test]
+fn test_strip_c_like_comments() {
+    let content = "// header\nfn main() { // trailing\n    /* inline */ call();\n}\n";
+    assert_eq!(strip("main.rs", content), "fn main() {\n     call();\n}\n");
+}
+
+#[test]
+fn test_block_comment_spanning_lines() {
+    let content = "int a; /* one\ntwo\nthree */ int b;\n/**\n * Doc\n */\nint c;\n";
+    assert_eq!(strip("a.c", content), "int a;\n int b;\nint c;\n");
+}
+
+#[test]
+fn test_markers_in_strings_are_kept() {
+    let content = concat!(
+        "let url = \"http://example.com\"; // link\n",
+        "let s = \"a \\\" // still a string\";\n",
+        "let c = '/'; let d = '\\''; // done\n",
+        "let e = \"/* not a comment */\";\n",
+    );
+    assert_eq!(
+        strip("main.rs", content),
+        concat!(
+            "let url = \"http://example.com\";\n",
+            "let s = \"a \\\" // still a string\";\n",
+            "let c = '/'; let d = '\\'';\n",
+            "let e = \"/* not a comment */\";\n",
+        )
+    );
+}
+
+#[test]
+fn test_rust_lifetimes_are_not_char_literals() {
+    let content = "fn f<'a>(x: &'a str) -> &'a str { x } // id\nlet q = \"//\";\n";
+    assert_eq!(
+        strip("lib.rs", content),
+        "fn f<'a>(x: &'a str) -> &'a str { x }\nlet q = \"//\";\n"
+    );
+}
+
+#[test]
+fn test_raw_strings_are_kept() {
+    let content = concat!(
+        "let s = r#\"{\"url\": \"http://x\"}\"#; // json\n",
+        "let p = r\"C:\\\"; // path\n",
+        "let b = br##\"a \"# // b\"##;\n",
+        "let m = r\"one\n// two\"; let r#type = 1; // ident\n",
+    );
+    assert_eq!(
+        strip("main.rs", content),
+        concat!(
+            "let s = r#\"{\"url\": \"http://x\"}\"#;\n",
+            "let p = r\"C:\\\";\n",
+            "let b = br##\"a \"# // b\"##;\n",
+            "let m = r\"one\n// two\"; let r#type = 1;\n",
+        )
+    );
+
+    let content = "auto s = R\"sql(a \")\" /* x */)sql\"; // cpp\nauto t = u8R\"(//)\";\n";
+    assert_eq!(
+        strip("a.cpp", content),
+        "auto s = R\"sql(a \")\" /* x */)sql\";\nauto t = u8R\"(//)\";\n"
+    );
+}
+
+#[test]
+fn test_javascript_strings_and_template_literals() {
+    let content = "const a = 'it // is'; // one\nconst b = `line\n// inside ${a}`; // two\n";
+    assert_eq!(
+        strip("app.js", content),
+        "const a = 'it // is';\nconst b = `line\n// inside ${a}`;\n"
+    );
+}
+
+#[test]
+fn test_strip_hash_comments() {
+    let content = "#!/bin/sh\n# setup\necho \"# not a comment\" # comment\necho $# ${#x}\n";
+    assert_eq!(
+        strip("run.sh", content),
+        "#!/bin/sh\necho \"# not a comment\"\necho $# ${#x}\n"
+    );
+}
+
+#[test]
+fn test_shell_single_quotes_have_no_escapes() {
+    let content = "echo 'a\\' # comment\n";
+    assert_eq!(strip("run.sh", content), "echo 'a\\'\n");
+}
+
+#[test]
+fn test_python_strings_and_docstrings() {
+    let content = concat!(
+        "def f():\n",
+        "    \"\"\"Docs # kept\n",
+        "    over lines\"\"\"\n",
+        "    # comment\n",
+        "    return '#' + \"\\\"#\"  # trailing\n",
+    );
+    assert_eq!(
+        strip("mod.py", content),
+        concat!(
+            "def f():\n",
+            "    \"\"\"Docs # kept\n",
+            "    over lines\"\"\"\n",
+            "    return '#' + \"\\\"#\"\n",
+        )
+    );
+}
+
+#[test]
+fn test_unterminated_string_ends_at_the_line() {
+    let content = "echo it's here # kept\n# dropped\n";
+    assert_eq!(strip("run.sh", content), "echo it's here # kept\n");
+}
+
+#[test]
+fn test_other_languages() {
+    assert_eq!(
+        strip("q.sql", "SELECT '--x' -- note\nFROM t; /* c */\n"),
+        "SELECT '--x'\nFROM t;\n"
+    );
+    assert_eq!(strip("a.lua", "--[[ block\n]]\nx = 1 -- c\n"), "x = 1\n");
+    assert_eq!(
+        strip("a.html", "<p>don't</p><!-- c -->\n<!--\nx\n-->\n"),
+        "<p>don't</p>\n"
+    );
+    assert_eq!(
+        strip("a.scss", "a { b: url(http://x); } // c\n"),
+        "a { b: url(http://x); }\n"
+    );
+}
+
+#[test]
+fn test_blank_lines_kept_by_strip_comments() {
+    let content = "a();\n\n// c\nb();\n";
+    assert_eq!(strip("a.c", content), "a();\n\nb();\n");
+}
+
+#[test]
+fn test_crlf_and_missing_final_newline() {
+    assert_eq!(strip("a.c", "a(); // x\r\n// y\r\nb();"), "a();\r\nb();");
+    assert_eq!(strip("a.c", "a(); // x"), "a();");
+}
+
+#[test]
+fn test_strip_blank_lines() {
+    assert_eq!(strip_blank_lines("a\n\n  \n\tb\n\r\nc"), "a\n\tb\nc");
+    assert_eq!(strip_blank_lines("\n\n"), "");
+}
@@ -3,7 +3,7 @@ mod symlink_tests {
     use std::collections::HashMap;
     use std::fs;
     use tempfile::tempdir;
-    use yek::{config::YekConfig, parallel::process_files_parallel};
+    use yek::{config::YekConfig, is_serialized, parallel::process_files_parallel, serialize_repo};
 
     #[cfg(unix)]
     #[test]
@@ -29,18 +29,119 @@ mod symlink_tests {
         let processed =
             process_files_parallel(base_path, &config, &boost_map).expect("processing failed");
 
-        // Collect the relative paths of processed files.
-        let files: Vec<_> = processed.into_iter().map(|pf| pf.rel_path).collect();
-
-        // The regular file should be processed and the symlink should be skipped.
+        // The regular file is read; the symlink is kept only as a link to its target
+        let regular = processed
+            .iter()
+            .find(|pf| pf.rel_path == "regular.txt")
+            .expect("Expected regular.txt to be processed");
+        assert_eq!(regular.content, "hello");
+        let link = processed
+            .iter()
+            .find(|pf| pf.rel_path == "symlink.txt")
+            .expect("Expected symlink.txt to be listed");
         assert!(
-            files.contains(&"regular.txt".to_string()),
-            "Expected regular.txt to be processed"
+            link.content.is_empty(),
+            "Expected symlink.txt not to be read"
+        );
+        assert_eq!(
+            link.symlink_target.as_deref(),
+            Some(regular_file.to_string_lossy().as_ref())
         );
+        assert!(!is_serialized(link, &config));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_listed_in_tree_with_target() {
+        let temp_dir = tempdir().expect("failed to create temp dir");
+        let base_path = temp_dir.path();
+        fs::write(base_path.join("target.rs"), "fn target() {}").unwrap();
+        std::os::unix::fs::symlink("target.rs", base_path.join("link.rs")).unwrap();
+
+        let config = YekConfig {
+            input_paths: vec![base_path.to_string_lossy().to_string()],
+            tree_header: true,
+            ..Default::default()
+        };
+        let (output, files) = serialize_repo(&config).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(output.contains("├── link.rs -> target.rs\n"), "{}", output);
+        assert!(output.contains(">>>> target.rs"), "{}", output);
+        assert!(!output.contains(">>>> link.rs"), "{}", output);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_serializes_targets() {
+        let temp_dir = tempdir().expect("failed to create temp dir");
+        let base_path = temp_dir.path();
+        let shared = tempdir().expect("failed to create temp dir");
+        fs::write(shared.path().join("shared.rs"), "fn shared() {}").unwrap();
+        fs::write(base_path.join("main.rs"), "fn main() {}").unwrap();
+        std::os::unix::fs::symlink(shared.path(), base_path.join("vendor")).unwrap();
+        std::os::unix::fs::symlink("main.rs", base_path.join("alias.rs")).unwrap();
+
+        let config = YekConfig {
+            input_paths: vec![base_path.to_string_lossy().to_string()],
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let (output, _) = serialize_repo(&config).unwrap();
         assert!(
-            !files.contains(&"symlink.txt".to_string()),
-            "Expected symlink.txt to be skipped"
+            output.contains(">>>> vendor/shared.rs\nfn shared() {}"),
+            "{}",
+            output
         );
+        assert!(output.contains(">>>> alias.rs\nfn main() {}"), "{}", output);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_survives_cycles() {
+        let temp_dir = tempdir().expect("failed to create temp dir");
+        let base_path = temp_dir.path();
+        fs::create_dir_all(base_path.join("a/b")).unwrap();
+        fs::write(base_path.join("a/b/lib.rs"), "// lib").unwrap();
+        // a/b/up -> a, and a second link to the same directory
+        std::os::unix::fs::symlink(base_path.join("a"), base_path.join("a/b/up")).unwrap();
+        std::os::unix::fs::symlink(base_path.join("a"), base_path.join("again")).unwrap();
+
+        let config = YekConfig {
+            input_paths: vec![base_path.to_string_lossy().to_string()],
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let (_, files) = serialize_repo(&config).unwrap();
+        // Each directory is walked once, so lib.rs appears exactly once
+        let libs: Vec<_> = files
+            .iter()
+            .filter(|f| f.rel_path.ends_with("lib.rs"))
+            .collect();
+        assert_eq!(libs.len(), 1, "{:?}", libs);
+        assert_eq!(libs[0].rel_path, "a/b/lib.rs");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_prefers_real_directory() {
+        let temp_dir = tempdir().expect("failed to create temp dir");
+        let base_path = temp_dir.path();
+        fs::create_dir_all(base_path.join("real")).unwrap();
+        fs::write(base_path.join("real/mod.rs"), "// mod").unwrap();
+        // Sorts before "real", so a walk in path order would reach the link first
+        std::os::unix::fs::symlink(base_path.join("real"), base_path.join("alias")).unwrap();
+
+        let config = YekConfig {
+            input_paths: vec![base_path.to_string_lossy().to_string()],
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        // The parallel walk has no fixed order, so repeat to catch a race
+        for _ in 0..20 {
+            let (_, files) = serialize_repo(&config).unwrap();
+            let paths: Vec<_> = files.iter().map(|f| f.rel_path.as_str()).collect();
+            assert_eq!(paths, ["real/mod.rs"]);
+        }
     }
 
     // For non-unix systems, we skip the symlink test.
@@ -0,0 +1,57 @@
+use yek::toc::{escape_link_text, heading_anchor, heading_text, Anchors};
+
+#[test]
+fn test_heading_anchor_matches_github() {
+    assert_eq!(heading_anchor("src/main.rs"), "srcmainrs");
+    assert_eq!(heading_anchor("README.md"), "readmemd");
+    assert_eq!(
+        heading_anchor("docs/Getting Started.md"),
+        "docsgetting-startedmd"
+    );
+    assert_eq!(
+        heading_anchor("src/my_mod/foo-bar.rs"),
+        "srcmy_modfoo-barrs"
+    );
+    assert_eq!(heading_anchor("*.rs"), "rs");
+    assert_eq!(heading_anchor("src/Ünïcode.rs"), "srcünïcoders");
+    assert_eq!(heading_anchor("  padded  "), "padded");
+}
+
+#[test]
+fn test_repeated_anchors_get_suffixes() {
+    let mut anchors = Anchors::default();
+    assert_eq!(anchors.next("Contents"), "contents");
+    assert_eq!(anchors.next("a.rs"), "ars");
+    assert_eq!(anchors.next("a/rs"), "ars-1");
+    assert_eq!(anchors.next("ars"), "ars-2");
+    assert_eq!(anchors.next("contents"), "contents-1");
+}
+
+#[test]
+fn test_suffixed_anchor_taken_by_an_earlier_heading() {
+    let mut anchors = Anchors::default();
+    assert_eq!(anchors.next("a-1"), "a-1");
+    assert_eq!(anchors.next("a"), "a");
+    assert_eq!(anchors.next("a"), "a-2");
+}
+
+#[test]
+fn test_escape_link_text() {
+    assert_eq!(escape_link_text("src/main.rs"), "src/main.rs");
+    assert_eq!(
+        escape_link_text("pkg/__init__.py"),
+        "pkg/\\_\\_init\\_\\_.py"
+    );
+    assert_eq!(escape_link_text("a[1]*`b`"), "a\\[1\\]\\*\\`b\\`");
+}
+
+#[test]
+fn test_heading_text() {
+    assert_eq!(heading_text("## src/main.rs"), Some("src/main.rs"));
+    assert_eq!(heading_text("# Title #"), Some("Title"));
+    assert_eq!(heading_text("### C#"), Some("C#"));
+    assert_eq!(heading_text("#"), Some(""));
+    assert_eq!(heading_text("#hashtag"), None);
+    assert_eq!(heading_text("####### seven"), None);
+    assert_eq!(heading_text("==== *.rs ===="), None);
+}
@@ -0,0 +1,144 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+use yek::config::YekConfig;
+use yek::serialize_repo;
+use yek::transform::{parse_transform_rule, run_transform};
+
+#[test]
+fn test_parse_transform_rule() {
+    let rule = parse_transform_rule("*.py:black -q -").unwrap();
+    assert!(rule.pattern.matches("src/app.py"));
+    assert_eq!(rule.command, "black -q -");
+
+    // The glob ends at the first colon
+    let rule = parse_transform_rule("*.json: jq -S . | sed 's/a:b/c/'").unwrap();
+    assert_eq!(rule.command, "jq -S . | sed 's/a:b/c/'");
+}
+
+#[test]
+fn test_parse_transform_rule_errors() {
+    assert_eq!(
+        parse_transform_rule("*.py").unwrap_err().to_string(),
+        "transform: Invalid rule '*.py': expected <glob>:<command>"
+    );
+    assert_eq!(
+        parse_transform_rule("*.py:  ").unwrap_err().to_string(),
+        "transform: Empty command in '*.py:  '"
+    );
+    assert!(parse_transform_rule("[:cat")
+        .unwrap_err()
+        .to_string()
+        .starts_with("transform: Invalid pattern in '[:cat'"));
+}
+
+#[test]
+fn test_run_transform() {
+    assert_eq!(
+        run_transform("tr a-z A-Z", "a.rs", "fn a() {}\n").unwrap(),
+        "FN A() {}\n"
+    );
+    assert_eq!(
+        run_transform("printf '%s' \"$YEK_FILE_PATH\"", "src/a.rs", "").unwrap(),
+        "src/a.rs"
+    );
+    // Commands that ignore their input still work
+    let large = "x".repeat(1 << 20);
+    assert_eq!(
+        run_transform("echo done", "a.rs", &large).unwrap(),
+        "done\n"
+    );
+
+    assert_eq!(
+        run_transform("echo broken >&2; exit 3", "a.rs", "")
+            .unwrap_err()
+            .to_string(),
+        "transform: 'echo broken >&2; exit 3' failed on a.rs (exit status: 3): broken"
+    );
+}
+
+#[test]
+fn test_transforms_apply_to_matching_files_in_order() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("main.rs"), "// comment\nfn main() {}\n").unwrap();
+    fs::write(dir.path().join("notes.md"), "# notes\n").unwrap();
+    let config = YekConfig {
+        input_paths: vec![dir.path().to_string_lossy().to_string()],
+        transform: vec![
+            "*.rs:grep -v '^//'".to_string(),
+            "*.rs:tr a-z A-Z".to_string(),
+        ],
+        ..Default::default()
+    };
+
+    let (output, _) = serialize_repo(&config).unwrap();
+    assert!(
+        output.contains(">>>> main.rs\nFN MAIN() {}\n"),
+        "{}",
+        output
+    );
+    assert!(output.contains(">>>> notes.md\n# notes\n"), "{}", output);
+}
+
+#[test]
+fn test_failing_transform() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+    let config = YekConfig {
+        input_paths: vec![dir.path().to_string_lossy().to_string()],
+        transform: vec!["*.rs:tr a-z A-Z".to_string(), "*.rs:exit 1".to_string()],
+        ..Default::default()
+    };
+    let err = serialize_repo(&config).unwrap_err().to_string();
+    assert!(
+        err.starts_with("transform: 'exit 1' failed on main.rs"),
+        "{}",
+        err
+    );
+
+    // Best effort keeps the original content, not a partly transformed one
+    let config = YekConfig {
+        transform_best_effort: true,
+        ..config
+    };
+    let (output, _) = serialize_repo(&config).unwrap();
+    assert!(
+        output.contains(">>>> main.rs\nfn main() {}\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_transform_flags() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+
+    Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--transform", "*.rs:tr a-z A-Z", "--", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("FN MAIN() {}"));
+
+    Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--transform", "*.rs:false", "--", "."])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "transform: 'false' failed on main.rs",
+        ));
+
+    Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--transform", "*.rs:false", "--transform-best-effort", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fn main() {}"))
+        .stderr(predicate::str::contains("keeping the original content"));
+}
@@ -4,7 +4,12 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use tempfile::TempDir;
-use yek::tree::{clean_path_components, generate_tree};
+use yek::tree::{
+    build_diff_tree, build_tree, clean_path_components, generate_diff_tree, generate_tree,
+    generate_tree_json, generate_tree_with_options, tree_file_order, TreeBuilder, TreeChange,
+    TreeConnectors, TreeEntry, TreeOptions, TreeOrder, TreeSort, TreeSortCase, TreeStyle,
+    MAX_JSON_TREE_DEPTH,
+};
 
 #[cfg(test)]
 mod tree_tests {
@@ -173,14 +178,26 @@ mod tree_tests {
     #[test]
     fn test_tree_only_with_json_output() {
         let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
         fs::write(temp_dir.path().join("test.rs"), "content").unwrap();
 
         let mut cmd = Command::cargo_bin("yek").unwrap();
         cmd.arg("--tree-only").arg("--json").arg(temp_dir.path());
 
-        cmd.assert().failure().stderr(predicate::str::contains(
-            "JSON output not supported in tree-only mode",
-        ));
+        let output = cmd.assert().success().get_output().stdout.clone();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(json["name"], ".");
+        assert_eq!(json["is_file"], false);
+        let children = json["children"].as_array().unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0]["name"], "src");
+        assert_eq!(children[0]["is_file"], false);
+        assert_eq!(children[0]["children"][0]["name"], "main.rs");
+        assert_eq!(children[0]["children"][0]["is_file"], true);
+        assert_eq!(children[1]["name"], "test.rs");
+        assert_eq!(children[1]["is_file"], true);
     }
 
     #[test]
@@ -492,14 +509,14 @@ mod tree_tests {
         // problematic components like ".." and "."
 
         let path = Path::new("./src/../src/lib.rs");
-        let components = clean_path_components(&path);
+        let components = clean_path_components(path);
 
         // Should filter out "." and keep ".." and normal components
         assert_eq!(components, vec!["src", "..", "src", "lib.rs"]);
 
         // Test with a simple path
         let path = Path::new("repo/src/lib.rs");
-        let components = clean_path_components(&path);
+        let components = clean_path_components(path);
         assert_eq!(components, vec!["repo", "src", "lib.rs"]);
     }
 
@@ -617,4 +634,1525 @@ mod tree_tests {
         let result2_lines: Vec<&str> = result2.lines().filter(|l| !l.trim().is_empty()).collect();
         assert_eq!(result1_lines.len(), result2_lines.len());
     }
+
+    #[test]
+    fn test_generate_tree_with_sizes() {
+        let entries = vec![
+            TreeEntry::file("src/lib.rs", Some(1200)),
+            TreeEntry::file("src/main.rs", Some(300)),
+            TreeEntry::file("README.md", Some(50)),
+        ];
+        let options = TreeOptions {
+            show_sizes: true,
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        // Directories show the aggregated size of their descendants
+        assert!(result.contains("├── src/      (1.5 kB)"), "got: {}", result);
+        assert!(
+            result.contains("│   ├── lib.rs  (1.2 kB)"),
+            "got: {}",
+            result
+        );
+        assert!(
+            result.contains("│   └── main.rs (300 B)"),
+            "got: {}",
+            result
+        );
+        assert!(result.contains("└── README.md (50 B)"), "got: {}", result);
+    }
+
+    #[test]
+    fn test_generate_tree_with_counts() {
+        let entries = vec![
+            TreeEntry::file("src/lib.rs", Some(1200)),
+            TreeEntry::file("src/main.rs", Some(300)),
+            TreeEntry::file("src/util/mod.rs", Some(10)),
+            TreeEntry::file("README.md", Some(50)),
+        ];
+        let options = TreeOptions {
+            show_counts: true,
+            root_name: Some("repo".to_string()),
+            header_text: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            "repo/ (4 files, 2 dirs)\n\
+             ├── src/      (3 files, 1 dir)\n\
+             │   ├── util/   (1 file)\n\
+             │   │   └── mod.rs\n\
+             │   ├── lib.rs\n\
+             │   └── main.rs\n\
+             └── README.md\n\n"
+        );
+
+        // Sizes follow the counts in the same parentheses
+        let options = TreeOptions {
+            show_sizes: true,
+            ..options
+        };
+        let result = generate_tree_with_options(&entries, &options);
+        assert!(
+            result.contains("├── src/      (3 files, 1 dir, 1.5 kB)"),
+            "got: {}",
+            result
+        );
+        assert!(
+            result.contains("│   │   └── mod.rs (10 B)"),
+            "got: {}",
+            result
+        );
+
+        // An empty directory counts as a directory with no files
+        let mut builder = TreeBuilder::new();
+        builder
+            .add_entry(&entries[0])
+            .add_entry(&TreeEntry::dir("src/empty"));
+        let tree = builder.build();
+        let src = tree.child("src").unwrap();
+        assert_eq!((src.file_count(), src.dir_count()), (1, 1));
+        assert_eq!((tree.file_count(), tree.dir_count()), (1, 2));
+
+        let json = generate_tree_json(&entries, &options);
+        assert_eq!(json["files"], 4);
+        assert_eq!(json["dirs"], 2);
+        assert_eq!(json["children"][0]["files"], 3);
+        assert!(json["children"][1].get("files").is_none());
+    }
+
+    #[test]
+    fn test_generate_tree_header_and_footer_text() {
+        let entries = vec![TreeEntry::file("src/lib.rs", None)];
+        let options = TreeOptions {
+            header_text: Some("```text".to_string()),
+            footer_text: Some("```".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            "```text\n└── src/\n    └── lib.rs\n```\n\n"
+        );
+
+        // An empty header drops the label line entirely
+        let options = TreeOptions {
+            header_text: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            "└── src/\n    └── lib.rs\n\n"
+        );
+
+        assert!(
+            generate_tree_with_options(&entries, &TreeOptions::default())
+                .starts_with("Directory structure:\n└── src/")
+        );
+    }
+
+    #[test]
+    fn test_generate_tree_root_name() {
+        let entries = vec![
+            TreeEntry::file("src/lib.rs", Some(1200)),
+            TreeEntry::file("README.md", Some(50)),
+        ];
+        let options = TreeOptions {
+            root_name: Some("myproject".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            "Directory structure:\nmyproject/\n├── src/\n│   └── lib.rs\n└── README.md\n\n"
+        );
+
+        // The root follows the directory marker, shows the total size, and is escaped for XML
+        let options = TreeOptions {
+            root_name: Some("<app>".to_string()),
+            dir_marker: Some(String::new()),
+            show_sizes: true,
+            escape_xml: true,
+            header_text: Some(String::new()),
+            ..Default::default()
+        };
+        assert!(
+            generate_tree_with_options(&entries, &options)
+                .starts_with("&lt;app&gt; (1.2 kB)\n├── src"),
+            "{}",
+            generate_tree_with_options(&entries, &options)
+        );
+
+        // JSON trees use it as the root object's name
+        let json = generate_tree_json(
+            &entries,
+            &TreeOptions {
+                root_name: Some("myproject".to_string()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(json["name"], "myproject");
+        assert_eq!(
+            generate_tree_json(&entries, &TreeOptions::default())["name"],
+            "."
+        );
+    }
+
+    #[test]
+    fn test_generate_diff_tree() {
+        let old = [
+            TreeEntry::file("src/lib.rs", Some(10)),
+            TreeEntry::file("src/old.rs", Some(20)),
+            TreeEntry::file("docs/guide.md", Some(30)),
+            TreeEntry::file("README.md", Some(40)),
+        ];
+        let new = [
+            TreeEntry::file("src/lib.rs", Some(15)),
+            TreeEntry::file("src/new.rs", Some(25)),
+            TreeEntry::file("tests/it.rs", Some(35)),
+            TreeEntry::file("README.md", Some(40)),
+        ];
+        assert_eq!(
+            generate_diff_tree(&old, &new, &TreeOptions::default()),
+            concat!(
+                "Directory structure:\n",
+                "- ├── docs/\n",
+                "- │   └── guide.md\n",
+                "  ├── src/\n",
+                "  │   ├── lib.rs\n",
+                "+ │   ├── new.rs\n",
+                "- │   └── old.rs\n",
+                "+ ├── tests/\n",
+                "+ │   └── it.rs\n",
+                "  └── README.md\n",
+                "\n"
+            )
+        );
+
+        // The root line and depth summaries get a gutter too
+        let options = TreeOptions {
+            root_name: Some("app".to_string()),
+            max_depth: Some(0),
+            header_text: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_diff_tree(&old, &new, &options),
+            concat!(
+                "  app/\n",
+                "- ├── docs/\n",
+                "- │   └── … (1 more)\n",
+                "  ├── src/\n",
+                "  │   └── … (3 more)\n",
+                "+ ├── tests/\n",
+                "+ │   └── … (1 more)\n",
+                "  └── README.md\n",
+                "\n"
+            )
+        );
+        assert_eq!(generate_diff_tree(&[], &[], &TreeOptions::default()), "");
+    }
+
+    #[test]
+    fn test_build_diff_tree_marks_nodes() {
+        let tree = build_diff_tree(
+            &[TreeEntry::file("a/x.rs", Some(1))],
+            &[TreeEntry::file("a/x.rs", Some(2)), TreeEntry::dir("b")],
+        );
+        let a = tree.child("a").unwrap();
+        assert_eq!(a.change(), Some(TreeChange::Unchanged));
+        assert_eq!(a.child("x.rs").unwrap().size(), Some(2));
+        assert_eq!(tree.child("b").unwrap().change(), Some(TreeChange::Added));
+        assert_eq!(build_tree([("a", true)]).change(), None);
+
+        let json = build_diff_tree(&[TreeEntry::file("gone.rs", None)], &[])
+            .to_json(&TreeOptions::default());
+        assert_eq!(json["children"][0]["change"], "removed");
+    }
+
+    #[test]
+    fn test_generate_tree_with_color() {
+        let entries = vec![
+            TreeEntry::file("src/lib.rs", Some(1200)),
+            TreeEntry::link("docs", "../shared/docs"),
+            TreeEntry::file("README.md", Some(50)),
+        ];
+        let options = TreeOptions {
+            color: true,
+            root_name: Some("app".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            concat!(
+                "Directory structure:\n",
+                "\x1b[1;34mapp/\x1b[0m\n",
+                "├── \x1b[1;34msrc/\x1b[0m\n",
+                "│   └── lib.rs\n",
+                "├── README.md\n",
+                "└── \x1b[36mdocs -> ../shared/docs\x1b[0m\n",
+                "\n"
+            )
+        );
+
+        // Sizes stay aligned: padding ignores the escapes
+        let options = TreeOptions {
+            color: true,
+            show_sizes: true,
+            header_text: Some(String::new()),
+            ..Default::default()
+        };
+        let tree = generate_tree_with_options(&entries[..1], &options);
+        assert_eq!(
+            tree,
+            "└── \x1b[1;34msrc/\x1b[0m (1.2 kB)\n    └── lib.rs (1.2 kB)\n\n"
+        );
+
+        // Off by default
+        assert!(!generate_tree_with_options(&entries, &TreeOptions::default()).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_color_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .args(["--tree-only", "--color", "always", "."]);
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "Directory structure:\n└── \x1b[1;34msrc/\x1b[0m\n    └── main.rs\n",
+        ));
+
+        // Piped output is plain by default, and never colored when written to a file
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path()).args(["--tree-only", "."]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains('\x1b').not());
+
+        let output = temp_dir.path().join("tree.txt");
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path()).args([
+            "--tree-only",
+            "--color",
+            "always",
+            "--output",
+            output.to_str().unwrap(),
+            ".",
+        ]);
+        cmd.assert().success();
+        let written = fs::read_to_string(&output).unwrap();
+        assert!(written.contains("└── src/"), "{}", written);
+        assert!(!written.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_tree_only_prints_to_terminal() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        let output_dir = temp_dir.path().join("out");
+
+        // On a terminal, the tree is shown there, in color by default
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .env("FORCE_TTY", "1")
+            .env_remove("NO_COLOR")
+            .args(["--tree-only", "."]);
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "Directory structure:\n└── \x1b[1;34msrc/\x1b[0m\n    └── main.rs\n",
+        ));
+
+        // NO_COLOR and --color never keep it plain
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .env("FORCE_TTY", "1")
+            .env("NO_COLOR", "1")
+            .args(["--tree-only", "."]);
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "Directory structure:\n└── src/\n    └── main.rs\n",
+        ));
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .env("FORCE_TTY", "1")
+            .env_remove("NO_COLOR")
+            .args(["--tree-only", "--color", "never", "."]);
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "Directory structure:\n└── src/\n    └── main.rs\n",
+        ));
+
+        // ...unless an output directory is given, whose file is never colored
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .env("FORCE_TTY", "1")
+            .args([
+                "--tree-only",
+                "--color",
+                "always",
+                "--output-dir",
+                output_dir.to_str().unwrap(),
+                ".",
+            ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Directory structure:").not());
+        let written: Vec<_> = fs::read_dir(&output_dir).unwrap().collect();
+        assert_eq!(written.len(), 1);
+        let tree = fs::read_to_string(written[0].as_ref().unwrap().path()).unwrap();
+        assert!(
+            tree.starts_with("Directory structure:\n└── src/\n    └── main.rs\n"),
+            "{}",
+            tree
+        );
+    }
+
+    #[test]
+    fn test_root_name_flag_with_several_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("api")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("web")).unwrap();
+        fs::write(temp_dir.path().join("api/main.go"), "package main").unwrap();
+        fs::write(temp_dir.path().join("web/app.js"), "app()").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path()).args([
+            "--tree-header",
+            "--root-name",
+            "platform",
+            "api",
+            "web",
+        ]);
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "Directory structure:\nplatform/\n├── api/\n│   └── main.go\n└── web/\n    └── app.js\n\n",
+        ));
+
+        // Without it the tree stays rootless
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .args(["--tree-header", "api", "web"]);
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "Directory structure:\n├── api/",
+        ));
+    }
+
+    #[test]
+    fn test_tree_header_text_flags() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path()).args([
+            "--tree-header",
+            "--tree-header-text",
+            "<tree>",
+            "--tree-footer-text",
+            "</tree>",
+            ".",
+        ]);
+        cmd.assert().success().stdout(predicate::str::starts_with(
+            "<tree>\n└── main.rs\n</tree>\n\n",
+        ));
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .args(["--tree-only", "--tree-header-text", "", "."]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::starts_with("└── main.rs\n"))
+            .stdout(predicate::str::contains("Directory structure:").not());
+    }
+
+    #[test]
+    fn test_generate_tree_sizes_disabled_by_default() {
+        let entries = vec![TreeEntry::file("src/lib.rs", Some(1200))];
+        let result = generate_tree_with_options(&entries, &TreeOptions::default());
+        assert!(result.contains("    └── lib.rs\n"));
+        assert!(!result.contains("kB"));
+    }
+
+    #[test]
+    fn test_tree_counts_flag_counts_included_files() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/nested")).unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), "pub fn lib() {}").unwrap();
+        fs::write(temp_dir.path().join("src/nested/mod.rs"), "mod x;").unwrap();
+        fs::write(temp_dir.path().join("src/debug.log"), "noise").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .args(["--tree-only", "--tree-counts", "."]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("└── src/ (2 files, 1 dir)\n"))
+            .stdout(predicate::str::contains("├── nested/ (1 file)\n"));
+    }
+
+    #[test]
+    fn test_tree_sizes_flag_shows_dropped_files() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("large.rs"), "x".repeat(2000)).unwrap();
+        fs::write(temp_dir.path().join("small.rs"), "small").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.arg("--tree-header")
+            .arg("--tree-sizes")
+            .arg("--max-size")
+            .arg("1KB")
+            .arg(temp_dir.path());
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("├── large.rs (2.0 kB)"))
+            .stdout(predicate::str::contains("└── small.rs (5 B)"));
+    }
+
+    #[test]
+    fn test_tree_sizes_are_sizes_on_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = format!("// {}\nfn main() {{}}\n", "x".repeat(1997));
+        fs::write(temp_dir.path().join("main.rs"), content).unwrap();
+
+        // Stripping the comment leaves 13 bytes, but the file is 2 kB
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.args(["--tree-header", "--tree-sizes", "--strip-comments"])
+            .arg(temp_dir.path());
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("└── main.rs (2.0 kB)"));
+    }
+
+    #[test]
+    fn test_generate_tree_depth_zero() {
+        let paths = vec![
+            TreeEntry::file("src/lib.rs", None),
+            TreeEntry::file("src/utils/helper.rs", None),
+            TreeEntry::file("README.md", None),
+        ];
+        let options = TreeOptions {
+            max_depth: Some(0),
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&paths, &options);
+
+        assert!(
+            result.contains("├── src/\n│   └── … (3 more)\n"),
+            "got: {}",
+            result
+        );
+        assert!(result.contains("└── README.md"));
+        assert!(!result.contains("lib.rs"));
+        assert!(!result.contains("helper.rs"));
+    }
+
+    #[test]
+    fn test_generate_tree_depth_one() {
+        let paths = vec![
+            TreeEntry::file("src/utils/helper.rs", None),
+            TreeEntry::file("src/utils/more.rs", None),
+            TreeEntry::file("src/main.rs", None),
+        ];
+        let options = TreeOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&paths, &options);
+
+        // Directories still come before files at each visible level
+        assert!(result.contains("    ├── utils/\n    │   └── … (2 more)\n    └── main.rs"));
+        assert!(!result.contains("helper.rs"));
+    }
+
+    #[test]
+    fn test_tree_depth_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_structure(temp_dir.path()).unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.arg("--tree-only")
+            .arg("--tree-depth")
+            .arg("0")
+            .arg(temp_dir.path());
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("├── docs/"))
+            .stdout(predicate::str::contains("│   └── … (3 more)"))
+            .stdout(predicate::str::contains("setup.py").not())
+            .stdout(predicate::str::contains("└── config.py"));
+    }
+
+    #[test]
+    fn test_generate_tree_json_structure() {
+        let entries = vec![
+            TreeEntry::file("src/lib.rs", Some(10)),
+            TreeEntry::file("README.md", Some(5)),
+        ];
+        let options = TreeOptions {
+            show_sizes: true,
+            ..Default::default()
+        };
+        let json = generate_tree_json(&entries, &options);
+
+        assert_eq!(json["size"], 15);
+        assert_eq!(json["children"][0]["name"], "src");
+        assert_eq!(json["children"][0]["size"], 10);
+        assert_eq!(json["children"][0]["children"][0]["name"], "lib.rs");
+        assert_eq!(json["children"][1]["name"], "README.md");
+        assert!(json["children"][1]["children"]
+            .as_array()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_generate_tree_marks_dropped_files() {
+        let entries = vec![
+            TreeEntry::file("src/lib.rs", Some(10)),
+            TreeEntry::file("src/big.rs", Some(500)).dropped(),
+        ];
+        let options = TreeOptions {
+            show_sizes: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            "Directory structure:\n└── src/ (510 B)\n    ├── big.rs (dropped) (500 B)\n    └── lib.rs           (10 B)\n\n"
+        );
+
+        let json = generate_tree_json(&entries, &options);
+        assert_eq!(json["children"][0]["children"][0]["name"], "big.rs");
+        assert_eq!(json["children"][0]["children"][0]["dropped"], true);
+        assert!(json["children"][0]["children"][1].get("dropped").is_none());
+    }
+
+    #[test]
+    fn test_tree_mark_dropped_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.rs"), "a".repeat(60)).unwrap();
+        fs::write(temp_dir.path().join("b.rs"), "b".repeat(60)).unwrap();
+
+        Command::cargo_bin("yek")
+            .unwrap()
+            .current_dir(temp_dir.path())
+            .args([
+                "--tree-header",
+                "--tree-mark-dropped",
+                "--max-size",
+                "150B",
+                ".",
+            ])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("├── a.rs\n└── b.rs (dropped)\n"))
+            .stdout(predicate::str::contains(">>>> a.rs"))
+            .stdout(predicate::str::contains(">>>> b.rs").not());
+    }
+
+    #[test]
+    fn test_generate_tree_ascii_style() {
+        let entries = vec![
+            TreeEntry::file("src/lib.rs", None),
+            TreeEntry::file("src/main.rs", None),
+            TreeEntry::file("README.md", None),
+        ];
+        let options = TreeOptions {
+            style: TreeStyle::Ascii,
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        assert_eq!(
+            result,
+            "Directory structure:\n|-- src/\n|   |-- lib.rs\n|   `-- main.rs\n`-- README.md\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_tree_markdown_style() {
+        let entries = vec![
+            TreeEntry::file("src/utils/helper.rs", None),
+            TreeEntry::file("src/main.rs", None),
+            TreeEntry::file("README.md", None),
+        ];
+        let options = TreeOptions {
+            style: TreeStyle::Markdown,
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        assert_eq!(
+            result,
+            "Directory structure:\n- src/\n  - utils/\n    - helper.rs\n  - main.rs\n- README.md\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_tree_indent() {
+        let entries = vec![
+            TreeEntry::file("src/lib.rs", None),
+            TreeEntry::file("src/main.rs", None),
+            TreeEntry::file("README.md", None),
+        ];
+        let tree = |style: TreeStyle, indent: Option<usize>| {
+            let options = TreeOptions {
+                style,
+                indent,
+                header_text: Some(String::new()),
+                ..Default::default()
+            };
+            generate_tree_with_options(&entries, &options)
+        };
+
+        // The default width is the classic look
+        assert_eq!(
+            tree(TreeStyle::Unicode, Some(4)),
+            tree(TreeStyle::Unicode, None)
+        );
+        assert_eq!(
+            tree(TreeStyle::Unicode, Some(2)),
+            "├ src/\n│ ├ lib.rs\n│ └ main.rs\n└ README.md\n\n"
+        );
+        assert_eq!(
+            tree(TreeStyle::Unicode, Some(3)),
+            "├─ src/\n│  ├─ lib.rs\n│  └─ main.rs\n└─ README.md\n\n"
+        );
+        assert_eq!(
+            tree(TreeStyle::Ascii, Some(6)),
+            "|---- src/\n|     |---- lib.rs\n|     `---- main.rs\n`---- README.md\n\n"
+        );
+        assert_eq!(
+            tree(TreeStyle::Markdown, Some(4)),
+            "- src/\n    - lib.rs\n    - main.rs\n- README.md\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_tree_custom_connectors() {
+        let entries = vec![
+            TreeEntry::file("src/a/x.rs", None),
+            TreeEntry::file("src/b.rs", None),
+            TreeEntry::file("c.rs", None),
+        ];
+        let options = TreeOptions {
+            connectors: Some(TreeConnectors {
+                branch: Some("+- ".to_string()),
+                last: Some("\\- ".to_string()),
+                vertical: Some("|  ".to_string()),
+                blank: Some("   ".to_string()),
+            }),
+            header_text: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            "+- src/\n|  +- a/\n|  |  \\- x.rs\n|  \\- b.rs\n\\- c.rs\n\n"
+        );
+
+        // Unset connectors come from the style and indent
+        let options = TreeOptions {
+            indent: Some(3),
+            connectors: Some(TreeConnectors {
+                last: Some("╰─ ".to_string()),
+                ..Default::default()
+            }),
+            header_text: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            "├─ src/\n│  ├─ a/\n│  │  ╰─ x.rs\n│  ╰─ b.rs\n╰─ c.rs\n\n"
+        );
+    }
+
+    #[test]
+    fn test_tree_indent_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(
+            temp_dir.path().join("yek.toml"),
+            "[tree_connectors]\nlast = \"`- \"\n",
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .args(["--tree-only", "--tree-indent", "2", "."]);
+        cmd.assert()
+            .success()
+            .stdout("Directory structure:\n`- src/\n  `- main.rs\n\n\n");
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .args(["--tree-only", "--tree-indent", "1", "."]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("tree_indent: must be at least 2"));
+    }
+
+    #[test]
+    fn test_generate_tree_collapses_single_directory_chains() {
+        let entries = vec![
+            TreeEntry::file("src/main/java/com/example/app/App.java", None),
+            TreeEntry::file("src/main/java/com/example/app/Util.java", None),
+            TreeEntry::file("src/main/resources/app.properties", None),
+            TreeEntry::file("docs/guide/intro.md", None),
+            TreeEntry::file("build.gradle", None),
+        ];
+        let options = TreeOptions {
+            collapse: true,
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        // Collapsing stops at src/main/ (two children) and at each directory holding a file
+        assert_eq!(
+            result,
+            "Directory structure:\n\
+             ├── docs/guide/\n\
+             │   └── intro.md\n\
+             ├── src/main/\n\
+             │   ├── java/com/example/app/\n\
+             │   │   ├── App.java\n\
+             │   │   └── Util.java\n\
+             │   └── resources/\n\
+             │       └── app.properties\n\
+             └── build.gradle\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_tree_dir_marker() {
+        let entries = vec![
+            TreeEntry::file("src/main/java/App.java", None),
+            TreeEntry::file("docs/intro.md", None),
+        ];
+        let options = TreeOptions {
+            dir_marker: Some(" [dir]".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            "Directory structure:\n\
+             ├── docs [dir]\n\
+             │   └── intro.md\n\
+             └── src [dir]\n    \
+             └── main [dir]\n        \
+             └── java [dir]\n            \
+             └── App.java\n\n"
+        );
+
+        // Collapsed directories keep '/' between names; the marker only ends the chain
+        let options = TreeOptions {
+            dir_marker: Some(String::new()),
+            collapse: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_tree_with_options(&entries, &options),
+            "Directory structure:\n\
+             ├── docs\n\
+             │   └── intro.md\n\
+             └── src/main/java\n    \
+             └── App.java\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_tree_collapse_with_sizes_and_depth() {
+        let entries = vec![
+            TreeEntry::file("a/b/c/one.rs", Some(100)),
+            TreeEntry::file("a/b/c/two.rs", Some(50)),
+            TreeEntry::file("z.rs", Some(5)),
+        ];
+        let options = TreeOptions {
+            collapse: true,
+            show_sizes: true,
+            max_depth: Some(0),
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        // The collapsed chain counts as one level and keeps the chain's total size
+        assert_eq!(
+            result,
+            "Directory structure:\n├── a/b/c/ (150 B)\n│   └── … (2 more)\n└── z.rs   (5 B)\n\n"
+        );
+    }
+
+    #[test]
+    fn test_tree_collapse_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("pkg/inner/deep")).unwrap();
+        fs::write(temp_dir.path().join("pkg/inner/deep/mod.rs"), "// mod").unwrap();
+
+        Command::cargo_bin("yek")
+            .unwrap()
+            .arg("--tree-only")
+            .arg("--tree-collapse")
+            .arg(temp_dir.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(
+                "└── pkg/inner/deep/\n    └── mod.rs",
+            ));
+    }
+
+    #[test]
+    fn test_generate_tree_sorted_by_size() {
+        let entries = vec![
+            TreeEntry::file("src/a.rs", Some(15)),
+            TreeEntry::file("src/b.rs", Some(30)),
+            TreeEntry::file("big.bin", Some(100)),
+            TreeEntry::file("Cargo.toml", Some(40)),
+            TreeEntry::file("LICENSE", Some(40)),
+        ];
+        let options = TreeOptions {
+            sort: TreeSort::Size,
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        // Directories rank by their total size; equal sizes fall back to the name
+        assert_eq!(
+            result,
+            "Directory structure:\n\
+             ├── big.bin\n\
+             ├── src/\n\
+             │   ├── b.rs\n\
+             │   └── a.rs\n\
+             ├── Cargo.toml\n\
+             └── LICENSE\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_tree_sorted_by_extension() {
+        let entries = vec![
+            TreeEntry::file("z.md", None),
+            TreeEntry::file("b.rs", None),
+            TreeEntry::file("a.toml", None),
+            TreeEntry::file("a.rs", None),
+            TreeEntry::file("Makefile", None),
+            TreeEntry::file("docs/x.md", None),
+        ];
+        let options = TreeOptions {
+            sort: TreeSort::Ext,
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        assert_eq!(
+            result,
+            "Directory structure:\n\
+             ├── docs/\n\
+             │   └── x.md\n\
+             ├── Makefile\n\
+             ├── z.md\n\
+             ├── a.rs\n\
+             ├── b.rs\n\
+             └── a.toml\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_tree_files_first() {
+        let entries = vec![
+            TreeEntry::file("src/b.rs", None),
+            TreeEntry::file("src/a/mod.rs", None),
+            TreeEntry::file("README.md", None),
+            TreeEntry::file("build.rs", None),
+        ];
+        let options = TreeOptions {
+            files_first: true,
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        assert_eq!(
+            result,
+            "Directory structure:\n\
+             ├── README.md\n\
+             ├── build.rs\n\
+             └── src/\n    \
+             ├── b.rs\n    \
+             └── a/\n        \
+             └── mod.rs\n\n"
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested_path_does_not_overflow() {
+        const DEPTH: usize = 5000;
+        let mut path = PathBuf::new();
+        for i in 0..DEPTH {
+            path.push(format!("d{i}"));
+        }
+        let entries = vec![
+            TreeEntry::file(path.join("leaf.rs"), Some(7)),
+            TreeEntry::file("top.rs", Some(3)),
+        ];
+        let options = TreeOptions {
+            show_sizes: true,
+            ..Default::default()
+        };
+
+        let tree = generate_tree_with_options(&entries, &options);
+        let lines: Vec<&str> = tree.lines().collect();
+        // Header, one line per directory, the leaf, top.rs and the blank line
+        assert_eq!(lines.len(), DEPTH + 4);
+        assert_eq!(lines[1], "├── d0/    (7 B)");
+        assert!(
+            lines[DEPTH + 1].ends_with("└── leaf.rs (7 B)"),
+            "{}",
+            lines[DEPTH + 1]
+        );
+        assert_eq!(lines[DEPTH + 2], "└── top.rs (3 B)");
+
+        let depth_limited = TreeOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        assert!(generate_tree_with_options(&entries, &depth_limited)
+            .contains(&format!("└── … ({} more)", DEPTH - 2)));
+
+        let mut json = &generate_tree_json(&entries, &options);
+        let mut levels = 0;
+        while let Some(child) = json["children"].get(0) {
+            json = child;
+            levels += 1;
+        }
+        // JSON stops nesting at MAX_JSON_TREE_DEPTH and counts what it left out
+        assert_eq!(levels, MAX_JSON_TREE_DEPTH);
+        assert_eq!(json["name"], format!("d{}", MAX_JSON_TREE_DEPTH - 1));
+        assert_eq!(json["truncated"], DEPTH - MAX_JSON_TREE_DEPTH + 1);
+
+        let order = tree_file_order(&entries, &TreeOptions::default());
+        assert_eq!(order.len(), 2);
+        assert!(order[0].ends_with("/leaf.rs"));
+    }
+
+    #[test]
+    fn test_tree_file_order_follows_rendering() {
+        let entries = vec![
+            TreeEntry::file("docs/guide.md", Some(10)),
+            TreeEntry::file("build.rs", Some(50)),
+            TreeEntry::file("./zz.txt", Some(1)),
+            TreeEntry::file("api/v1/mod.rs", Some(5)),
+            TreeEntry::dir("empty"),
+        ];
+        assert_eq!(
+            tree_file_order(&entries, &TreeOptions::default()),
+            ["api/v1/mod.rs", "docs/guide.md", "build.rs", "zz.txt"]
+        );
+        let interleaved = TreeOptions {
+            order: TreeOrder::AlphaInterleaved,
+            ..Default::default()
+        };
+        assert_eq!(
+            tree_file_order(&entries, &interleaved),
+            ["api/v1/mod.rs", "build.rs", "docs/guide.md", "zz.txt"]
+        );
+        // Depth limits only affect rendering
+        let by_size = TreeOptions {
+            sort: TreeSort::Size,
+            max_depth: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            tree_file_order(&entries, &by_size),
+            ["build.rs", "docs/guide.md", "api/v1/mod.rs", "zz.txt"]
+        );
+    }
+
+    #[test]
+    fn test_file_paths_in_display_order() {
+        let mut builder = TreeBuilder::with_root_name("repo");
+        builder
+            .add_entry(&TreeEntry::file("src/main.rs", Some(10)))
+            .add_entry(&TreeEntry::file("src/cli/args.rs", Some(20)))
+            .add_entry(&TreeEntry::link("docs", "../docs"))
+            .add_entry(&TreeEntry::file("Cargo.toml", Some(5)))
+            .add_entry(&TreeEntry::dir("target"));
+        let tree = builder.build();
+
+        // Directories first, alphabetically, as rendered; the root's name is not included
+        let paths = tree.file_paths(&TreeOptions::default());
+        assert_eq!(
+            paths,
+            [
+                Path::new("src").join("cli").join("args.rs"),
+                Path::new("src").join("main.rs"),
+                PathBuf::from("Cargo.toml"),
+                PathBuf::from("docs"),
+            ]
+        );
+        let rendered = tree.render(&TreeOptions::default());
+        let rendered: Vec<&str> = rendered
+            .lines()
+            .filter_map(|line| line.rsplit(' ').next())
+            .filter(|name| !name.ends_with('/'))
+            .collect();
+        assert_eq!(rendered, ["args.rs", "main.rs", "Cargo.toml", "../docs"]);
+
+        // Files first, and below a subtree
+        let files_first = TreeOptions {
+            files_first: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            tree.child("src").unwrap().file_paths(&files_first),
+            [PathBuf::from("main.rs"), Path::new("cli").join("args.rs")]
+        );
+        assert!(tree
+            .child("Cargo.toml")
+            .unwrap()
+            .file_paths(&files_first)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_generate_tree_interleaved() {
+        let entries = vec![
+            TreeEntry::file("docs/guide.md", None),
+            TreeEntry::file("build.rs", None),
+            TreeEntry::file("zz.txt", None),
+            TreeEntry::file("api/mod.rs", None),
+        ];
+        let options = TreeOptions {
+            order: TreeOrder::AlphaInterleaved,
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        assert_eq!(
+            result,
+            "Directory structure:\n\
+             ├── api/\n\
+             │   └── mod.rs\n\
+             ├── build.rs\n\
+             ├── docs/\n\
+             │   └── guide.md\n\
+             └── zz.txt\n\n"
+        );
+
+        // files_first has nothing to reorder once directories and files are mixed
+        let files_first = TreeOptions {
+            files_first: true,
+            ..options
+        };
+        assert_eq!(generate_tree_with_options(&entries, &files_first), result);
+    }
+
+    #[test]
+    fn test_generate_tree_case_insensitive() {
+        let entries = vec![
+            TreeEntry::file("Zebra.md", None),
+            TreeEntry::file("apple.md", None),
+            TreeEntry::file("readme.md", None),
+            TreeEntry::file("README.md", None),
+            TreeEntry::file("Lib/b.rs", None),
+            TreeEntry::file("app/a.rs", None),
+        ];
+        let options = TreeOptions {
+            sort_case: TreeSortCase::Insensitive,
+            ..Default::default()
+        };
+        let result = generate_tree_with_options(&entries, &options);
+
+        assert_eq!(
+            result,
+            "Directory structure:\n\
+             ├── app/\n\
+             │   └── a.rs\n\
+             ├── Lib/\n\
+             │   └── b.rs\n\
+             ├── apple.md\n\
+             ├── README.md\n\
+             ├── readme.md\n\
+             └── Zebra.md\n\n"
+        );
+
+        // Byte order by default: capitals first
+        let result = generate_tree_with_options(&entries, &TreeOptions::default());
+        assert!(
+            result.contains("├── Lib/\n│   └── b.rs\n├── app/\n"),
+            "{}",
+            result
+        );
+        assert!(
+            result.contains("├── README.md\n├── Zebra.md\n├── apple.md\n└── readme.md\n"),
+            "{}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_tree_order_flags() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), "//").unwrap();
+        fs::write(temp_dir.path().join("Cargo.toml"), "[package]").unwrap();
+        fs::write(temp_dir.path().join("zebra.md"), "z").unwrap();
+        let tree = |args: &[&str]| {
+            let output = Command::cargo_bin("yek")
+                .unwrap()
+                .arg("--tree-only")
+                .args(args)
+                .arg(temp_dir.path())
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        assert!(tree(&[]).contains("├── src/\n│   └── lib.rs\n├── Cargo.toml\n└── zebra.md\n"));
+        assert!(tree(&["--tree-dirs-first", "false"])
+            .contains("├── Cargo.toml\n├── zebra.md\n└── src/\n    └── lib.rs\n"));
+        assert!(tree(&[
+            "--tree-order",
+            "alpha-interleaved",
+            "--tree-sort-case",
+            "insensitive"
+        ])
+        .contains("├── Cargo.toml\n├── src/\n│   └── lib.rs\n└── zebra.md\n"));
+        assert!(tree(&["--tree-order", "alpha-interleaved"])
+            .contains("├── Cargo.toml\n├── src/\n│   └── lib.rs\n└── zebra.md\n"));
+    }
+
+    #[test]
+    fn test_sort_flags() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("small.rs"), "//").unwrap();
+        fs::write(temp_dir.path().join("large.rs"), "// ".repeat(100)).unwrap();
+
+        let output = Command::cargo_bin("yek")
+            .unwrap()
+            .args(["--tree-header", "--tree-sort", "size", "--sort", "size"])
+            .arg(temp_dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            stdout.contains("├── large.rs\n└── small.rs\n"),
+            "{}",
+            stdout
+        );
+        let large = stdout.find(">>>> large.rs").unwrap();
+        let small = stdout.find(">>>> small.rs").unwrap();
+        assert!(large < small, "{}", stdout);
+    }
+
+    #[test]
+    fn test_tree_style_flag_in_header_mode() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_structure(temp_dir.path()).unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.arg("--tree-header")
+            .arg("--tree-style")
+            .arg("markdown")
+            .arg(temp_dir.path());
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("- src/\n  - lib.rs\n  - main.rs"))
+            .stdout(predicate::str::contains("├──").not())
+            .stdout(predicate::str::contains(">>>> "));
+    }
+
+    #[test]
+    fn test_generate_tree_with_directory_entries() {
+        let entries = vec![
+            TreeEntry::file("src/main.rs", None),
+            TreeEntry::dir("migrations"),
+            TreeEntry::dir("src"), // already present via main.rs, no change
+            TreeEntry::file("Cargo.toml", None),
+        ];
+        let result = generate_tree_with_options(&entries, &TreeOptions::default());
+
+        assert_eq!(
+            result,
+            "Directory structure:\n├── migrations/\n├── src/\n│   └── main.rs\n└── Cargo.toml\n\n"
+        );
+    }
+
+    #[test]
+    fn test_tree_include_empty_dirs_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_structure(temp_dir.path()).unwrap();
+        fs::create_dir_all(temp_dir.path().join("migrations")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("assets/icons")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.arg("--tree-only")
+            .arg("--tree-include-empty-dirs")
+            .arg(temp_dir.path());
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("├── assets/\n│   └── icons/\n"))
+            .stdout(predicate::str::contains("├── migrations/\n"))
+            .stdout(predicate::str::contains("node_modules").not());
+    }
+
+    #[test]
+    fn test_tree_dir_marker_flags() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), "//").unwrap();
+        let tree = |args: &[&str]| {
+            let output = Command::cargo_bin("yek")
+                .unwrap()
+                .arg("--tree-only")
+                .args(args)
+                .arg(temp_dir.path())
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        assert!(tree(&[]).contains("└── src/\n"));
+        assert!(tree(&["--no-tree-trailing-slash"]).contains("└── src\n"));
+        assert!(tree(&["--tree-dir-marker", "[dir]"]).contains("└── src[dir]\n"));
+
+        Command::cargo_bin("yek")
+            .unwrap()
+            .args(["--no-tree-trailing-slash", "--tree-dir-marker", "[dir]"])
+            .arg(temp_dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "no_tree_trailing_slash: cannot be combined with --tree-dir-marker",
+            ));
+    }
+
+    #[test]
+    fn test_tree_min_size_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("pkg/sub")).unwrap();
+        fs::write(temp_dir.path().join("pkg/__init__.py"), "").unwrap();
+        fs::write(temp_dir.path().join("pkg/sub/__init__.py"), "#").unwrap();
+        fs::write(temp_dir.path().join("pkg/models.py"), "class Model: pass\n").unwrap();
+        let run = |extra: &[&str]| {
+            let output = Command::cargo_bin("yek")
+                .unwrap()
+                .args(["--tree-header", "--tree-min-size", "2"])
+                .args(extra)
+                .arg(temp_dir.path())
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        // Tiny files leave the tree, and so does the directory they emptied,
+        // but their content is still serialized
+        let stdout = run(&[]);
+        assert!(
+            stdout.starts_with("Directory structure:\n└── pkg/\n    └── models.py\n\n"),
+            "{}",
+            stdout
+        );
+        assert!(stdout.contains(">>>> pkg/sub/__init__.py\n#"), "{}", stdout);
+
+        let stdout = run(&["--tree-include-empty-dirs"]);
+        assert!(
+            stdout
+                .starts_with("Directory structure:\n└── pkg/\n    ├── sub/\n    └── models.py\n\n"),
+            "{}",
+            stdout
+        );
+
+        Command::cargo_bin("yek")
+            .unwrap()
+            .args(["--tree-min-size", "tiny"])
+            .arg(temp_dir.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "tree_min_size: Invalid size format",
+            ));
+    }
+
+    #[test]
+    fn test_tree_empty_dirs_hidden_by_default() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_structure(temp_dir.path()).unwrap();
+        fs::create_dir_all(temp_dir.path().join("migrations")).unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.arg("--tree-only").arg(temp_dir.path());
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("migrations").not());
+    }
+
+    #[test]
+    fn test_build_tree_public_api() {
+        let tree = build_tree(vec![
+            (PathBuf::from("src/lib.rs"), true),
+            (PathBuf::from("src/bin"), false),
+            (PathBuf::from("README.md"), true),
+        ]);
+
+        let names: Vec<&str> = tree.children().iter().map(|c| c.name()).collect();
+        assert_eq!(names, vec!["src", "README.md"]);
+
+        let src = tree.child("src").unwrap();
+        assert!(!src.is_file());
+        assert_eq!(src.descendant_count(), 2);
+        let bin = src.child("bin").unwrap();
+        assert!(!bin.is_file());
+        assert!(bin.children().is_empty());
+        assert!(src.child("lib.rs").unwrap().is_file());
+
+        assert_eq!(
+            tree.render(&TreeOptions::default()),
+            "├── src/\n│   ├── bin/\n│   └── lib.rs\n└── README.md\n"
+        );
+    }
+
+    #[test]
+    fn test_tree_builder_aggregates_sizes() {
+        let mut builder = TreeBuilder::with_root_name("repo");
+        builder
+            .add_entry(&TreeEntry::file("a/one.txt", Some(3)))
+            .add_entry(&TreeEntry::file("a/two.txt", Some(4)))
+            .add_path(Path::new("b"), false);
+        let tree = builder.build();
+
+        assert_eq!(tree.name(), "repo");
+        assert_eq!(tree.size(), Some(7));
+        assert_eq!(tree.child("a").unwrap().size(), Some(7));
+        assert_eq!(tree.child("b").unwrap().size(), None);
+    }
+
+    #[test]
+    fn test_tree_glob_respects_nested_gitignore() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/gen")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(temp_dir.path().join("src/.gitignore"), "gen/\n").unwrap();
+        fs::write(temp_dir.path().join("src/gen/out.rs"), "// generated").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .arg("--tree-only")
+            .arg("src/**/*.rs");
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("main.rs"))
+            .stdout(predicate::str::contains("out.rs").not())
+            .stdout(predicate::str::contains("gen/").not());
+    }
+
+    #[test]
+    fn test_tree_respects_directory_ignore_pattern() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("generated/deep")).unwrap();
+        fs::write(temp_dir.path().join("generated/deep/api.rs"), "// api").unwrap();
+        fs::write(temp_dir.path().join("lib.rs"), "pub fn lib() {}").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.arg("--tree-only")
+            .arg("--ignore-patterns")
+            .arg("generated/")
+            .arg("--")
+            .arg(temp_dir.path());
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("lib.rs"))
+            .stdout(predicate::str::contains("generated/").not())
+            .stdout(predicate::str::contains("api.rs").not());
+    }
+
+    #[test]
+    fn test_tree_header_reflects_ext_flags() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(temp_dir.path().join("Cargo.toml"), "[package]").unwrap();
+        fs::write(temp_dir.path().join("Cargo.lock"), "# lock").unwrap();
+        fs::write(temp_dir.path().join("notes.md"), "notes").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path()).args([
+            "--tree-header",
+            "--ext",
+            "rs,toml,lock",
+            "--no-ext",
+            "lock",
+            ".",
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("└── main.rs"))
+            .stdout(predicate::str::contains("Cargo.toml"))
+            .stdout(predicate::str::contains("Cargo.lock").not())
+            .stdout(predicate::str::contains("notes.md").not())
+            .stdout(predicate::str::contains(">>>> src/main.rs"));
+    }
+
+    #[test]
+    fn test_tree_header_reflects_filter() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/api/tests")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/empty")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(temp_dir.path().join("src/api/mod.rs"), "// api").unwrap();
+        fs::write(temp_dir.path().join("src/api/tests/t.rs"), "// t").unwrap();
+        fs::write(temp_dir.path().join("notes.md"), "notes").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.current_dir(temp_dir.path()).args([
+            "--tree-header",
+            "--tree-include-empty-dirs",
+            "--filter",
+            "src/**",
+            "!src/**/tests/**",
+            "--",
+            ".",
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("empty/"))
+            .stdout(predicate::str::contains("mod.rs"))
+            .stdout(predicate::str::contains(">>>> src/api/mod.rs"))
+            .stdout(predicate::str::contains("tests").not())
+            .stdout(predicate::str::contains("notes.md").not());
+    }
+
+    #[test]
+    fn test_tree_header_reflects_include_exclude() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/gen")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(temp_dir.path().join("src/gen/api.rs"), "// api").unwrap();
+        fs::write(temp_dir.path().join("notes.md"), "notes").unwrap();
+
+        let mut cmd = Command::cargo_bin("yek").unwrap();
+        cmd.arg("--tree-header")
+            .arg("--include")
+            .arg("*.rs")
+            .arg("--exclude")
+            .arg("gen/")
+            .arg("--")
+            .arg(temp_dir.path());
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("└── main.rs"))
+            .stdout(predicate::str::contains("gen/").not())
+            .stdout(predicate::str::contains("notes.md").not())
+            .stdout(predicate::str::contains(">>>> src/main.rs"));
+    }
 }
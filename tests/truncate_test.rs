@@ -0,0 +1,100 @@
+use yek::truncate::{
+    head_tail_lines, limit_line_length, parse_truncate_rule, truncate_limit, truncate_lines,
+};
+
+#[test]
+fn test_parse_truncate_rule() {
+    let rule = parse_truncate_rule("*.lock:20").unwrap();
+    assert!(rule.pattern.matches("Cargo.lock"));
+    assert_eq!(rule.lines, 20);
+
+    // The line count follows the last colon
+    let rule = parse_truncate_rule("dist/a:b.js: 5").unwrap();
+    assert!(rule.pattern.matches("dist/a:b.js"));
+    assert_eq!(rule.lines, 5);
+}
+
+#[test]
+fn test_parse_truncate_rule_errors() {
+    assert_eq!(
+        parse_truncate_rule("*.lock").unwrap_err().to_string(),
+        "truncate: Invalid rule '*.lock': expected <glob>:<lines>"
+    );
+    assert!(parse_truncate_rule("*.lock:many")
+        .unwrap_err()
+        .to_string()
+        .starts_with("truncate: Invalid line count in '*.lock:many'"));
+    assert!(parse_truncate_rule("[:3")
+        .unwrap_err()
+        .to_string()
+        .starts_with("truncate: Invalid pattern in '[:3'"));
+}
+
+#[test]
+fn test_truncate_limit_uses_smallest_match() {
+    let rules = vec![
+        parse_truncate_rule("*.js:50").unwrap(),
+        parse_truncate_rule("dist/*:10").unwrap(),
+    ];
+    assert_eq!(truncate_limit("dist/bundle.js", &rules), Some(10));
+    assert_eq!(truncate_limit("src/app.js", &rules), Some(50));
+    assert_eq!(truncate_limit("src/main.rs", &rules), None);
+}
+
+#[test]
+fn test_truncate_lines() {
+    let content = "one\ntwo\nthree\nfour\n";
+    assert_eq!(
+        truncate_lines(content, 2),
+        "one\ntwo\n... (truncated, 2 more lines)\n"
+    );
+    assert_eq!(
+        truncate_lines(content, 0),
+        "... (truncated, 4 more lines)\n"
+    );
+    // Files that already fit are left alone
+    assert_eq!(truncate_lines(content, 4), content);
+    assert_eq!(truncate_lines("no newline", 1), "no newline");
+}
+
+#[test]
+fn test_head_tail_lines() {
+    let content = "1\n2\n3\n4\n5\n6\n";
+    assert_eq!(
+        head_tail_lines(content, 2, 1),
+        "1\n2\n... (3 lines omitted)\n6\n"
+    );
+    assert_eq!(
+        head_tail_lines(content, 2, 0),
+        "1\n2\n... (4 lines omitted)\n"
+    );
+    assert_eq!(
+        head_tail_lines(content, 0, 2),
+        "... (4 lines omitted)\n5\n6\n"
+    );
+    // Short files are emitted whole
+    assert_eq!(head_tail_lines(content, 3, 3), content);
+    assert_eq!(head_tail_lines("", 1, 1), "");
+}
+
+#[test]
+fn test_limit_line_length_truncates() {
+    let content = "short\nabcdefghij\r\nlast line!";
+    assert_eq!(
+        limit_line_length(content, 6, false),
+        "short\nabcdef…(truncated)\r\nlast l…(truncated)"
+    );
+    // Characters are counted, not bytes
+    assert_eq!(limit_line_length("ééééé\n", 3, false), "ééé…(truncated)\n");
+    // Content that fits is left alone
+    assert_eq!(limit_line_length(content, 10, false), content);
+}
+
+#[test]
+fn test_limit_line_length_wraps() {
+    assert_eq!(
+        limit_line_length("abcdefg\nab\n", 3, true),
+        "abc\ndef\ng\nab\n"
+    );
+    assert_eq!(limit_line_length("abcdef", 3, true), "abc\ndef");
+}
@@ -0,0 +1,157 @@
+use assert_cmd::Command;
+use std::fs;
+use tempfile::tempdir;
+use yek::config::{SeparatorStyle, YekConfig};
+use yek::serialize_repo;
+use yek::unpack::{parse_output, unpack_target, write_unpacked, UnpackedFile};
+
+fn file(path: &str, content: &str) -> UnpackedFile {
+    UnpackedFile {
+        path: path.to_string(),
+        content: content.to_string(),
+    }
+}
+
+#[test]
+fn test_parse_prefix_output() {
+    let text = "Directory structure:\n└── a.rs\n\n>>>> a.rs\nfn a() {}\n\n>>>> src/b.rs\nfn b() {}";
+    assert_eq!(
+        parse_output(text, ">>>>").unwrap(),
+        vec![file("a.rs", "fn a() {}\n"), file("src/b.rs", "fn b() {}")]
+    );
+}
+
+#[test]
+fn test_parse_fenced_output_keeps_marker_lines_in_content() {
+    let text = ">>>> a.md\nquote:\n>>>> not a file\n<<<< a.md\n>>>> empty.txt\n\n<<<< empty.txt\n\n\nStats:\n  Files: 2\n";
+    assert_eq!(
+        parse_output(text, ">>>>").unwrap(),
+        vec![
+            file("a.md", "quote:\n>>>> not a file"),
+            file("empty.txt", "")
+        ]
+    );
+}
+
+#[test]
+fn test_parse_symmetric_separator() {
+    let text = "@@ a.rs\none\n@@ a.rs\n@@ b.rs\ntwo\n@@ b.rs";
+    assert_eq!(
+        parse_output(text, "@@").unwrap(),
+        vec![file("a.rs", "one"), file("b.rs", "two")]
+    );
+}
+
+#[test]
+fn test_parse_fenced_output_missing_close() {
+    let text = ">>>> a.rs\none\n<<<< a.rs\n>>>> b.rs\ntwo";
+    assert_eq!(
+        parse_output(text, ">>>>").unwrap_err().to_string(),
+        "unpack: 'b.rs' has no closing '<<<< b.rs' line"
+    );
+}
+
+#[test]
+fn test_parse_output_without_markers() {
+    assert!(parse_output("just text\n", ">>>>").unwrap().is_empty());
+}
+
+#[test]
+fn test_unpack_target_rejects_escapes() {
+    let root = tempdir().unwrap();
+    assert_eq!(
+        unpack_target(root.path(), "./src/a.rs").unwrap(),
+        root.path().join("./src/a.rs")
+    );
+    for path in ["../evil.rs", "src/../../evil.rs", "/etc/passwd", ""] {
+        let err = unpack_target(root.path(), path).unwrap_err().to_string();
+        assert!(err.contains("outside the target directory"), "{}", err);
+    }
+}
+
+#[test]
+fn test_write_unpacked_checks_all_paths_first() {
+    let root = tempdir().unwrap();
+    let files = vec![file("ok.rs", "fine"), file("../evil.rs", "bad")];
+    assert!(write_unpacked(root.path(), &files).is_err());
+    assert!(!root.path().join("ok.rs").exists());
+}
+
+#[test]
+fn test_fenced_output_round_trips() {
+    let source = tempdir().unwrap();
+    fs::create_dir_all(source.path().join("src/nested")).unwrap();
+    fs::write(source.path().join("src/lib.rs"), "pub fn lib() {}\n").unwrap();
+    fs::write(
+        source.path().join("src/nested/notes.md"),
+        ">>>> looks like a marker\n<<<< so does this\n\n",
+    )
+    .unwrap();
+    fs::write(source.path().join("empty.txt"), "").unwrap();
+
+    let config = YekConfig {
+        input_paths: vec![source.path().to_string_lossy().to_string()],
+        separator_style: SeparatorStyle::Fence,
+        tree_header: true,
+        ..Default::default()
+    };
+    let (output, _) = serialize_repo(&config).unwrap();
+
+    let target = tempdir().unwrap();
+    let files = parse_output(&output, ">>>>").unwrap();
+    assert_eq!(files.len(), 3);
+    write_unpacked(target.path(), &files).unwrap();
+    for path in ["src/lib.rs", "src/nested/notes.md", "empty.txt"] {
+        assert_eq!(
+            fs::read_to_string(target.path().join(path)).unwrap(),
+            fs::read_to_string(source.path().join(path)).unwrap(),
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn test_unpack_subcommand() {
+    let source = tempdir().unwrap();
+    fs::create_dir_all(source.path().join("src")).unwrap();
+    fs::write(source.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+
+    let output = Command::cargo_bin("yek")
+        .unwrap()
+        .current_dir(source.path())
+        .args(["--separator-style", "fence", "."])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let packed = source.path().join("packed.txt");
+    fs::write(&packed, &output.stdout).unwrap();
+
+    let target = tempdir().unwrap();
+    Command::cargo_bin("yek")
+        .unwrap()
+        .arg("unpack")
+        .arg(&packed)
+        .arg("--into")
+        .arg(target.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Unpacked 1 file(s)"));
+    assert_eq!(
+        fs::read_to_string(target.path().join("src/main.rs")).unwrap(),
+        "fn main() {}\n"
+    );
+
+    // Input without any markers is an error
+    let empty = source.path().join("empty.txt");
+    fs::write(&empty, "nothing here").unwrap();
+    Command::cargo_bin("yek")
+        .unwrap()
+        .arg("unpack")
+        .arg(&empty)
+        .arg("--into")
+        .arg(target.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("no '>>>> <path>' lines found"));
+}
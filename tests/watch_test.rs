@@ -0,0 +1,183 @@
+use notify::{
+    event::{AccessKind, CreateKind, ModifyKind},
+    Event, EventKind, RecursiveMode,
+};
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+use yek::atomic::temp_path;
+use yek::config::YekConfig;
+use yek::parallel::is_watched_path;
+use yek::watch::{is_relevant_event, watch_targets};
+
+fn config_for(root: &Path) -> YekConfig {
+    YekConfig::extend_config_with_defaults(
+        vec![root.to_string_lossy().to_string()],
+        root.join("out").to_string_lossy().to_string(),
+    )
+}
+
+#[test]
+fn test_watched_path_honors_ignore_rules() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+    fs::write(root.join(".gitignore"), "generated/\n").unwrap();
+    let config = YekConfig {
+        ignore_patterns: vec!["node_modules/**".to_string()],
+        ..config_for(root)
+    };
+
+    assert!(is_watched_path(&root.join("src/main.rs"), &config).unwrap());
+    assert!(!is_watched_path(&root.join("generated/schema.rs"), &config).unwrap());
+    assert!(!is_watched_path(&root.join("node_modules/pkg/index.js"), &config).unwrap());
+    assert!(!is_watched_path(&root.join(".git/index"), &config).unwrap());
+    assert!(!is_watched_path(&root.join(".env"), &config).unwrap());
+}
+
+#[test]
+fn test_watched_path_with_hidden() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let config = YekConfig {
+        hidden: true,
+        ..config_for(root)
+    };
+
+    assert!(is_watched_path(&root.join(".env.example"), &config).unwrap());
+    assert!(is_watched_path(&root.join(".github/workflows/ci.yml"), &config).unwrap());
+    assert!(!is_watched_path(&root.join(".git/index"), &config).unwrap());
+}
+
+#[test]
+fn test_watched_path_honors_include_and_exclude() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let config = YekConfig {
+        include: vec!["*.rs".to_string()],
+        exclude: vec!["tests/".to_string()],
+        ..config_for(root)
+    };
+
+    assert!(is_watched_path(&root.join("src/lib.rs"), &config).unwrap());
+    assert!(!is_watched_path(&root.join("README.md"), &config).unwrap());
+    assert!(!is_watched_path(&root.join("tests/e2e.rs"), &config).unwrap());
+}
+
+#[test]
+fn test_watched_path_outside_inputs() {
+    let temp = tempdir().unwrap();
+    let other = tempdir().unwrap();
+    let config = config_for(&temp.path().join("src"));
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+
+    assert!(is_watched_path(&temp.path().join("src/a.rs"), &config).unwrap());
+    assert!(!is_watched_path(&temp.path().join("Cargo.toml"), &config).unwrap());
+    assert!(!is_watched_path(&other.path().join("a.rs"), &config).unwrap());
+}
+
+#[test]
+fn test_watched_path_single_file_and_glob_inputs() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    fs::write(root.join(".env"), "KEY=1").unwrap();
+    let config = YekConfig {
+        input_paths: vec![
+            root.join(".env").to_string_lossy().to_string(),
+            root.join("src/*.rs").to_string_lossy().to_string(),
+        ],
+        ..config_for(root)
+    };
+
+    // Files named directly are watched even when hidden
+    assert!(is_watched_path(&root.join(".env"), &config).unwrap());
+    assert!(is_watched_path(&root.join("src/lib.rs"), &config).unwrap());
+    assert!(!is_watched_path(&root.join("src/lib.md"), &config).unwrap());
+    assert!(!is_watched_path(&root.join("Cargo.toml"), &config).unwrap());
+}
+
+#[test]
+fn test_watched_path_files_from() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let list = root.join("files.txt");
+    fs::write(&list, format!("{}\n", root.join("a.rs").display())).unwrap();
+    let config = YekConfig {
+        files_from: Some(list.to_string_lossy().to_string()),
+        ..config_for(root)
+    };
+
+    assert!(is_watched_path(&root.join("a.rs"), &config).unwrap());
+    assert!(!is_watched_path(&root.join("b.rs"), &config).unwrap());
+}
+
+#[test]
+fn test_relevant_event_kinds_and_output_dir() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let config = config_for(root);
+    let event = |kind: EventKind, path: &Path| Event::new(kind).add_path(path.to_path_buf());
+
+    let file = root.join("main.rs");
+    assert!(is_relevant_event(
+        &event(EventKind::Modify(ModifyKind::Any), &file),
+        &config
+    ));
+    assert!(is_relevant_event(
+        &event(EventKind::Create(CreateKind::File), &file),
+        &config
+    ));
+    assert!(!is_relevant_event(
+        &event(EventKind::Access(AccessKind::Any), &file),
+        &config
+    ));
+
+    // Writing the output must not trigger another regeneration
+    let output = root.join("out/yek-output.txt");
+    assert!(!is_relevant_event(
+        &event(EventKind::Modify(ModifyKind::Any), &output),
+        &config
+    ));
+}
+
+#[test]
+fn test_relevant_event_ignores_output_file() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let output = root.join("prompt.txt");
+    let config = YekConfig {
+        output: Some(output.to_string_lossy().to_string()),
+        ..config_for(root)
+    };
+    let event =
+        |path: &Path| Event::new(EventKind::Modify(ModifyKind::Any)).add_path(path.to_path_buf());
+
+    assert!(!is_relevant_event(&event(&output), &config));
+    assert!(!is_relevant_event(&event(&temp_path(&output)), &config));
+    assert!(is_relevant_event(&event(&root.join("main.rs")), &config));
+}
+
+#[test]
+fn test_watch_targets() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+    let config = YekConfig {
+        input_paths: vec![
+            root.join("src").to_string_lossy().to_string(),
+            root.join("Cargo.toml").to_string_lossy().to_string(),
+            root.join("src/**/*.rs").to_string_lossy().to_string(),
+        ],
+        ..config_for(root)
+    };
+
+    assert_eq!(
+        watch_targets(&config).unwrap(),
+        vec![
+            (root.join("src"), RecursiveMode::Recursive),
+            (root.to_path_buf(), RecursiveMode::NonRecursive),
+        ]
+    );
+}